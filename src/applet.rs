@@ -1,6 +1,10 @@
 #![allow(clippy::new_ret_no_self)]
 use anyhow::{Context, Result};
 use clap::{arg, Command};
+use std::io::{Read, Write};
+
+/// Size of the chunks read by applets which process their input as a stream.
+pub const STREAM_BUF_SIZE: usize = 64 * 1024;
 
 pub trait Applet {
     /// The string which will define the subcommand.
@@ -35,20 +39,59 @@ pub trait Applet {
         Some("value")
     }
 
-    /// Called by `main` to process the data in `val`
+    /// Process the data in `val` and return the result.
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>>;
 
+    /// Called by `main` to process data read from `input` and write the
+    /// result to `output`.
+    ///
+    /// The default implementation reads all the input in memory and calls `process`.
+    /// Applets which can work on chunks of data should overload it so that
+    /// they run in constant memory.
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut val = vec![];
+        input
+            .read_to_end(&mut val)
+            .context("Reading input failed")?;
+        let res = self.process(val)?;
+        output.write_all(&res)?;
+        Ok(())
+    }
+
     /* No error wrapping to make it easier to test */
     #[cfg(test)]
     fn process_test(&self, val: Vec<u8>) -> Vec<u8> {
         self.process(val).unwrap()
     }
 
+    #[cfg(test)]
+    fn process_stream_test(&self, val: Vec<u8>) -> Vec<u8> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res).unwrap();
+        res
+    }
+
     fn new() -> Box<dyn Applet>
     where
         Self: Sized;
 }
 
+/// Fill `buf` with data from `input`, retrying on interruptions.
+/// Returns the number of bytes read, which is only smaller than `buf.len()`
+/// at the end of the input.
+pub fn read_chunk(input: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("Reading input failed"),
+        }
+    }
+    Ok(len)
+}
+
 /* Helper to trim whitespace
  * Note: trim_ascii does this in Rust 1.80
  * */
@@ -4,11 +4,34 @@ use anyhow::{Context, Result};
 use base64::engine::general_purpose;
 use base64::engine::Engine;
 use clap::{arg, Command};
+use std::io::{Read, Write};
 
 pub struct B64EncApplet {
     engine: general_purpose::GeneralPurpose,
 }
 
+/// Get the alphabet to use: URL-safe, custom or standard
+fn build_alphabet(url_safe: bool, alphabet: Option<&str>) -> Result<base64::alphabet::Alphabet> {
+    if url_safe {
+        Ok(base64::alphabet::URL_SAFE)
+    } else if let Some(custom) = alphabet {
+        base64::alphabet::Alphabet::new(custom).with_context(|| "Invalid alphabet")
+    } else {
+        Ok(base64::alphabet::STANDARD)
+    }
+}
+
+impl B64EncApplet {
+    /// Build a `B64EncApplet` using the URL-safe alphabet, a custom `alphabet`, or the
+    /// standard one if none is specified.
+    pub fn with_options(url_safe: bool, alphabet: Option<&str>) -> Result<Self> {
+        let alphabet = build_alphabet(url_safe, alphabet)?;
+        Ok(Self {
+            engine: general_purpose::GeneralPurpose::new(&alphabet, general_purpose::PAD),
+        })
+    }
+}
+
 impl Applet for B64EncApplet {
     fn command(&self) -> &'static str {
         "b64"
@@ -36,25 +59,22 @@ impl Applet for B64EncApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            engine: if args.get_flag("URL") {
-                general_purpose::URL_SAFE
-            } else if args.contains_id("alphabet") {
-                let custom = base64::alphabet::Alphabet::new(
-                    args.get_one::<String>("alphabet")
-                        .with_context(|| "alphabet is not specified")?,
-                )
-                .with_context(|| "Invalid alphabet")?;
-                base64::engine::GeneralPurpose::new(&custom, base64::engine::general_purpose::PAD)
-            } else {
-                general_purpose::STANDARD
-            },
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_flag("URL"),
+            args.get_one::<String>("alphabet").map(|a| a.as_str()),
+        )?))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
         Ok(self.engine.encode(val).as_bytes().to_vec())
     }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut encoder = base64::write::EncoderWriter::new(output, &self.engine);
+        std::io::copy(input, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
 }
 
 pub struct B64DecApplet {
@@ -62,6 +82,20 @@ pub struct B64DecApplet {
     strict: bool,
 }
 
+impl B64DecApplet {
+    /// Build a `B64DecApplet`, see [`B64EncApplet::with_options`] for the alphabet.
+    /// Unless `strict` is set, decoding is lenient: invalid data is copied as is.
+    pub fn with_options(url_safe: bool, alphabet: Option<&str>, strict: bool) -> Result<Self> {
+        let engine_cfg =
+            base64::engine::GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true);
+        let alphabet = build_alphabet(url_safe, alphabet)?;
+        Ok(Self {
+            engine: general_purpose::GeneralPurpose::new(&alphabet, engine_cfg),
+            strict,
+        })
+    }
+}
+
 impl Applet for B64DecApplet {
     fn command(&self) -> &'static str {
         "d64"
@@ -94,23 +128,11 @@ impl Applet for B64DecApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        let engine_cfg =
-            base64::engine::GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true);
-        let alphabet = if args.get_flag("URL") {
-            base64::alphabet::URL_SAFE
-        } else if args.contains_id("alphabet") {
-            base64::alphabet::Alphabet::new(
-                args.get_one::<String>("alphabet")
-                    .with_context(|| "alphabet is not specified")?,
-            )
-            .with_context(|| "Invalid alphabet")?
-        } else {
-            base64::alphabet::STANDARD
-        };
-        Ok(Box::new(Self {
-            engine: general_purpose::GeneralPurpose::new(&alphabet, engine_cfg),
-            strict: false,
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_flag("URL"),
+            args.get_one::<String>("alphabet").map(|a| a.as_str()),
+            args.get_flag("strict"),
+        )?))
     }
 
     /* b64_decode. With two modes:
@@ -129,7 +151,7 @@ impl Applet for B64DecApplet {
             return Ok(decoded);
         }
 
-        let decoded = self.engine.decode(b64val);
+        let decoded = self.engine.decode(&trimmed);
         match decoded {
             Ok(res) => Ok(res),
             Err(ref e) => {
@@ -181,6 +203,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_b64_strict() {
+        let d64 = B64DecApplet::with_options(false, None, true).unwrap();
+        assert_eq!(d64.process(b"Zm9v\n".to_vec()).unwrap(), b"foo");
+        assert!(d64.process(b"Zm9v!!!!".to_vec()).is_err());
+
+        let d64 = B64DecApplet::with_options(false, None, false).unwrap();
+        assert_eq!(d64.process(b"Zm9v!!!!".to_vec()).unwrap(), b"foo!!!!");
+    }
+
     #[test]
     fn test_b64_enc() {
         let b64 = B64EncApplet {
@@ -220,6 +252,22 @@ mod tests {
         assert_eq!("FPucA9l+".as_bytes().to_vec(), b64.process_test(test));
     }
 
+    #[test]
+    fn test_b64_enc_stream() {
+        let b64 = B64EncApplet {
+            engine: general_purpose::STANDARD,
+        };
+        assert_eq!(
+            "Zm9vYmE=".as_bytes().to_vec(),
+            b64.process_stream_test("fooba".as_bytes().to_vec())
+        );
+        let data = vec![0x42; 100_000];
+        assert_eq!(
+            b64.process_stream_test(data.clone()),
+            b64.process_test(data)
+        );
+    }
+
     #[test]
     fn test_b64_url_enc() {
         let b64 = B64EncApplet {
@@ -11,6 +11,17 @@ pub struct BaseIntApplet {
     target_radix: u32,
 }
 
+impl BaseIntApplet {
+    /// Build a `BaseIntApplet` converting integers from `source_radix` (or guessed from
+    /// prefixes if `None`) to `target_radix`.
+    pub fn with_options(source_radix: Option<u32>, target_radix: u32) -> Self {
+        Self {
+            source_radix,
+            target_radix,
+        }
+    }
+}
+
 impl Applet for BaseIntApplet {
     fn command(&self) -> &'static str {
         "base"
@@ -42,10 +53,10 @@ impl Applet for BaseIntApplet {
         } else {
             10
         };
-        Ok(Box::new(Self {
-            source_radix: args.get_one::<u32>("from").copied(),
+        Ok(Box::new(Self::with_options(
+            args.get_one::<u32>("from").copied(),
             target_radix,
-        }))
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
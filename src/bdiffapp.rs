@@ -0,0 +1,339 @@
+use crate::applet::Applet;
+use crate::bgrepapp::map_input;
+use anyhow::Result;
+use clap::{arg, value_parser, Command};
+
+/// Number of identical bytes needed to consider that both inputs are in sync again
+const SYNC_LEN: usize = 8;
+
+/// Number of bytes per side on each line of the side-by-side output
+const DIFF_WIDTH: usize = 8;
+
+/// Ranges which differ between two inputs, `start1..end1` in the first one and
+/// `start2..end2` in the second one. One of them is empty for insertions and
+/// deletions.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiffRange {
+    pub start1: usize,
+    pub end1: usize,
+    pub start2: usize,
+    pub end2: usize,
+}
+
+impl DiffRange {
+    /// Number of changed bytes: the size of the largest side
+    pub fn len(&self) -> usize {
+        (self.end1 - self.start1).max(self.end2 - self.start2)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn describe(&self) -> String {
+        let (len1, len2) = (self.end1 - self.start1, self.end2 - self.start2);
+        let range = |start, end| {
+            if start == end {
+                format!("0x{:x}", start)
+            } else {
+                format!("0x{:x}-0x{:x}", start, end)
+            }
+        };
+        let what = if len1 == 0 {
+            format!("{} bytes inserted", len2)
+        } else if len2 == 0 {
+            format!("{} bytes deleted", len1)
+        } else if len1 == len2 {
+            format!("{} bytes changed", len1)
+        } else {
+            format!("{} bytes replaced by {} bytes", len1, len2)
+        };
+        format!(
+            "{} -> {}: {}",
+            range(self.start1, self.end1),
+            range(self.start2, self.end2),
+            what
+        )
+    }
+}
+
+/* Check if `a` and `b` match after skipping `di` and `dj` bytes: on SYNC_LEN
+ * bytes, or until their (common) end. */
+fn synced(a: &[u8], b: &[u8], di: usize, dj: usize) -> bool {
+    if di > a.len() || dj > b.len() {
+        return false;
+    }
+    let (a, b) = (&a[di..], &b[dj..]);
+    let n = SYNC_LEN.min(a.len()).min(b.len());
+    a[..n] == b[..n] && (n == SYNC_LEN || a.len() == b.len())
+}
+
+/* Find how many bytes to skip in `a` and `b`, which start with a difference,
+ * to get them in sync again, up to `window` bytes. Substitutions are tried
+ * first, then insertions and deletions. */
+fn resync(a: &[u8], b: &[u8], window: usize) -> (usize, usize) {
+    for d in 1..=window {
+        if synced(a, b, d, d) {
+            return (d, d);
+        }
+        for k in 0..d {
+            if synced(a, b, d, k) {
+                return (d, k);
+            }
+            if synced(a, b, k, d) {
+                return (k, d);
+            }
+        }
+    }
+    // Not found: only consider the following differing bytes as changed
+    let changed = a.iter().zip(b).take_while(|(x, y)| x != y).count();
+    (changed, changed)
+}
+
+/// Compare `a` and `b`, calling `found` for each differing range. Insertions
+/// and deletions of up to `window` bytes are detected, with larger windows
+/// being slower. If `window` is 0, bytes are compared at the same offsets.
+pub fn diff(
+    a: &[u8],
+    b: &[u8],
+    window: usize,
+    found: &mut dyn FnMut(DiffRange) -> Result<()>,
+) -> Result<()> {
+    let (mut i, mut j) = (0, 0);
+    /* Adjacent ranges are merged before being reported */
+    let mut pending: Option<DiffRange> = None;
+    loop {
+        let same = a[i..]
+            .iter()
+            .zip(&b[j..])
+            .take_while(|(x, y)| x == y)
+            .count();
+        i += same;
+        j += same;
+        let (di, dj) = if i == a.len() || j == b.len() {
+            (a.len() - i, b.len() - j)
+        } else {
+            resync(&a[i..], &b[j..], window)
+        };
+        if same > 0 || di + dj == 0 {
+            if let Some(range) = pending.take() {
+                found(range)?;
+            }
+        }
+        if di + dj == 0 {
+            return Ok(());
+        }
+        match &mut pending {
+            Some(range) => {
+                range.end1 += di;
+                range.end2 += dj;
+            }
+            None => {
+                pending = Some(DiffRange {
+                    start1: i,
+                    end1: i + di,
+                    start2: j,
+                    end2: j + dj,
+                })
+            }
+        }
+        i += di;
+        j += dj;
+    }
+}
+
+/* Offset, hex and ASCII of `data[start..end]`, `DIFF_WIDTH` bytes at most, padded */
+fn side(data: &[u8], start: usize, end: usize) -> String {
+    if start >= end {
+        return " ".repeat(8 + 2 + DIFF_WIDTH * 3 + 1 + DIFF_WIDTH + 2);
+    }
+    let data = &data[start..end.min(start + DIFF_WIDTH)];
+    let hex: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = data
+        .iter()
+        .map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    format!(
+        "{:08x}  {:<hex_width$}  |{:<width$}|",
+        start,
+        hex.join(" "),
+        ascii,
+        hex_width = DIFF_WIDTH * 3 - 1,
+        width = DIFF_WIDTH
+    )
+}
+
+pub struct BdiffApplet {
+    file1: String,
+    file2: String,
+    window: usize,
+    summary: bool,
+}
+
+impl BdiffApplet {
+    /// Build a `BdiffApplet` comparing `file1` and `file2` ("-" for stdin), see
+    /// [`diff`] for `window`. If `summary` is set, only output statistics.
+    pub fn with_options(file1: &str, file2: &str, window: usize, summary: bool) -> Self {
+        Self {
+            file1: file1.to_string(),
+            file2: file2.to_string(),
+            window,
+            summary,
+        }
+    }
+}
+
+impl Applet for BdiffApplet {
+    fn command(&self) -> &'static str {
+        "bdiff"
+    }
+
+    fn description(&self) -> &'static str {
+        "binary diff of two files"
+    }
+
+    fn returns_data(&self) -> bool {
+        false
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-w --window <size> "maximum size of detected insertions and deletions, 0 to disable")
+                    .value_parser(value_parser!(usize))
+                    .default_value("64"),
+            )
+            .arg(arg!(-s --summary "only output the number of changed bytes"))
+            .arg(arg!(<file1> "first file, - for stdin"))
+            .arg(arg!(<file2> "second file, - for stdin"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file1: String::new(),
+            file2: String::new(),
+            window: 64,
+            summary: false,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file1").unwrap(),
+            args.get_one::<String>("file2").unwrap(),
+            *args.get_one::<usize>("window").unwrap(),
+            args.get_flag("summary"),
+        )))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let a = map_input(&self.file1)?;
+        let b = map_input(&self.file2)?;
+
+        let (mut ranges, mut changed) = (0, 0);
+        diff(&a, &b, self.window, &mut |range| {
+            ranges += 1;
+            changed += range.len();
+            if self.summary {
+                return Ok(());
+            }
+            /* Print ranges on stdout directly, to avoid buffering */
+            println!("{}", range.describe());
+            let mut offset = 0;
+            while offset < range.len() {
+                let line = format!(
+                    "  {}  {}",
+                    side(&a, range.start1 + offset, range.end1),
+                    side(&b, range.start2 + offset, range.end2)
+                );
+                println!("{}", line.trim_end());
+                offset += DIFF_WIDTH;
+            }
+            Ok(())
+        })?;
+
+        if self.summary {
+            let size = a.len().max(b.len());
+            println!(
+                "{}: {} bytes, {}: {} bytes",
+                self.file1,
+                a.len(),
+                self.file2,
+                b.len()
+            );
+            println!(
+                "{} differing ranges, {} bytes changed ({:.2}%)",
+                ranges,
+                changed,
+                if size > 0 {
+                    changed as f64 * 100.0 / size as f64
+                } else {
+                    0.0
+                }
+            );
+        }
+
+        /* Return empty Vec as we output directly on stdout */
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff_all(a: &[u8], b: &[u8], window: usize) -> Vec<(usize, usize, usize, usize)> {
+        let mut res = vec![];
+        diff(a, b, window, &mut |r| {
+            res.push((r.start1, r.end1, r.start2, r.end2));
+            Ok(())
+        })
+        .unwrap();
+        res
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        assert_eq!(diff_all(a, a, 64), []);
+        assert_eq!(diff_all(b"", b"", 64), []);
+
+        // Changed, inserted and deleted bytes
+        let b = b"0123XX6789abcdeINSERTEDfghijklmnpqrstuvwxyz";
+        assert_eq!(
+            diff_all(a, b, 64),
+            [(4, 6, 4, 6), (15, 15, 15, 23), (24, 25, 32, 32)]
+        );
+        // Without resync, everything after the insertion differs
+        assert_eq!(diff_all(a, b, 0)[0], (4, 6, 4, 6));
+        assert_eq!(diff_all(a, b, 0)[1].0, 15);
+
+        // Different lengths
+        assert_eq!(diff_all(&a[..10], a, 64), [(10, 10, 10, 36)]);
+        assert_eq!(diff_all(a, b"0123", 64), [(4, 36, 4, 4)]);
+        assert_eq!(diff_all(b"0123ab", b"0123cdef", 64), [(4, 6, 4, 8)]);
+    }
+
+    #[test]
+    fn test_describe() {
+        let range = DiffRange {
+            start1: 0x10,
+            end1: 0x10,
+            start2: 0x10,
+            end2: 0x13,
+        };
+        assert_eq!(range.describe(), "0x10 -> 0x10-0x13: 3 bytes inserted");
+        assert_eq!(range.len(), 3);
+    }
+}
@@ -1,10 +1,15 @@
-use crate::applet::Applet;
-use anyhow::{bail, Context, Result};
-use clap::{arg, Command};
+use crate::applet::{Applet, FromStrWithRadix};
+use crate::hexapp::{DumpFormat, HexdumpApplet};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{arg, value_parser, Command};
 use memmap2::Mmap;
+use regex_automata::{meta, util::syntax, Anchored, Input, MatchKind, PatternID};
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeSet, VecDeque},
     fs::{self, read_dir, File},
+    io::{self, Read, Write},
+    iter,
+    ops::{Deref, Range},
     path::PathBuf,
 };
 
@@ -13,7 +18,7 @@ use regex::bytes::{Regex, RegexBuilder};
 /// Build the regex pattern with the given options.
 /// By default, the `unicode` flag is set to false, and `dot_matches_new_line` set to true.
 /// Code borrowed from gahag's bgrep <https://github.com/gahag/bgrep>
-fn build_pattern<P: AsRef<str>>(pattern: &P) -> Result<Regex> {
+pub fn build_pattern<P: AsRef<str>>(pattern: &P) -> Result<Regex> {
     let mut builder = RegexBuilder::new(pattern.as_ref());
 
     builder.unicode(false);
@@ -23,11 +28,532 @@ fn build_pattern<P: AsRef<str>>(pattern: &P) -> Result<Regex> {
         .with_context(|| "Could not build regular expression")
 }
 
+/* Error pointing at `len` characters at `pos` in `pattern` */
+fn pattern_error(pattern: &str, pos: usize, len: usize, msg: &str) -> anyhow::Error {
+    anyhow!(
+        "{} in hex pattern:\n  {}\n  {}{}",
+        msg,
+        pattern,
+        " ".repeat(pattern[..pos].chars().count()),
+        "^".repeat(len.max(1))
+    )
+}
+
+/// Convert a hex pattern to a regex for [`build_pattern`]. Besides hex bytes, YARA-like
+/// wildcards are accepted: `??` for any byte, `4?` and `?4` for nibbles, jumps of 4 to
+/// 8 bytes as `[4-8]` (or `[4]`, `[4-]`, `[-]`) and alternatives such as `(01|02 03)`.
+/// Whitespace is ignored.
+pub fn hex_pattern(pattern: &str) -> Result<String> {
+    let chars: Vec<(usize, char)> = pattern.char_indices().collect();
+    let mut res = String::new();
+    /* Positions of the open parentheses, and whether the last token started an
+     * alternative, to reject empty ones */
+    let mut groups = vec![];
+    let mut alt_start = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        match c {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                groups.push(pos);
+                res += "(?:";
+            }
+            '|' | ')' if groups.is_empty() => {
+                return Err(pattern_error(pattern, pos, 1, "Unexpected character"))
+            }
+            '|' | ')' if alt_start => {
+                return Err(pattern_error(pattern, pos, 1, "Empty alternative"))
+            }
+            '|' => res.push('|'),
+            ')' => {
+                groups.pop();
+                res.push(')');
+            }
+            '[' => {
+                let len = pattern[pos..]
+                    .find(']')
+                    .ok_or_else(|| pattern_error(pattern, pos, 1, "Unclosed jump"))?
+                    + 1;
+                let jump = &pattern[pos + 1..pos + len - 1];
+                let parse = |val: &str| -> Result<Option<usize>> {
+                    match val.trim() {
+                        "" => Ok(None),
+                        val => {
+                            Ok(Some(usize::from_str_with_radix(val).map_err(|_| {
+                                pattern_error(pattern, pos, len, "Invalid jump")
+                            })?))
+                        }
+                    }
+                };
+                let (min, max) = match jump.split_once('-') {
+                    Some((min, max)) => (parse(min)?.unwrap_or(0), parse(max)?),
+                    None => match parse(jump)? {
+                        Some(n) => (n, Some(n)),
+                        None => return Err(pattern_error(pattern, pos, len, "Empty jump")),
+                    },
+                };
+                match max {
+                    Some(max) if max < min => {
+                        return Err(pattern_error(pattern, pos, len, "Invalid jump range"))
+                    }
+                    Some(max) if max == min => res += &format!(".{{{}}}", min),
+                    Some(max) => res += &format!(".{{{},{}}}?", min, max),
+                    None => res += &format!(".{{{},}}?", min),
+                }
+                i += jump.chars().count() + 2;
+                alt_start = false;
+                continue;
+            }
+            c if c.is_ascii_hexdigit() || c == '?' => {
+                let low = match chars.get(i + 1) {
+                    Some((_, low)) if low.is_ascii_hexdigit() || *low == '?' => *low,
+                    Some(_) => return Err(pattern_error(pattern, pos, 2, "Invalid byte")),
+                    None => return Err(pattern_error(pattern, pos, 1, "Incomplete byte")),
+                };
+                match (c, low) {
+                    ('?', '?') => res.push('.'),
+                    ('?', low) => {
+                        res.push('[');
+                        for high in 0..16 {
+                            res += &format!("\\x{:x}{}", high, low);
+                        }
+                        res.push(']');
+                    }
+                    (high, '?') => res += &format!("[\\x{}0-\\x{}f]", high, high),
+                    (high, low) => res += &format!("\\x{}{}", high, low),
+                }
+                i += 2;
+                alt_start = false;
+                continue;
+            }
+            _ => return Err(pattern_error(pattern, pos, 1, "Invalid character")),
+        }
+        alt_start = c != ')';
+        i += 1;
+    }
+    if let Some(pos) = groups.pop() {
+        return Err(pattern_error(pattern, pos, 1, "Unclosed parenthesis"));
+    }
+    if res.is_empty() {
+        bail!("Empty hex pattern");
+    }
+    Ok(res)
+}
+
+/// Contents of an input file: mmapped, or read in memory for stdin.
+pub enum InputData {
+    Mapped(Mmap),
+    Stdin(Vec<u8>),
+}
+
+impl Deref for InputData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            InputData::Mapped(mmap) => mmap,
+            InputData::Stdin(buf) => buf,
+        }
+    }
+}
+
+/// Map the file at `path` in memory, or read stdin if `path` is "-".
+pub fn map_input(path: &str) -> Result<InputData> {
+    if path == "-" {
+        let mut buf = vec![];
+        io::stdin()
+            .read_to_end(&mut buf)
+            .with_context(|| "Could not read stdin")?;
+        return Ok(InputData::Stdin(buf));
+    }
+    let f = File::open(path).with_context(|| format!("Could not open {}", path))?;
+    /* Mmap is necessarily unsafe as data can change unexpectedly */
+    let mmap = unsafe { Mmap::map(&f).with_context(|| "Could not mmap input file")? };
+    Ok(InputData::Mapped(mmap))
+}
+
+/// Text encodings in which `bgrep -e` searches strings
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    /// UTF-8, which includes ASCII
+    Utf8,
+    Utf16le,
+    Utf16be,
+    Utf32le,
+    Utf32be,
+    /// All of the above
+    All,
+}
+
+impl Encoding {
+    /* Widest first, so that a short string matching in several encodings at the
+     * same offset is reported in the widest one first */
+    const ALL: [Encoding; 5] = [
+        Encoding::Utf32le,
+        Encoding::Utf32be,
+        Encoding::Utf16le,
+        Encoding::Utf16be,
+        Encoding::Utf8,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf8",
+            Encoding::Utf16le => "utf16le",
+            Encoding::Utf16be => "utf16be",
+            Encoding::Utf32le => "utf32le",
+            Encoding::Utf32be => "utf32be",
+            Encoding::All => "all",
+        }
+    }
+
+    fn encode(&self, c: char) -> Vec<u8> {
+        let mut buf = [0; 4];
+        match self {
+            Encoding::Utf16le => c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .flat_map(|u| u.to_le_bytes())
+                .collect(),
+            Encoding::Utf16be => c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .flat_map(|u| u.to_be_bytes())
+                .collect(),
+            Encoding::Utf32le => (c as u32).to_le_bytes().to_vec(),
+            Encoding::Utf32be => (c as u32).to_be_bytes().to_vec(),
+            Encoding::Utf8 | Encoding::All => c.encode_utf8(&mut buf).as_bytes().to_vec(),
+        }
+    }
+}
+
+/// Build a regex matching `text` encoded in `encoding`, ignoring the case of
+/// ASCII letters if `ignore_case` is set.
+pub fn encoded_pattern(text: &str, encoding: Encoding, ignore_case: bool) -> String {
+    let mut res = String::new();
+    for c in text.chars() {
+        let bytes = encoding.encode(c);
+        let other = if ignore_case && c.is_ascii_alphabetic() {
+            encoding.encode((c as u8 ^ 0x20) as char)
+        } else {
+            bytes.clone()
+        };
+        for (b, o) in bytes.iter().zip(other) {
+            if *b == o {
+                res += &format!("\\x{:02x}", b);
+            } else {
+                res += &format!("[\\x{:02x}\\x{:02x}]", b, o);
+            }
+        }
+    }
+    res
+}
+
+/// Patterns searched by `bgrep`. They are combined in a single regex, so that
+/// they are all found in one pass: all the patterns which match at a given
+/// offset are reported, in order.
+pub struct PatternSet {
+    regex: meta::Regex,
+    /* Same patterns reporting all the matches, to find which ones match at an
+     * offset in a single search. Only built for several patterns. */
+    overlapping: Option<meta::Regex>,
+    labels: Vec<String>,
+    /* Print the label of the pattern which matched */
+    labelled: bool,
+}
+
+impl PatternSet {
+    /// Compile `patterns`, given as (label, regex) pairs. See [`build_pattern`]
+    /// for the regex options and [`hex_pattern`] for hex patterns.
+    pub fn new(patterns: Vec<(String, String)>) -> Result<Self> {
+        if patterns.is_empty() {
+            bail!("No pattern to search");
+        }
+        let (labels, regexes): (Vec<String>, Vec<String>) = patterns.into_iter().unzip();
+        let build = |kind| {
+            /* Same options as build_pattern */
+            meta::Regex::builder()
+                .syntax(
+                    syntax::Config::new()
+                        .unicode(false)
+                        .utf8(false)
+                        .dot_matches_new_line(true),
+                )
+                .configure(meta::Config::new().utf8_empty(false).match_kind(kind))
+                .build_many(&regexes)
+                .map_err(|e| {
+                    let context = match e.pattern() {
+                        Some(pid) => format!("Invalid pattern \"{}\"", labels[pid]),
+                        None => "Could not build regular expression".to_string(),
+                    };
+                    anyhow::Error::new(e).context(context)
+                })
+        };
+        let regex = build(MatchKind::LeftmostFirst)?;
+        let overlapping = if labels.len() > 1 {
+            Some(build(MatchKind::All)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            regex,
+            overlapping,
+            labelled: labels.len() > 1,
+            labels,
+        })
+    }
+
+    /// Build a set with a single `pattern`, which is hex if `hex` is set.
+    pub fn single(pattern: &str, hex: bool) -> Result<Self> {
+        let regex = if hex {
+            hex_pattern(pattern)?
+        } else {
+            pattern.to_string()
+        };
+        Self::new(vec![(pattern.to_string(), regex)])
+    }
+
+    /// Build a set searching `text` in each of `encodings`, labelled by the
+    /// encoding name, see [`encoded_pattern`].
+    pub fn encoded(text: &str, encodings: &[Encoding], ignore_case: bool) -> Result<Self> {
+        if text.is_empty() {
+            bail!("Empty pattern");
+        }
+        let mut patterns = vec![];
+        for &encoding in encodings {
+            let expanded = if encoding == Encoding::All {
+                &Encoding::ALL[..]
+            } else {
+                &[encoding][..]
+            };
+            for enc in expanded {
+                let entry = (
+                    enc.name().to_string(),
+                    encoded_pattern(text, *enc, ignore_case),
+                );
+                if !patterns.contains(&entry) {
+                    patterns.push(entry);
+                }
+            }
+        }
+        let mut set = Self::new(patterns)?;
+        set.labelled = true;
+        Ok(set)
+    }
+
+    /* Search for pattern `index` at `start`, in the whole data to keep its
+     * context (for \b or ^) */
+    fn anchored_input<'a>(&self, data: &'a [u8], index: usize, start: usize) -> Input<'a> {
+        Input::new(data)
+            .range(start..)
+            .anchored(Anchored::Pattern(PatternID::must(index)))
+    }
+
+    /// Find the matches in `data`, as the index of the pattern which matched
+    /// and the match range. Matches of a pattern do not overlap, but they can
+    /// overlap the matches of other patterns.
+    pub fn matches<'a>(
+        &'a self,
+        data: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (usize, Range<usize>)> + 'a> {
+        let overlapping = match &self.overlapping {
+            Some(overlapping) => overlapping,
+            None => return Box::new(self.regex.find_iter(data).map(|m| (0, m.range()))),
+        };
+        /* End of the last match of each pattern, matches found at the current offset */
+        let mut ends = vec![0; self.labels.len()];
+        let mut found = VecDeque::new();
+        let mut matching = regex_automata::PatternSet::new(self.labels.len());
+        let mut pos = 0;
+        Box::new(iter::from_fn(move || {
+            while found.is_empty() && pos <= data.len() {
+                /* Skip quickly to the next offset where a pattern matches, and
+                 * find which ones match there */
+                let start = self.regex.find(Input::new(data).range(pos..))?.start();
+                pos = start + 1;
+                matching.clear();
+                let input = Input::new(data).range(start..).anchored(Anchored::Yes);
+                overlapping.which_overlapping_matches(&input, &mut matching);
+                for index in matching.iter().map(|pid| pid.as_usize()) {
+                    if start < ends[index] {
+                        continue;
+                    }
+                    if let Some(m) = self.regex.find(self.anchored_input(data, index, start)) {
+                        ends[index] = m.end();
+                        found.push_back((index, m.range()));
+                    }
+                }
+            }
+            found.pop_front()
+        }))
+    }
+
+    /* Capture groups of pattern `index` matching at `start`: their name (or
+     * number), offset and value */
+    fn groups<'a>(
+        &self,
+        data: &'a [u8],
+        index: usize,
+        start: usize,
+    ) -> Vec<(String, usize, &'a [u8])> {
+        let mut caps = self.regex.create_captures();
+        self.regex
+            .search_captures(&self.anchored_input(data, index, start), &mut caps);
+        if !caps.is_match() {
+            return vec![];
+        }
+        self.regex
+            .group_info()
+            .pattern_names(PatternID::must(index))
+            .enumerate()
+            .skip(1)
+            .filter_map(|(i, name)| {
+                caps.get_group(i).map(|group| {
+                    (
+                        name.map_or(i.to_string(), str::to_string),
+                        group.start,
+                        &data[group.range()],
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parse a list of patterns, one per line, which are regexes or hex patterns
+/// if `hex` is set. This can be overridden for each line with a `regex:` or
+/// `hex:` prefix. Empty lines and lines starting with `#` are ignored. Returns
+/// (label, regex) pairs for [`PatternSet::new`].
+pub fn parse_patterns(text: &str, hex: bool) -> Result<Vec<(String, String)>> {
+    let mut res = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let regex = if let Some(pattern) = line.strip_prefix("hex:") {
+            hex_pattern(pattern)?
+        } else if let Some(pattern) = line.strip_prefix("regex:") {
+            pattern.to_string()
+        } else if hex {
+            hex_pattern(line)?
+        } else {
+            line.to_string()
+        };
+        res.push((line.to_string(), regex));
+    }
+    Ok(res)
+}
+
+/// What `bgrep` prints for each file. By default, the offset of each match.
+#[derive(Default, Clone, Copy)]
+pub struct MatchOutput {
+    /// Number of bytes to dump before each match
+    pub before: usize,
+    /// Number of bytes to dump after each match
+    pub after: usize,
+    /// Print the matched bytes in hex after their offset
+    pub hex: bool,
+    /// Only print the matched bytes, concatenated
+    pub raw: bool,
+    /// Print the capture groups, in hex
+    pub groups: bool,
+    /// Only print the number of matches
+    pub count: bool,
+    /// Only print the name of files which match
+    pub files_only: bool,
+    /// Stop after this number of matches in each file
+    pub max_count: Option<usize>,
+}
+
 pub struct BgrepApplet {
     paths: Option<Vec<String>>,
-    pattern: Option<Regex>,
+    patterns: Option<PatternSet>,
     verbose: bool,
     recursive: bool,
+    output: MatchOutput,
+}
+
+impl BgrepApplet {
+    /// Build a `BgrepApplet` looking for `patterns` in `paths`, with subfolders if
+    /// `recursive` is set.
+    pub fn with_options(
+        paths: Vec<String>,
+        patterns: PatternSet,
+        verbose: bool,
+        recursive: bool,
+        output: MatchOutput,
+    ) -> Self {
+        Self {
+            paths: Some(paths),
+            patterns: Some(patterns),
+            verbose,
+            recursive,
+            output,
+        }
+    }
+
+    /* Print the matches found in `data`, prefixed by `name` if `many` is set */
+    fn report(&self, name: &str, many: bool, data: &[u8], out: &mut dyn Write) -> Result<()> {
+        let patterns = self.patterns.as_ref().unwrap();
+        let opts = &self.output;
+        let prefix = if many {
+            format!("{}: ", name)
+        } else {
+            String::new()
+        };
+        let context = opts.before > 0 || opts.after > 0;
+        let dumper = HexdumpApplet::with_options(DumpFormat::Canonical, 16, None, 0, None, false)?;
+
+        let mut count = 0;
+        for (index, range) in patterns
+            .matches(data)
+            .take(opts.max_count.unwrap_or(usize::MAX))
+        {
+            let matched = &data[range.clone()];
+            count += 1;
+            if opts.files_only {
+                writeln!(out, "{}", name)?;
+                break;
+            } else if opts.count {
+                continue;
+            } else if opts.raw {
+                out.write_all(matched)?;
+                continue;
+            }
+
+            if context && count > 1 {
+                writeln!(out, "--")?;
+            }
+            let mut line = format!("{}0x{:x}", prefix, range.start);
+            if patterns.labelled {
+                line += &format!(" [{}]", patterns.labels[index]);
+            }
+            if opts.hex {
+                line += &format!(": {}", hex::encode(matched));
+            }
+            writeln!(out, "{}", line)?;
+            if opts.groups {
+                for (name, offset, group) in patterns.groups(data, index, range.start) {
+                    writeln!(out, "  {} @ 0x{:x}: {}", name, offset, hex::encode(group))?;
+                }
+            }
+            if context {
+                let start = range.start.saturating_sub(opts.before);
+                let end = (range.end + opts.after).min(data.len());
+                for line in dumper.format_lines(start as u64, &data[start..end]) {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+        }
+        if opts.count {
+            writeln!(out, "{}{}", prefix, count)?;
+        }
+        Ok(())
+    }
 }
 
 impl Applet for BgrepApplet {
@@ -47,10 +573,70 @@ impl Applet for BgrepApplet {
         Command::new(self.command())
             .about(self.description())
             .arg(arg!(-v --verbose  "verbose"))
-            .arg(arg!(-x --hex  "pattern is hex"))
+            .arg(arg!(-x --hex  "pattern is hex, with wildcards: ?? 4? [2-4] (01|02)"))
+            .arg(
+                arg!(-e --encoding <encodings> "pattern is text, searched in these encodings (comma-separated)")
+                    .value_parser(clap::builder::EnumValueParser::<Encoding>::new())
+                    .value_delimiter(',')
+                    .conflicts_with("hex"),
+            )
+            .arg(arg!(-i --"ignore-case" "ignore the case of ASCII letters").conflicts_with("hex"))
             .arg(arg!(-r --recursive "search in subfolders"))
-            .arg(arg!(<pattern>  "pattern to search"))
-            .arg(arg!(<path>    "file(s) or directory(ies) to search in").num_args(1..))
+            .arg(
+                arg!(-A --after <n> "dump <n> bytes after each match")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                arg!(-B --before <n> "dump <n> bytes before each match")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                arg!(-C --context <n> "dump <n> bytes before and after each match")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(arg!(-p --"print-hex" "print the matched bytes in hex"))
+            .arg(arg!(-g --groups "print the capture groups in hex"))
+            .arg(
+                arg!(--raw "only print the matched bytes, concatenated").conflicts_with_all([
+                    "after",
+                    "before",
+                    "context",
+                    "print-hex",
+                    "groups",
+                ]),
+            )
+            .arg(
+                arg!(-c --count "only print the number of matches").conflicts_with_all([
+                    "after",
+                    "before",
+                    "context",
+                    "print-hex",
+                    "groups",
+                    "raw",
+                ]),
+            )
+            .arg(
+                arg!(-l --"files-with-matches" "only print the names of files which match")
+                    .conflicts_with_all([
+                        "after",
+                        "before",
+                        "context",
+                        "print-hex",
+                        "groups",
+                        "raw",
+                        "count",
+                    ]),
+            )
+            .arg(
+                arg!(-m --"max-count" <n> "stop after <n> matches in each file")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                arg!(-f --file <file> "read patterns from <file>, one per line, prefixed by hex: or regex: to override -x")
+                    .conflicts_with_all(["encoding", "ignore-case"]),
+            )
+            .arg(arg!([pattern]  "pattern to search, unless -f is used").required_unless_present("file"))
+            .arg(arg!([path]    "file(s) or directory(ies) to search in").num_args(1..))
     }
 
     fn arg_or_stdin(&self) -> Option<&'static str> {
@@ -60,48 +646,72 @@ impl Applet for BgrepApplet {
     fn new() -> Box<dyn Applet> {
         Box::new(Self {
             paths: None,
-            pattern: None,
+            patterns: None,
             verbose: false,
             recursive: false,
+            output: MatchOutput::default(),
         })
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        let filenames = args
+        let mut filenames: Vec<String> = args
             .get_many::<String>("path")
-            .unwrap()
+            .unwrap_or_default()
             .map(|s| s.to_string())
             .collect();
-        let pattern_val = args.get_one::<String>("pattern").unwrap();
+        let pattern_val = args.get_one::<String>("pattern");
 
-        /* Convert hex pattern to "\x00" format if needed */
-        let mut s = String::new();
-        let final_pat = if args.get_flag("hex") {
-            if pattern_val.len() % 2 != 0 {
-                bail!("hex pattern length is not even");
-            }
-            for i in 0..(pattern_val.len() / 2) {
-                s += "\\x";
-                s += &pattern_val[i * 2..i * 2 + 2];
+        let patterns = if let Some(patterns_file) = args.get_one::<String>("file") {
+            /* No pattern is given: the first argument is a path */
+            if let Some(path) = pattern_val {
+                filenames.insert(0, path.to_string());
             }
-            s.as_str()
+            let text = fs::read_to_string(patterns_file)
+                .with_context(|| format!("Could not read patterns from {}", patterns_file))?;
+            PatternSet::new(parse_patterns(&text, args.get_flag("hex"))?)?
         } else {
-            pattern_val
+            let pattern = pattern_val.with_context(|| "No pattern to search")?;
+            let ignore_case = args.get_flag("ignore-case");
+            if let Some(encodings) = args.get_many::<Encoding>("encoding") {
+                let encodings: Vec<Encoding> = encodings.copied().collect();
+                PatternSet::encoded(pattern, &encodings, ignore_case)?
+            } else if ignore_case {
+                PatternSet::new(vec![(pattern.to_string(), format!("(?i:{})", pattern))])?
+            } else {
+                PatternSet::single(pattern, args.get_flag("hex"))?
+            }
         };
+        if filenames.is_empty() {
+            bail!("No file to search in");
+        }
 
-        let pattern = build_pattern(&final_pat)?;
+        let context = args.get_one::<usize>("context").copied().unwrap_or(0);
+        let output = MatchOutput {
+            before: args.get_one::<usize>("before").copied().unwrap_or(context),
+            after: args.get_one::<usize>("after").copied().unwrap_or(context),
+            hex: args.get_flag("print-hex"),
+            raw: args.get_flag("raw"),
+            groups: args.get_flag("groups"),
+            count: args.get_flag("count"),
+            files_only: args.get_flag("files-with-matches"),
+            max_count: args.get_one::<usize>("max-count").copied(),
+        };
 
-        Ok(Box::new(Self {
-            paths: Some(filenames),
-            pattern: Some(pattern),
-            verbose: args.get_flag("verbose"),
-            recursive: args.get_flag("recursive"),
-        }))
+        Ok(Box::new(Self::with_options(
+            filenames,
+            patterns,
+            args.get_flag("verbose"),
+            args.get_flag("recursive"),
+            output,
+        )))
     }
 
     fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
         let input_paths = self.paths.as_ref().unwrap();
         let many = input_paths.len() > 1 || self.recursive;
+        /* Print matches on stdout directly, to avoid buffering */
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
         // Make sure we keep the search order based on what is given first as the input
         for input_path in input_paths.iter() {
             // A BTreeSet ensure we get a consistant order
@@ -130,17 +740,7 @@ impl Applet for BgrepApplet {
                                 Mmap::map(&f).with_context(|| "Could not mmap input file")?
                             };
 
-                            let regex = self.pattern.as_ref().unwrap();
-                            let matches = regex.find_iter(&data);
-
-                            /* Print offsets on stdout directly, to avoid buffering */
-                            for m in matches {
-                                if many {
-                                    println!("{}: 0x{:x}", path.to_string_lossy(), m.start());
-                                } else {
-                                    println!("0x{:x}", m.start());
-                                }
-                            }
+                            self.report(&path.to_string_lossy(), many, &data, &mut out)?;
                         }
                         Err(e) => eprintln!("Could not open {}: {}", path.to_string_lossy(), e),
                     }
@@ -186,3 +786,153 @@ impl Applet for BgrepApplet {
         Ok(Vec::<u8>::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_all(pattern: &str, data: &[u8]) -> Vec<(usize, usize)> {
+        let regex = build_pattern(&hex_pattern(pattern).unwrap()).unwrap();
+        regex
+            .find_iter(data)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hex_pattern() {
+        assert_eq!(hex_pattern("454c46").unwrap(), "\\x45\\x4c\\x46");
+        let data = b"\x7fELF\x02\x01\x01\n\x00ELF\x01\x02";
+        assert_eq!(find_all("45 4c ?? 0?", data), [(1, 5), (9, 13)]);
+        assert_eq!(find_all("45 4c 46 ?2", data), [(1, 5)]);
+        assert_eq!(find_all("7f [3] 02", data), [(0, 5)]);
+        assert_eq!(find_all("46 [1-5] 45", data), [(3, 10)]);
+        assert_eq!(find_all("46 [1-4] 45", data), []);
+        assert_eq!(find_all("46 [-] 02", data), [(3, 5), (11, 14)]);
+        assert_eq!(find_all("46 (01 02|02 (01|03))", data), [(3, 6), (11, 14)]);
+    }
+
+    #[test]
+    fn test_hex_pattern_errors() {
+        let err = |pattern| hex_pattern(pattern).unwrap_err().to_string();
+        assert_eq!(
+            err("41 4g"),
+            "Invalid byte in hex pattern:\n  41 4g\n     ^^"
+        );
+        assert_eq!(
+            err("41 [4-2] 42"),
+            "Invalid jump range in hex pattern:\n  41 [4-2] 42\n     ^^^^^"
+        );
+        assert!(err("41 (42|)").starts_with("Empty alternative"));
+        assert!(err("41 (42").starts_with("Unclosed parenthesis"));
+        assert!(err("41 42)").starts_with("Unexpected character"));
+        assert!(err("41 [2").starts_with("Unclosed jump"));
+        assert!(err("41 []").starts_with("Empty jump"));
+        assert!(err("41 4").starts_with("Incomplete byte"));
+        assert!(err("zz").starts_with("Invalid character"));
+        assert!(err(" ").starts_with("Empty hex pattern"));
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        let text = "# ELF\n7f454c46\n\nregex:PK\\x03\\x04\n  hex:4d 5a  \n";
+        assert_eq!(
+            parse_patterns(text, true).unwrap(),
+            [
+                ("7f454c46".to_string(), "\\x7f\\x45\\x4c\\x46".to_string()),
+                ("regex:PK\\x03\\x04".to_string(), "PK\\x03\\x04".to_string()),
+                ("hex:4d 5a".to_string(), "\\x4d\\x5a".to_string()),
+            ]
+        );
+        assert_eq!(parse_patterns("a.c", false).unwrap()[0].1, "a.c");
+        assert!(parse_patterns("hex:zz", false).is_err());
+    }
+
+    #[test]
+    fn test_pattern_set() {
+        let patterns = PatternSet::new(parse_patterns("abc\nb(c)d\nbcd", false).unwrap()).unwrap();
+        let data = b"xxabcdbcd";
+        let found: Vec<_> = patterns.matches(data).collect();
+        assert_eq!(
+            found,
+            [(0, 2..5), (1, 3..6), (2, 3..6), (1, 6..9), (2, 6..9)]
+        );
+        assert_eq!(
+            patterns.groups(data, 1, 6),
+            [("1".to_string(), 7, &b"c"[..])]
+        );
+
+        let single = PatternSet::single("(?<x>b)", false).unwrap();
+        assert_eq!(single.groups(data, 0, 3), [("x".to_string(), 3, &b"b"[..])]);
+        assert!(PatternSet::new(vec![]).is_err());
+        assert!(PatternSet::new(vec![("(".to_string(), "(".to_string())]).is_err());
+
+        // Patterns keep their context, and all the ones matching at an offset are reported
+        let patterns = PatternSet::new(parse_patterns("\\bbar\nba\n^f", false).unwrap()).unwrap();
+        let found: Vec<_> = patterns.matches(b"foobar").collect();
+        assert_eq!(found, [(2, 0..1), (1, 3..5)]);
+        let found: Vec<_> = patterns.matches(b"foo bar").collect();
+        assert_eq!(found, [(2, 0..1), (0, 4..7), (1, 4..6)]);
+        // Each pattern finds the same matches as when searched alone
+        let text = "hex:0? ??\nhex:?1 02\n\\bab\nb.";
+        let patterns = PatternSet::new(parse_patterns(text, false).unwrap()).unwrap();
+        let data: Vec<u8> = b"ab\x01\x02ab xab\x00\x00\x00\x01\x02".repeat(10);
+        let found: Vec<_> = patterns.matches(&data).collect();
+        for (index, (_, regex)) in parse_patterns(text, false).unwrap().iter().enumerate() {
+            let alone: Vec<_> = build_pattern(regex)
+                .unwrap()
+                .find_iter(&data)
+                .map(|m| (index, m.range()))
+                .collect();
+            assert!(!alone.is_empty());
+            assert_eq!(
+                found
+                    .iter()
+                    .filter(|(i, _)| *i == index)
+                    .cloned()
+                    .collect::<Vec<_>>(),
+                alone
+            );
+        }
+
+        let patterns = PatternSet::new(parse_patterns("(a)b\nab(c)", false).unwrap()).unwrap();
+        assert_eq!(
+            patterns.groups(b"xabc", 1, 1),
+            [("1".to_string(), 3, &b"c"[..])]
+        );
+    }
+
+    #[test]
+    fn test_encoded_pattern() {
+        assert_eq!(
+            encoded_pattern("a\u{e9}", Encoding::Utf8, false),
+            "\\x61\\xc3\\xa9"
+        );
+        assert_eq!(
+            encoded_pattern("a1", Encoding::Utf16le, true),
+            "[\\x61\\x41]\\x00\\x31\\x00"
+        );
+        assert_eq!(
+            encoded_pattern("A", Encoding::Utf32be, true),
+            "\\x00\\x00\\x00[\\x41\\x61]"
+        );
+        // Surrogate pair
+        assert_eq!(
+            encoded_pattern("\u{1f600}", Encoding::Utf16be, false),
+            "\\xd8\\x3d\\xde\\x00"
+        );
+
+        let patterns = PatternSet::encoded("Key", &[Encoding::All], true).unwrap();
+        assert_eq!(patterns.labels.len(), 5);
+        let data = b"xKEY\x00k\x00e\x00y\x00";
+        let found: Vec<_> = patterns
+            .matches(data)
+            .map(|(index, range)| (patterns.labels[index].as_str(), range))
+            .collect();
+        assert_eq!(
+            found,
+            [("utf8", 1..4), ("utf16be", 4..10), ("utf16le", 5..11)]
+        );
+        assert!(PatternSet::encoded("", &[Encoding::Utf8], false).is_err());
+    }
+}
@@ -0,0 +1,916 @@
+use crate::applet::Applet;
+use crate::bgrepapp::{build_pattern, map_input};
+use crate::flateapp::{inflate_prefix, FlateFormat};
+use anyhow::{Context, Result};
+use clap::{arg, Command};
+use crc::{Crc, CRC_32_ISO_HDLC};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+
+enum Check {
+    /// Check a signature hit, given the data starting at the hit: `None` if the
+    /// header is not valid, else the length of the file, if it can be determined.
+    Header(fn(&[u8]) -> Option<Option<usize>>),
+    /// For formats whose start is known from their trailer: `header` checks the
+    /// data starting at a hit, and `files` maps the start of the files found in
+    /// the whole data to their length, in a single pass.
+    Trailer {
+        header: fn(&[u8]) -> bool,
+        files: fn(&[u8]) -> BTreeMap<usize, usize>,
+    },
+}
+
+struct Signature {
+    name: &'static str,
+    /// Extension of carved files
+    ext: &'static str,
+    /// Regular expression matching the header, see [`build_pattern`]
+    pattern: &'static str,
+    check: Check,
+}
+
+static CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        name: "ELF",
+        ext: "elf",
+        pattern: r"\x7fELF[\x01\x02][\x01\x02]\x01",
+        check: Check::Header(elf_len),
+    },
+    Signature {
+        name: "PE",
+        ext: "exe",
+        pattern: "MZ",
+        check: Check::Header(pe_len),
+    },
+    Signature {
+        name: "Mach-O",
+        ext: "macho",
+        pattern: r"\xfe\xed\xfa[\xce\xcf]|[\xce\xcf]\xfa\xed\xfe",
+        check: Check::Header(macho_len),
+    },
+    Signature {
+        name: "Mach-O fat",
+        ext: "macho",
+        pattern: r"\xca\xfe\xba\xbe",
+        check: Check::Header(macho_fat_len),
+    },
+    Signature {
+        name: "ZIP",
+        ext: "zip",
+        pattern: r"PK\x03\x04",
+        check: Check::Trailer {
+            header: zip_header,
+            files: zip_files,
+        },
+    },
+    Signature {
+        name: "gzip",
+        ext: "gz",
+        pattern: r"\x1f\x8b\x08",
+        check: Check::Header(gzip_len),
+    },
+    Signature {
+        name: "PNG",
+        ext: "png",
+        pattern: r"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR",
+        check: Check::Header(|val| Some(png_len(val))),
+    },
+    Signature {
+        name: "JPEG",
+        ext: "jpg",
+        pattern: r"\xff\xd8\xff[\xc0-\xfe]",
+        check: Check::Header(|val| Some(jpeg_len(val))),
+    },
+    Signature {
+        name: "PDF",
+        ext: "pdf",
+        pattern: r"%PDF-[12]\.[0-9]",
+        check: Check::Header(|val| Some(pdf_len(val))),
+    },
+    Signature {
+        name: "SQLite",
+        ext: "sqlite",
+        pattern: r"SQLite format 3\x00",
+        check: Check::Header(sqlite_len),
+    },
+    Signature {
+        name: "squashfs",
+        ext: "squashfs",
+        pattern: "hsqs|sqsh",
+        check: Check::Header(squashfs_len),
+    },
+    Signature {
+        name: "cpio",
+        ext: "cpio",
+        // newc, newc with CRC, and odc (portable ASCII) formats
+        pattern: "07070[12][0-9A-Fa-f]{104}|070707[0-7]{70}",
+        check: Check::Header(|val| Some(cpio_len(val))),
+    },
+    Signature {
+        name: "uImage",
+        ext: "uimage",
+        pattern: r"\x27\x05\x19\x56",
+        check: Check::Header(uimage_len),
+    },
+    Signature {
+        name: "DER certificate",
+        ext: "der",
+        // SEQUENCE { SEQUENCE { [0] { INTEGER version } ...
+        pattern: r"\x30\x82..\x30\x82..\xa0\x03\x02\x01[\x00-\x02]",
+        check: Check::Header(der_len),
+    },
+];
+
+/// Maximum distance from a PDF header at which its `%%EOF` is searched, so that
+/// false positives stay cheap.
+const MAX_SEARCH_LEN: usize = 256 << 20;
+
+/* Helpers to parse headers: all of them return None if the data is too short */
+
+fn uint(val: &[u8], offset: usize, size: usize, big_endian: bool) -> Option<usize> {
+    let bytes = val.get(offset..offset.checked_add(size)?)?;
+    let fold = |acc: u64, b: &u8| (acc << 8) | *b as u64;
+    let n = if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    };
+    usize::try_from(n).ok()
+}
+
+fn find(val: &[u8], needle: &[u8]) -> Option<usize> {
+    val.windows(needle.len()).position(|w| w == needle)
+}
+
+fn elf_len(val: &[u8]) -> Option<Option<usize>> {
+    let is64 = val[4] == 2;
+    let be = val[5] == 2;
+    let rd = |offset, size| uint(val, offset, size, be);
+    let (ehsize, word) = if is64 { (64, 8) } else { (52, 4) };
+    if rd(0x14, 4)? != 1 || rd(ehsize - 12, 2)? != ehsize {
+        return None;
+    }
+    // Offsets of e_phoff, e_shoff and the following fields depend on the word size
+    let phoff = rd(0x18 + word, word)?;
+    let shoff = rd(0x18 + 2 * word, word)?;
+    let fields = 0x18 + 3 * word + 6;
+    let (phentsize, phnum) = (rd(fields, 2)?, rd(fields + 2, 2)?);
+    let (shentsize, shnum) = (rd(fields + 4, 2)?, rd(fields + 6, 2)?);
+    Some(
+        elf_content_len(val, is64, be, phoff, phentsize, phnum)
+            .map(|end| end.max(ehsize).max(shoff.saturating_add(shentsize * shnum))),
+    )
+}
+
+/* End of the segments of an ELF file */
+fn elf_content_len(
+    val: &[u8],
+    is64: bool,
+    be: bool,
+    phoff: usize,
+    phentsize: usize,
+    phnum: usize,
+) -> Option<usize> {
+    let mut end = phoff.checked_add(phentsize * phnum)?;
+    for i in 0..phnum {
+        let ph = phoff + i * phentsize;
+        let (offset, filesz) = if is64 {
+            (uint(val, ph + 8, 8, be)?, uint(val, ph + 0x20, 8, be)?)
+        } else {
+            (uint(val, ph + 4, 4, be)?, uint(val, ph + 0x10, 4, be)?)
+        };
+        end = end.max(offset.saturating_add(filesz));
+    }
+    Some(end)
+}
+
+fn pe_len(val: &[u8]) -> Option<Option<usize>> {
+    let pe = uint(val, 0x3c, 4, false)?;
+    if pe > 0x1000 || val.get(pe..pe + 4)? != b"PE\x00\x00" {
+        return None;
+    }
+    let rd = |offset, size| uint(val, offset, size, false);
+    let nsections = rd(pe + 6, 2)?;
+    let opt_size = rd(pe + 20, 2)?;
+    let opt = pe + 24;
+    let sections = opt + opt_size;
+    let mut end = sections + nsections * 40;
+    for i in 0..nsections {
+        let section = sections + i * 40;
+        let (raw_size, raw_ptr) = match (rd(section + 16, 4), rd(section + 20, 4)) {
+            (Some(size), Some(ptr)) => (size, ptr),
+            _ => return Some(None),
+        };
+        if raw_size > 0 {
+            end = end.max(raw_ptr + raw_size);
+        }
+    }
+    /* Authenticode signatures are not part of a section and are usually
+     * appended: the security directory contains a file offset */
+    let dirs = match rd(opt, 2) {
+        Some(0x10b) => opt + 96,
+        Some(0x20b) => opt + 112,
+        _ => return Some(Some(end)),
+    };
+    if rd(dirs - 4, 4).is_some_and(|n| n > 4) && opt_size >= dirs + 5 * 8 - opt {
+        if let (Some(offset), Some(size)) = (rd(dirs + 32, 4), rd(dirs + 36, 4)) {
+            end = end.max(offset + size);
+        }
+    }
+    Some(Some(end))
+}
+
+fn macho_len(val: &[u8]) -> Option<Option<usize>> {
+    let be = val[0] == 0xfe;
+    let is64 = val[0] == 0xcf || val[3] == 0xcf;
+    let rd = |offset, size| uint(val, offset, size, be);
+    let filetype = rd(12, 4)?;
+    let ncmds = rd(16, 4)?;
+    let sizeofcmds = rd(20, 4)?;
+    if !(1..=12).contains(&filetype) || ncmds == 0 || sizeofcmds < ncmds * 8 {
+        return None;
+    }
+    let mut pos = if is64 { 32 } else { 28 };
+    let mut end = pos + sizeofcmds;
+    for _ in 0..ncmds {
+        let (cmd, cmdsize) = match (rd(pos, 4), rd(pos + 4, 4)) {
+            (Some(cmd), Some(size)) if size >= 8 => (cmd, size),
+            _ => return Some(None),
+        };
+        let range = match cmd {
+            // LC_SEGMENT
+            0x1 => (rd(pos + 32, 4), rd(pos + 36, 4)),
+            // LC_SEGMENT_64
+            0x19 => (rd(pos + 40, 8), rd(pos + 48, 8)),
+            // LC_SYMTAB: string table
+            0x2 => (rd(pos + 16, 4), rd(pos + 20, 4)),
+            // LC_CODE_SIGNATURE
+            0x1d => (rd(pos + 8, 4), rd(pos + 12, 4)),
+            _ => (Some(0), Some(0)),
+        };
+        match range {
+            (Some(offset), Some(size)) => end = end.max(offset.saturating_add(size)),
+            _ => return Some(None),
+        }
+        pos += cmdsize;
+    }
+    Some(Some(end))
+}
+
+fn macho_fat_len(val: &[u8]) -> Option<Option<usize>> {
+    // Java class files use the same magic, with their version instead of nfat_arch
+    let nfat_arch = uint(val, 4, 4, true)?;
+    if !(1..=20).contains(&nfat_arch) {
+        return None;
+    }
+    let mut end = 8 + nfat_arch * 20;
+    for i in 0..nfat_arch {
+        let arch = 8 + i * 20;
+        match (uint(val, arch + 8, 4, true), uint(val, arch + 12, 4, true)) {
+            (Some(offset), Some(size)) => end = end.max(offset + size),
+            _ => return Some(None),
+        }
+    }
+    Some(Some(end))
+}
+
+fn zip_header(val: &[u8]) -> bool {
+    // "version needed to extract"
+    uint(val, 4, 2, false).is_some_and(|version| version <= 100)
+}
+
+/* Find the end of central directory records: the central directory ends right
+ * before, and its offset is relative to the start of the archive, unless data
+ * was prepended. For each start, the first matching record is kept. */
+fn zip_files(val: &[u8]) -> BTreeMap<usize, usize> {
+    let mut files = BTreeMap::new();
+    let mut pos = 0;
+    while let Some(i) = find(&val[pos..], b"PK\x05\x06") {
+        let eocd = pos + i;
+        let cd_size = uint(val, eocd + 12, 4, false);
+        let cd_offset = uint(val, eocd + 16, 4, false);
+        let comment_len = uint(val, eocd + 20, 2, false);
+        if let (Some(size), Some(offset), Some(comment_len)) = (cd_size, cd_offset, comment_len) {
+            if let Some(start) = eocd.checked_sub(offset + size) {
+                files
+                    .entry(start)
+                    .or_insert(eocd - start + 22 + comment_len);
+            }
+        }
+        pos = eocd + 4;
+    }
+    files
+}
+
+fn gzip_len(val: &[u8]) -> Option<Option<usize>> {
+    // Reserved flags must be zero
+    if val.get(3)? & 0xe0 != 0 {
+        return None;
+    }
+    Some(
+        inflate_prefix(val, FlateFormat::Gzip)
+            .ok()
+            .map(|(_, len)| len),
+    )
+}
+
+fn png_len(val: &[u8]) -> Option<usize> {
+    let mut pos = 8;
+    loop {
+        let len = uint(val, pos, 4, true)?;
+        let kind = val.get(pos + 4..pos + 8)?;
+        // Length, type, data and CRC
+        pos = pos.checked_add(len + 12)?;
+        if kind == b"IEND" {
+            return Some(pos);
+        }
+    }
+}
+
+fn jpeg_len(val: &[u8]) -> Option<usize> {
+    let mut pos = 2;
+    loop {
+        if *val.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *val.get(pos + 1)?;
+        match marker {
+            // Fill byte
+            0xff => pos += 1,
+            // EOI
+            0xd9 => return Some(pos + 2),
+            // Markers without length: TEM and RSTn
+            0x01 | 0xd0..=0xd7 => pos += 2,
+            _ => {
+                pos += 2 + uint(val, pos + 2, 2, true)?;
+                // SOS is followed by entropy coded data, in which 0xff is
+                // escaped as 0xff00, and restart markers can appear.
+                if marker == 0xda {
+                    while *val.get(pos)? != 0xff || matches!(*val.get(pos + 1)?, 0x00 | 0xd0..=0xd7)
+                    {
+                        pos += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/* Check if `val` starts, after whitespace, with an object ("1 0 obj") or a
+ * cross-reference table */
+fn is_pdf_update(val: &[u8]) -> bool {
+    let mut rest = val.trim_ascii_start();
+    if rest.starts_with(b"xref") {
+        return true;
+    }
+    for _ in 0..2 {
+        let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return false;
+        }
+        let after = &rest[digits..];
+        rest = after.trim_ascii_start();
+        if rest.len() == after.len() {
+            return false;
+        }
+    }
+    rest.starts_with(b"obj")
+}
+
+fn pdf_len(val: &[u8]) -> Option<usize> {
+    /* Incremental updates add "%%EOF" markers: stop at the first one which is
+     * not followed by an update, before the next PDF if any */
+    let val = &val[..val.len().min(MAX_SEARCH_LEN)];
+    let val = &val[..find(&val[1..], b"%PDF-").map_or(val.len(), |i| i + 1)];
+    let mut pos = 0;
+    loop {
+        let eof = pos + find(&val[pos..], b"%%EOF")? + 5;
+        let eol = match val.get(eof..eof + 2) {
+            Some(b"\r\n") => 2,
+            _ if matches!(val.get(eof), Some(b'\r' | b'\n')) => 1,
+            _ => 0,
+        };
+        pos = eof + eol;
+        if !is_pdf_update(&val[pos..]) || find(&val[pos..], b"%%EOF").is_none() {
+            return Some(pos);
+        }
+    }
+}
+
+fn sqlite_len(val: &[u8]) -> Option<Option<usize>> {
+    let page_size = match uint(val, 16, 2, true)? {
+        1 => 65536,
+        size if size.is_power_of_two() && size >= 512 => size,
+        _ => return None,
+    };
+    // The page count is only valid if the "version-valid-for" number matches
+    // the change counter
+    let counter = uint(val, 24, 4, true);
+    let valid = counter.is_some() && counter == uint(val, 92, 4, true);
+    let pages = uint(val, 28, 4, true).filter(|n| valid && *n > 0);
+    Some(pages.map(|n| n * page_size))
+}
+
+fn squashfs_len(val: &[u8]) -> Option<Option<usize>> {
+    let be = val[0] == b's';
+    let major = uint(val, 28, 2, be)?;
+    if !(1..=4).contains(&major) {
+        return None;
+    }
+    // Only the version 4 superblock is supported: bytes_used
+    if major != 4 {
+        return Some(None);
+    }
+    Some(uint(val, 40, 8, be))
+}
+
+fn cpio_len(val: &[u8]) -> Option<usize> {
+    let field = |offset: usize, len: usize, radix: u32| -> Option<usize> {
+        let digits = std::str::from_utf8(val.get(offset..offset + len)?).ok()?;
+        usize::from_str_radix(digits, radix).ok()
+    };
+    let mut pos = 0;
+    loop {
+        let header = val.get(pos..pos + 6)?;
+        let (name, data_end) = if header == b"070707" {
+            // odc: octal fields, no padding
+            let namesize = field(pos + 59, 6, 8)?;
+            let filesize = field(pos + 65, 11, 8)?;
+            let name = pos + 76;
+            (
+                name..name + namesize,
+                (name + namesize).checked_add(filesize)?,
+            )
+        } else if header == b"070701" || header == b"070702" {
+            // newc: hex fields, name and data are padded to 4 bytes
+            let filesize = field(pos + 6 + 6 * 8, 8, 16)?;
+            let namesize = field(pos + 6 + 11 * 8, 8, 16)?;
+            let name = pos + 110;
+            let data = (name + namesize + 3) & !3;
+            (name..name + namesize, (data + filesize + 3) & !3)
+        } else {
+            return None;
+        };
+        pos = data_end;
+        if val.get(name)? == b"TRAILER!!!\x00" {
+            return Some(pos);
+        }
+    }
+}
+
+fn uimage_len(val: &[u8]) -> Option<Option<usize>> {
+    let mut header = val.get(..64)?.to_vec();
+    let hcrc = uint(&header, 4, 4, true)?;
+    header[4..8].fill(0);
+    if CRC32.checksum(&header) as usize != hcrc {
+        return None;
+    }
+    Some(Some(64 + uint(val, 12, 4, true)?))
+}
+
+fn der_len(val: &[u8]) -> Option<Option<usize>> {
+    let len = 4 + uint(val, 2, 2, true)?;
+    let tbs_len = 4 + uint(val, 6, 2, true)?;
+    // The signature algorithm and value follow the TBS certificate
+    if tbs_len + 4 >= len {
+        return None;
+    }
+    Some(Some(len))
+}
+
+/// File found by [`find_signatures`]
+pub struct Hit {
+    pub offset: usize,
+    pub name: &'static str,
+    pub ext: &'static str,
+    /// Length of the file, from its headers. It can be larger than the
+    /// available data if the file is truncated.
+    pub length: Option<usize>,
+}
+
+/// Find known file formats in `val`, sorted by offset. Once a file is found
+/// and its length known, files of the same format inside it are ignored.
+pub fn find_signatures(val: &[u8]) -> Result<Vec<Hit>> {
+    let mut hits = vec![];
+    for sig in SIGNATURES {
+        let regex = build_pattern(&sig.pattern)?;
+        /* Built on the first hit of formats checked from their trailer */
+        let mut files = None;
+        let mut pos = 0;
+        while let Some(m) = regex.find_at(val, pos) {
+            let offset = m.start();
+            pos = offset + 1;
+            let length = match sig.check {
+                Check::Header(check) => check(&val[offset..]),
+                Check::Trailer {
+                    header,
+                    files: find_files,
+                } => header(&val[offset..]).then(|| {
+                    files
+                        .get_or_insert_with(|| find_files(val))
+                        .get(&offset)
+                        .copied()
+                }),
+            };
+            if let Some(length) = length {
+                if let Some(len) = length {
+                    pos = pos.max(offset.saturating_add(len)).min(val.len());
+                }
+                hits.push(Hit {
+                    offset,
+                    name: sig.name,
+                    ext: sig.ext,
+                    length,
+                });
+            }
+        }
+    }
+    hits.sort_by_key(|h| h.offset);
+    Ok(hits)
+}
+
+pub struct CarveApplet {
+    file: String,
+    output_dir: Option<PathBuf>,
+}
+
+impl CarveApplet {
+    /// Build a `CarveApplet` looking for known file formats in `file` ("-" for
+    /// stdin). If `output_dir` is set, files with a known length are carved to it.
+    pub fn with_options(file: &str, output_dir: Option<PathBuf>) -> Self {
+        Self {
+            file: file.to_string(),
+            output_dir,
+        }
+    }
+}
+
+impl Applet for CarveApplet {
+    fn command(&self) -> &'static str {
+        "carve"
+    }
+
+    fn description(&self) -> &'static str {
+        "identify and carve embedded files"
+    }
+
+    fn returns_data(&self) -> bool {
+        false
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        let formats: Vec<&str> = SIGNATURES.iter().map(|s| s.name).collect();
+        Command::new(self.command())
+            .about(self.description())
+            .after_help(format!("Supported formats: {}", formats.join(", ")))
+            .arg(arg!(-o --output <dir> "carve files with a known length to <dir>, as OFFSET.EXT"))
+            .arg(arg!(<file> "file to scan, - for stdin"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file: String::new(),
+            output_dir: None,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            args.get_one::<String>("output").map(PathBuf::from),
+        )))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let data = map_input(&self.file)?;
+
+        if let Some(dir) = &self.output_dir {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Could not create {}", dir.to_string_lossy()))?;
+        }
+
+        for hit in find_signatures(&data)? {
+            let length = match hit.length {
+                Some(len) => len,
+                None => {
+                    println!("0x{:x}: {}", hit.offset, hit.name);
+                    continue;
+                }
+            };
+            let end = hit.offset.saturating_add(length).min(data.len());
+            println!(
+                "0x{:x}: {}, {} bytes{}",
+                hit.offset,
+                hit.name,
+                length,
+                if end - hit.offset < length {
+                    " (truncated)"
+                } else {
+                    ""
+                }
+            );
+            if let Some(dir) = &self.output_dir {
+                let path = dir.join(format!("{:08x}.{}", hit.offset, hit.ext));
+                fs::write(&path, &data[hit.offset..end])
+                    .with_context(|| format!("Could not write {}", path.to_string_lossy()))?;
+            }
+        }
+
+        /* Return empty Vec as we output directly on stdout */
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flateapp::DeflateApplet;
+
+    fn png() -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [
+            (&b"IHDR"[..], &[0u8; 13][..]),
+            (b"IDAT", b"data"),
+            (b"IEND", b""),
+        ] {
+            png.extend((data.len() as u32).to_be_bytes());
+            png.extend(kind);
+            png.extend(data);
+            png.extend(b"CRC!");
+        }
+        png
+    }
+
+    fn zip() -> Vec<u8> {
+        let mut zip = b"PK\x03\x04\x14\x00".to_vec();
+        zip.extend([0; 20]);
+        zip.extend(b"\x01\x00\x00\x00a");
+        let cd_offset = zip.len() as u32;
+        zip.extend(b"PK\x01\x02\x14\x00\x14\x00");
+        zip.extend([0; 20]);
+        zip.extend(b"\x01\x00");
+        zip.extend([0; 16]);
+        zip.extend(b"a");
+        let cd_size = zip.len() as u32 - cd_offset;
+        zip.extend(b"PK\x05\x06\x00\x00\x00\x00\x01\x00\x01\x00");
+        zip.extend(cd_size.to_le_bytes());
+        zip.extend(cd_offset.to_le_bytes());
+        zip.extend(b"\x00\x00");
+        zip
+    }
+
+    fn cpio() -> Vec<u8> {
+        let mut cpio = vec![];
+        for (name, data) in [("file", &b"hello"[..]), ("TRAILER!!!", b"")] {
+            cpio.extend(b"070701");
+            for field in [
+                0,
+                0o100644,
+                0,
+                0,
+                1,
+                0,
+                data.len(),
+                0,
+                0,
+                0,
+                0,
+                name.len() + 1,
+                0,
+            ] {
+                cpio.extend(format!("{:08x}", field).as_bytes());
+            }
+            cpio.extend(name.as_bytes());
+            cpio.push(0);
+            cpio.resize((cpio.len() + 3) & !3, 0);
+            cpio.extend(data);
+            cpio.resize((cpio.len() + 3) & !3, 0);
+        }
+        cpio
+    }
+
+    #[test]
+    fn test_find_signatures() {
+        let gzip = DeflateApplet::with_options(FlateFormat::Gzip, 6, None, 0)
+            .unwrap()
+            .process(b"test".to_vec())
+            .unwrap();
+        let mut blob = b"garbage MZ".to_vec();
+        let mut expected = vec![];
+        for (name, file) in [
+            ("PNG", png()),
+            ("ZIP", zip()),
+            ("gzip", gzip),
+            ("cpio", cpio()),
+        ] {
+            expected.push((blob.len(), name, Some(file.len())));
+            blob.extend(&file);
+            blob.extend(b"garbage");
+        }
+        // Truncated
+        let png = png();
+        expected.push((blob.len(), "PNG", None));
+        blob.extend(&png[..png.len() - 12]);
+
+        let hits: Vec<_> = find_signatures(&blob)
+            .unwrap()
+            .into_iter()
+            .map(|h| (h.offset, h.name, h.length))
+            .collect();
+        assert_eq!(hits, expected);
+    }
+
+    fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn test_elf_len() {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(64 + 56, 0);
+        put(&mut elf, 0x14, &1u32.to_le_bytes());
+        put(&mut elf, 0x20, &64u64.to_le_bytes());
+        put(&mut elf, 0x28, &0x200u64.to_le_bytes());
+        // ehsize, phentsize, phnum, shentsize and shnum
+        for (i, field) in [64u16, 56, 1, 64, 2].iter().enumerate() {
+            put(&mut elf, 0x34 + 2 * i, &field.to_le_bytes());
+        }
+        put(&mut elf, 64 + 0x20, &0x300u64.to_le_bytes());
+        assert_eq!(elf_len(&elf), Some(Some(0x300)));
+        put(&mut elf, 64 + 0x20, &0x100u64.to_le_bytes());
+        assert_eq!(elf_len(&elf), Some(Some(0x280)));
+        put(&mut elf, 0x14, &2u32.to_le_bytes());
+        assert_eq!(elf_len(&elf), None);
+    }
+
+    #[test]
+    fn test_pe_len() {
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x40 + 24 + 0xe0 + 40, 0);
+        put(&mut pe, 0x3c, &0x40u32.to_le_bytes());
+        put(&mut pe, 0x40, b"PE\x00\x00");
+        put(&mut pe, 0x46, &1u16.to_le_bytes());
+        put(&mut pe, 0x54, &0xe0u16.to_le_bytes());
+        let opt = 0x40 + 24;
+        put(&mut pe, opt, &0x10bu16.to_le_bytes());
+        put(&mut pe, opt + 92, &16u32.to_le_bytes());
+        let section = opt + 0xe0;
+        put(&mut pe, section + 16, &0x200u32.to_le_bytes());
+        put(&mut pe, section + 20, &0x400u32.to_le_bytes());
+        assert_eq!(pe_len(&pe), Some(Some(0x600)));
+        // Appended signature
+        put(&mut pe, opt + 96 + 32, &0x600u32.to_le_bytes());
+        put(&mut pe, opt + 96 + 36, &0x100u32.to_le_bytes());
+        assert_eq!(pe_len(&pe), Some(Some(0x700)));
+        put(&mut pe, 0x40, b"NE");
+        assert_eq!(pe_len(&pe), None);
+    }
+
+    #[test]
+    fn test_macho_len() {
+        let mut macho = b"\xcf\xfa\xed\xfe".to_vec();
+        macho.resize(32 + 72, 0);
+        put(&mut macho, 12, &2u32.to_le_bytes());
+        put(&mut macho, 16, &1u32.to_le_bytes());
+        put(&mut macho, 20, &72u32.to_le_bytes());
+        put(&mut macho, 32, &0x19u32.to_le_bytes());
+        put(&mut macho, 36, &72u32.to_le_bytes());
+        put(&mut macho, 32 + 40, &0x1000u64.to_le_bytes());
+        put(&mut macho, 32 + 48, &0x2000u64.to_le_bytes());
+        assert_eq!(macho_len(&macho), Some(Some(0x3000)));
+        // Truncated load commands
+        assert_eq!(macho_len(&macho[..40]), Some(None));
+        put(&mut macho, 12, &0u32.to_le_bytes());
+        assert_eq!(macho_len(&macho), None);
+    }
+
+    #[test]
+    fn test_zip_files() {
+        let zip = zip();
+        assert!(zip_header(&zip));
+        assert!(!zip_header(b"PK\x03\x04\xff\x00"));
+        let mut blob = zip.clone();
+        blob.extend(b"garbage");
+        blob.extend(&zip);
+        assert_eq!(
+            zip_files(&blob),
+            BTreeMap::from([(0, zip.len()), (zip.len() + 7, zip.len())])
+        );
+        // Without its end of central directory, the first archive is not found
+        let mut blob = zip[..zip.len() - 22].to_vec();
+        blob.extend(b"garbage");
+        blob.extend(&zip);
+        assert_eq!(
+            zip_files(&blob),
+            BTreeMap::from([(zip.len() - 15, zip.len())])
+        );
+    }
+
+    #[test]
+    fn test_gzip_len() {
+        let gzip = DeflateApplet::with_options(FlateFormat::Gzip, 6, None, 0)
+            .unwrap()
+            .process(b"test".to_vec())
+            .unwrap();
+        let mut blob = gzip.clone();
+        blob.extend(b"garbage");
+        assert_eq!(gzip_len(&blob), Some(Some(gzip.len())));
+        assert_eq!(gzip_len(&gzip[..gzip.len() - 1]), Some(None));
+    }
+
+    #[test]
+    fn test_pdf_len() {
+        let pdf = b"%PDF-1.4\n1 0 obj\n<<>>\nendobj\nxref\n0 1\ntrailer\n<<>>\n%%EOF\n";
+        let update = b"2 0 obj\n<<>>\nendobj\nxref\n0 1\ntrailer\n<<>>\n%%EOF\r\n";
+        let mut blob = pdf.to_vec();
+        blob.extend(b"garbage %%EOF");
+        assert_eq!(pdf_len(&blob), Some(pdf.len()));
+
+        // Incremental update
+        let mut updated = pdf.to_vec();
+        updated.extend(update);
+        let mut blob = updated.clone();
+        blob.extend(b"garbage %%EOF");
+        assert_eq!(pdf_len(&blob), Some(updated.len()));
+        blob.truncate(updated.len());
+        blob.extend(pdf);
+        assert_eq!(pdf_len(&blob), Some(updated.len()));
+
+        assert_eq!(pdf_len(&pdf[..pdf.len() - 2]), None);
+        assert!(is_pdf_update(b"\n12 0 obj"));
+        assert!(!is_pdf_update(b"\n12 0obj"));
+        assert!(!is_pdf_update(b"12 obj"));
+    }
+
+    #[test]
+    fn test_sqlite_len() {
+        let mut sqlite = b"SQLite format 3\x00".to_vec();
+        sqlite.resize(100, 0);
+        put(&mut sqlite, 16, &4096u16.to_be_bytes());
+        put(&mut sqlite, 24, &5u32.to_be_bytes());
+        put(&mut sqlite, 28, &3u32.to_be_bytes());
+        put(&mut sqlite, 92, &5u32.to_be_bytes());
+        assert_eq!(sqlite_len(&sqlite), Some(Some(3 * 4096)));
+        // Stale page count
+        put(&mut sqlite, 92, &4u32.to_be_bytes());
+        assert_eq!(sqlite_len(&sqlite), Some(None));
+        put(&mut sqlite, 16, &1000u16.to_be_bytes());
+        assert_eq!(sqlite_len(&sqlite), None);
+    }
+
+    #[test]
+    fn test_squashfs_len() {
+        let mut squashfs = b"hsqs".to_vec();
+        squashfs.resize(96, 0);
+        put(&mut squashfs, 28, &4u16.to_le_bytes());
+        put(&mut squashfs, 40, &0x1234u64.to_le_bytes());
+        assert_eq!(squashfs_len(&squashfs), Some(Some(0x1234)));
+        put(&mut squashfs, 28, &3u16.to_le_bytes());
+        assert_eq!(squashfs_len(&squashfs), Some(None));
+        put(&mut squashfs, 28, &5u16.to_le_bytes());
+        assert_eq!(squashfs_len(&squashfs), None);
+    }
+
+    #[test]
+    fn test_cpio_len() {
+        let cpio = cpio();
+        let mut blob = cpio.clone();
+        blob.extend(b"garbage");
+        assert_eq!(cpio_len(&blob), Some(cpio.len()));
+        assert_eq!(cpio_len(&cpio[..cpio.len() - 4]), None);
+    }
+
+    #[test]
+    fn test_der_len() {
+        let der = b"\x30\x82\x01\x00\x30\x82\x00\xf0\xa0\x03\x02\x01\x02";
+        assert_eq!(der_len(der), Some(Some(0x104)));
+        // The TBS certificate must leave room for the signature
+        assert_eq!(der_len(b"\x30\x82\x01\x00\x30\x82\x00\xfe"), None);
+    }
+
+    #[test]
+    fn test_jpeg_len() {
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x04JF\xff\xda\x00\x02\x12\xff\x00\xff\xd0\x34\xff\xd9";
+        assert_eq!(jpeg_len(jpeg), Some(jpeg.len()));
+        assert_eq!(jpeg_len(&jpeg[..jpeg.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_uimage_len() {
+        let mut uimage = b"\x27\x05\x19\x56".to_vec();
+        uimage.extend([0; 60]);
+        uimage[15] = 10;
+        assert_eq!(uimage_len(&uimage), None);
+        let crc = CRC32.checksum(&uimage);
+        uimage[4..8].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(uimage_len(&uimage), Some(Some(74)));
+    }
+}
@@ -0,0 +1,227 @@
+use crate::applet::Applet;
+use anyhow::{anyhow, bail, Context, Result};
+
+/// An applet, configured with its arguments, as part of a chain.
+pub struct Stage {
+    name: String,
+    applet: Box<dyn Applet>,
+    // Value given as argument, which replaces the stage input
+    value: Option<Vec<u8>>,
+}
+
+impl Stage {
+    /// Returns true if the stage needs the output of the previous stage (or stdin)
+    pub fn reads_input(&self) -> bool {
+        self.applet.arg_or_stdin().is_some() && self.value.is_none()
+    }
+
+    pub fn returns_data(&self) -> bool {
+        self.applet.returns_data()
+    }
+}
+
+/* Split a stage definition into arguments, like a (very) simple shell would:
+ * - arguments are separated by whitespace
+ * - single quotes preserve everything up to the next single quote
+ * - double quotes preserve everything except backslash-escaped chars
+ * - backslash escapes the next char outside of single quotes
+ */
+pub fn split_args(stage: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut cur = String::new();
+    // Keep track of empty quoted args like ''
+    let mut in_arg = false;
+    let mut chars = stage.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => cur.push(c),
+                        None => bail!("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => cur.push(
+                            chars
+                                .next()
+                                .ok_or_else(|| anyhow!("unterminated double quote"))?,
+                        ),
+                        Some(c) => cur.push(c),
+                        None => bail!("unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                cur.push(chars.next().ok_or_else(|| anyhow!("trailing backslash"))?);
+            }
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut cur));
+                    in_arg = false;
+                }
+            }
+            c => {
+                in_arg = true;
+                cur.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(cur);
+    }
+    Ok(args)
+}
+
+/// Parse a single stage, such as `["inflate", "-z"]`, with the matching applet's arguments parser.
+fn parse_stage(apps: &[Box<dyn Applet>], args: &[String]) -> Result<Stage> {
+    let name = args.first().ok_or_else(|| anyhow!("empty stage"))?;
+    let app = apps
+        .iter()
+        .find(|a| a.command() == name)
+        .ok_or_else(|| anyhow!("unknown applet \"{}\"", name))?;
+    let matches = app
+        .clap_command()
+        .try_get_matches_from(args)
+        .context("invalid arguments")?;
+    let applet = app.parse_args(&matches)?;
+    /* The value argument can depend on the options */
+    let value = applet
+        .arg_or_stdin()
+        .and_then(|argname| matches.get_one::<String>(argname))
+        .map(|v| v.as_bytes().to_vec());
+    Ok(Stage {
+        name: name.to_string(),
+        applet,
+        value,
+    })
+}
+
+/// Parse all the stages of a chain, given as strings such as `inflate -z`.
+pub fn parse_stages<S: AsRef<str>>(apps: &[Box<dyn Applet>], stages: &[S]) -> Result<Vec<Stage>> {
+    let mut split_stages = Vec::with_capacity(stages.len());
+    for (i, stage) in stages.iter().enumerate() {
+        split_stages.push(
+            split_args(stage.as_ref())
+                .with_context(|| format!("stage {} (\"{}\")", i + 1, stage.as_ref()))?,
+        );
+    }
+    parse_split_stages(apps, &split_stages)
+}
+
+/// Parse all the stages of a chain, given as lists of arguments, checking that
+/// each stage can be fed the output of the previous one.
+pub fn parse_split_stages(apps: &[Box<dyn Applet>], stages: &[Vec<String>]) -> Result<Vec<Stage>> {
+    if stages.is_empty() {
+        bail!("no stage given");
+    }
+    let mut res: Vec<Stage> = Vec::with_capacity(stages.len());
+    for (i, stage) in stages.iter().enumerate() {
+        let parsed = parse_stage(apps, stage)
+            .with_context(|| format!("stage {} (\"{}\")", i + 1, stage.join(" ")))?;
+        if i > 0 && parsed.applet.arg_or_stdin().is_none() {
+            bail!(
+                "stage {} ({}) does not take its data from stdin",
+                i + 1,
+                parsed.name
+            );
+        }
+        /* The value would replace the output of the previous stage */
+        if i > 0 && parsed.value.is_some() {
+            bail!(
+                "stage {} ({}) takes its data from the previous stage, no value can be given",
+                i + 1,
+                parsed.name
+            );
+        }
+        if i < stages.len() - 1 && !parsed.returns_data() {
+            bail!(
+                "stage {} ({}) does not output data to pass to the next stage",
+                i + 1,
+                parsed.name
+            );
+        }
+        res.push(parsed);
+    }
+    Ok(res)
+}
+
+/// Run `input` through all the stages and return the output of the last one.
+pub fn run_stages(stages: &[Stage], input: Vec<u8>) -> Result<Vec<u8>> {
+    let mut data = input;
+    for (i, stage) in stages.iter().enumerate() {
+        let stage_input = match &stage.value {
+            Some(value) => value.clone(),
+            None => data,
+        };
+        data = stage
+            .applet
+            .process(stage_input)
+            .with_context(|| format!("stage {} ({}) failed", i + 1, stage.name))?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::b64app::B64DecApplet;
+    use crate::bgrepapp::BgrepApplet;
+    use crate::hexapp::{HexApplet, UnHexApplet};
+    use crate::xorapp::XorApplet;
+
+    fn apps() -> Vec<Box<dyn Applet>> {
+        vec![
+            HexApplet::new(),
+            UnHexApplet::new(),
+            B64DecApplet::new(),
+            XorApplet::new(),
+            BgrepApplet::new(),
+        ]
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("inflate -z").unwrap(), ["inflate", "-z"]);
+        assert_eq!(
+            split_args(" xor  -x 'a b' \"c\\\"d\" e\\ f ''").unwrap(),
+            ["xor", "-x", "a b", "c\"d", "e f", ""]
+        );
+        assert!(split_args("xor 'ab").is_err());
+        assert!(split_args("xor \"ab").is_err());
+    }
+
+    #[test]
+    fn test_chain() {
+        let stages = parse_stages(&apps(), &["unhex", "xor -x 01", "hex"]).unwrap();
+        assert_eq!(run_stages(&stages, b"414243".to_vec()).unwrap(), b"404342");
+    }
+
+    #[test]
+    fn test_chain_value() {
+        let stages = parse_stages(&apps(), &["unhex 41", "hex"]).unwrap();
+        assert!(!stages[0].reads_input());
+        assert_eq!(run_stages(&stages, vec![]).unwrap(), b"41");
+    }
+
+    #[test]
+    fn test_chain_errors() {
+        assert!(parse_stages::<&str>(&apps(), &[]).is_err());
+        assert!(parse_stages(&apps(), &["nope"]).is_err());
+        assert!(parse_stages(&apps(), &["xor --invalid"]).is_err());
+        assert!(parse_stages(&apps(), &["hex", "bgrep a /tmp"]).is_err());
+        assert!(parse_stages(&apps(), &["hex", "xor -x 41 ABC"]).is_err());
+
+        let stages = parse_stages(&apps(), &["unhex", "unhex -s", "hex"]).unwrap();
+        let err = run_stages(&stages, b"6c".to_vec()).unwrap_err();
+        assert_eq!(err.to_string(), "stage 2 (unhex) failed");
+    }
+}
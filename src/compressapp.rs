@@ -0,0 +1,580 @@
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{arg, value_parser, Command};
+use liblzma::stream::{Filters, LzmaOptions, Stream};
+use std::io::{self, Read, Write};
+
+/// Compression formats supported by `compress` and `decompress`
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Codec {
+    Xz,
+    /// LZMA "alone" format, with a 13 bytes header
+    Lzma,
+    /// LZMA1 stream without header, see `--props`
+    LzmaRaw,
+    Bzip2,
+    Zstd,
+    /// LZ4 frame format
+    Lz4,
+    /// LZ4 block, without any header
+    Lz4Block,
+    Brotli,
+}
+
+impl Codec {
+    /// Default and maximum compression levels
+    fn levels(&self) -> Option<(u32, u32)> {
+        match self {
+            Codec::Xz | Codec::Lzma | Codec::LzmaRaw => Some((6, 9)),
+            Codec::Bzip2 => Some((9, 9)),
+            Codec::Zstd => Some((3, 22)),
+            Codec::Brotli => Some((11, 11)),
+            Codec::Lz4 | Codec::Lz4Block => None,
+        }
+    }
+}
+
+// lc=3, lp=0, pb=2, 8 MiB dictionary: the defaults of the LZMA SDK
+const DEFAULT_LZMA_PROPS: &str = "5d00008000";
+
+/// Detect the codec of compressed data from its magic bytes. Raw LZMA,
+/// LZ4 blocks and brotli cannot be detected.
+pub fn detect_codec(val: &[u8]) -> Option<Codec> {
+    if val.starts_with(b"\xfd7zXZ\x00") {
+        Some(Codec::Xz)
+    } else if val.starts_with(b"BZh") && val.len() > 3 && (b'1'..=b'9').contains(&val[3]) {
+        Some(Codec::Bzip2)
+    } else if val.starts_with(b"\x28\xb5\x2f\xfd")
+        // Skippable frames, LZ4 uses them too but they are mostly found with zstd
+        || (val.len() >= 4 && val[0] & 0xf0 == 0x50 && val[1..4] == *b"\x2a\x4d\x18")
+    {
+        Some(Codec::Zstd)
+    } else if val.starts_with(b"\x04\x22\x4d\x18") {
+        Some(Codec::Lz4)
+    } else if val.len() >= 13 && val[0] == 0x5d && {
+        // Usual properties, with a power of 2 dictionary size
+        let dict_size = u32::from_le_bytes([val[1], val[2], val[3], val[4]]);
+        dict_size.is_power_of_two() && dict_size >= 4096
+    } {
+        Some(Codec::Lzma)
+    } else {
+        None
+    }
+}
+
+/* Build raw LZMA1 filters from the properties found in LZMA headers:
+ * (pb * 5 + lp) * 9 + lc, followed by the dictionary size (LE). */
+fn lzma_raw_filters(props: &[u8], preset: u32) -> Result<Filters> {
+    let mut opts = LzmaOptions::new_preset(preset)?;
+    opts.literal_context_bits((props[0] % 9).into())
+        .literal_position_bits((props[0] / 9 % 5).into())
+        .position_bits((props[0] / 45).into())
+        .dict_size(u32::from_le_bytes([props[1], props[2], props[3], props[4]]));
+    let mut filters = Filters::new();
+    filters.lzma1(&opts);
+    Ok(filters)
+}
+
+fn parse_lzma_props(props: &str) -> Result<Vec<u8>> {
+    let props = hex::decode(props).context("LZMA properties must be hex")?;
+    if props.len() != 5 {
+        bail!("LZMA properties must be 5 bytes: properties byte and dictionary size");
+    }
+    if props[0] >= 9 * 5 * 5 {
+        bail!("invalid LZMA properties byte: {:#x}", props[0]);
+    }
+    Ok(props)
+}
+
+/// Decompress a LZ4 block into `output`, which holds the data decompressed so far
+/// if the block is invalid or truncated.
+pub fn lz4_block_decompress(val: &[u8], output: &mut Vec<u8>) -> Result<()> {
+    // Lengths of 15 are followed by bytes to add, until one is not 255
+    let read_len = |pos: &mut usize, mut len: usize| -> Result<usize> {
+        if len == 15 {
+            loop {
+                let b = *val.get(*pos).ok_or_else(|| anyhow!("truncated block"))?;
+                *pos += 1;
+                len += b as usize;
+                if b != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    };
+
+    let mut pos = 0;
+    while pos < val.len() {
+        let token = val[pos];
+        pos += 1;
+        let lit_len = read_len(&mut pos, (token >> 4) as usize)?;
+        let lit_end = pos.saturating_add(lit_len);
+        output.extend_from_slice(&val[pos..lit_end.min(val.len())]);
+        if lit_end > val.len() {
+            bail!("truncated block");
+        }
+        pos = lit_end;
+        // The last sequence only has literals
+        if pos == val.len() {
+            break;
+        }
+        if pos + 2 > val.len() {
+            bail!("truncated block");
+        }
+        let offset = u16::from_le_bytes([val[pos], val[pos + 1]]) as usize;
+        pos += 2;
+        if offset == 0 || offset > output.len() {
+            bail!(
+                "invalid match offset {} at input offset {}",
+                offset,
+                pos - 2
+            );
+        }
+        let match_len = read_len(&mut pos, (token & 0xf) as usize)? + 4;
+        // Matches can overlap the data they produce
+        let start = output.len() - offset;
+        for i in start..start + match_len {
+            output.push(output[i]);
+        }
+    }
+    Ok(())
+}
+
+/// Decompress `val`, detecting its codec if `codec` is None. Unlike the applet,
+/// fail on any error.
+pub fn decompress_all(val: &[u8], codec: Option<Codec>) -> Result<Vec<u8>> {
+    let codec = codec
+        .or_else(|| detect_codec(val))
+        .ok_or_else(|| anyhow!("unknown compression format"))?;
+    let mut res = vec![];
+    DecompressApplet::with_options(Some(codec), DEFAULT_LZMA_PROPS, true)?.decompress(
+        codec,
+        &mut &val[..],
+        &mut res,
+    )?;
+    Ok(res)
+}
+
+pub struct CompressApplet {
+    codec: Codec,
+    level: Option<u32>,
+    lzma_props: Vec<u8>,
+}
+
+impl CompressApplet {
+    /// Build a `CompressApplet` compressing with `codec`, at the given `level`
+    /// (or the codec's default) if the codec supports levels.
+    /// `lzma_props` is the hex encoded properties byte and dictionary size, for raw LZMA.
+    pub fn with_options(codec: Codec, level: Option<u32>, lzma_props: &str) -> Result<Self> {
+        match (level, codec.levels()) {
+            (Some(level), Some((_, max))) if level > max => {
+                bail!("maximum compression level for {:?} is {}", codec, max)
+            }
+            (Some(_), None) => bail!("{:?} has no compression levels", codec),
+            _ => (),
+        }
+        Ok(Self {
+            codec,
+            level,
+            lzma_props: parse_lzma_props(lzma_props)?,
+        })
+    }
+}
+
+impl Applet for CompressApplet {
+    fn command(&self) -> &'static str {
+        "compress"
+    }
+
+    fn description(&self) -> &'static str {
+        "compress (xz, LZMA, bzip2, zstd, LZ4, brotli)"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-c --codec <codec> "compression format")
+                    .value_parser(clap::builder::EnumValueParser::<Codec>::new())
+                    .required(true),
+            )
+            .arg(
+                arg!(-l --level <level> "compression level, if supported by the codec")
+                    .value_parser(value_parser!(u32)),
+            )
+            .arg(
+                arg!(-p --props <props> "raw LZMA properties: properties byte and dictionary size (LE), in hex")
+                    .default_value(DEFAULT_LZMA_PROPS),
+            )
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            *args.get_one::<Codec>("codec").unwrap(),
+            args.get_one::<u32>("level").copied(),
+            args.get_one::<String>("props").unwrap(),
+        )?))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, mut input: &mut dyn Read, mut output: &mut dyn Write) -> Result<()> {
+        let level = self
+            .level
+            .or_else(|| self.codec.levels().map(|(default, _)| default))
+            .unwrap_or(0);
+        match self.codec {
+            Codec::Xz => {
+                let mut enc = liblzma::write::XzEncoder::new(output, level);
+                io::copy(input, &mut enc)?;
+                enc.finish()?;
+            }
+            Codec::Lzma | Codec::LzmaRaw => {
+                let stream = if self.codec == Codec::Lzma {
+                    Stream::new_lzma_encoder(&LzmaOptions::new_preset(level)?)?
+                } else {
+                    Stream::new_raw_encoder(&lzma_raw_filters(&self.lzma_props, level)?)?
+                };
+                let mut enc = liblzma::write::XzEncoder::new_stream(output, stream);
+                io::copy(input, &mut enc)?;
+                enc.finish()?;
+            }
+            Codec::Bzip2 => {
+                let mut enc = bzip2::write::BzEncoder::new(output, bzip2::Compression::new(level));
+                io::copy(input, &mut enc)?;
+                enc.finish()?;
+            }
+            Codec::Zstd => {
+                let mut enc = zstd::stream::write::Encoder::new(output, level as i32)?;
+                io::copy(input, &mut enc)?;
+                enc.finish()?;
+            }
+            Codec::Lz4 => {
+                let mut enc = lz4_flex::frame::FrameEncoder::new(output);
+                io::copy(input, &mut enc)?;
+                enc.finish()?;
+            }
+            Codec::Lz4Block => {
+                let mut val = vec![];
+                input.read_to_end(&mut val)?;
+                output.write_all(&lz4_flex::block::compress(&val))?;
+            }
+            Codec::Brotli => {
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: level as i32,
+                    ..Default::default()
+                };
+                brotli::BrotliCompress(&mut input, &mut output, &params)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            codec: Codec::Xz,
+            level: None,
+            lzma_props: hex::decode(DEFAULT_LZMA_PROPS).unwrap(),
+        })
+    }
+}
+
+/* Reader keeping the I/O errors of `input`, which decoders report like
+ * invalid data */
+struct IoErrorKeeper<'a> {
+    input: &'a mut dyn Read,
+    error: Option<io::Error>,
+}
+
+impl Read for IoErrorKeeper<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf).map_err(|e| {
+            let copy = io::Error::new(e.kind(), e.to_string());
+            if e.kind() != io::ErrorKind::Interrupted {
+                self.error = Some(e);
+            }
+            copy
+        })
+    }
+}
+
+pub struct DecompressApplet {
+    codec: Option<Codec>,
+    lzma_props: Vec<u8>,
+    quiet: bool,
+}
+
+impl DecompressApplet {
+    /// Build a `DecompressApplet` decompressing `codec` data, detected from the
+    /// magic bytes if None. `lzma_props` is used for raw LZMA, see
+    /// [`CompressApplet::with_options`]. Unless `quiet` is set, errors are reported on stderr.
+    pub fn with_options(codec: Option<Codec>, lzma_props: &str, quiet: bool) -> Result<Self> {
+        Ok(Self {
+            codec,
+            lzma_props: parse_lzma_props(lzma_props)?,
+            quiet,
+        })
+    }
+
+    fn report_error(&self, err: &str) {
+        if !self.quiet {
+            eprintln!(
+                "Decompression error: {} (still outputing data to stdout)",
+                err
+            );
+        }
+    }
+
+    /* Decompress `input` to `output`. Decompressed data is written as soon as
+     * possible, so that it is kept even if an error occurs afterwards.
+     * Errors are either I/O errors or invalid data. */
+    fn decompress(&self, codec: Codec, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut input = IoErrorKeeper { input, error: None };
+        let res = self.decode(codec, &mut input, output);
+        match input.error {
+            Some(e) => Err(e.into()),
+            None => res,
+        }
+    }
+
+    fn decode(&self, codec: Codec, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut decoder: Box<dyn Read + '_> = match codec {
+            Codec::Xz => Box::new(liblzma::read::XzDecoder::new_multi_decoder(input)),
+            Codec::Lzma => Box::new(liblzma::read::XzDecoder::new_stream(
+                input,
+                Stream::new_lzma_decoder(u64::MAX)?,
+            )),
+            Codec::LzmaRaw => Box::new(liblzma::read::XzDecoder::new_stream(
+                input,
+                Stream::new_raw_decoder(&lzma_raw_filters(&self.lzma_props, 6)?)?,
+            )),
+            Codec::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(input)),
+            Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(input)?),
+            Codec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(input)),
+            Codec::Lz4Block => {
+                let mut val = vec![];
+                input.read_to_end(&mut val)?;
+                let mut res = vec![];
+                let status = lz4_block_decompress(&val, &mut res);
+                output.write_all(&res)?;
+                return status;
+            }
+            Codec::Brotli => Box::new(brotli::Decompressor::new(input, STREAM_BUF_SIZE)),
+        };
+
+        let mut buf = vec![0; STREAM_BUF_SIZE];
+        loop {
+            // Don't use `read_chunk`, which would drop the data read before an error
+            let len = match decoder.read(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // I/O errors on the input are kept by `decompress`
+                Err(e) => bail!("{}", e),
+            };
+            if len == 0 {
+                return Ok(());
+            }
+            output.write_all(&buf[..len])?;
+        }
+    }
+}
+
+impl Applet for DecompressApplet {
+    fn command(&self) -> &'static str {
+        "decompress"
+    }
+
+    fn description(&self) -> &'static str {
+        "decompress (xz, LZMA, bzip2, zstd, LZ4, brotli)"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-c --codec <codec> "compression format")
+                    .value_parser(clap::builder::EnumValueParser::<Codec>::new()),
+            )
+            .arg(
+                arg!(-p --props <props> "raw LZMA properties: properties byte and dictionary size (LE), in hex")
+                    .default_value(DEFAULT_LZMA_PROPS),
+            )
+            .arg(arg!(-q --quiet "don't output error message on stderr if decompression failed"))
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+            .after_help("The codec is detected from the magic bytes, unless specified. Raw LZMA, LZ4 blocks and brotli cannot be detected.")
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_one::<Codec>("codec").copied(),
+            args.get_one::<String>("props").unwrap(),
+            args.get_flag("quiet"),
+        )?))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        // Read the first chunk to detect the codec
+        let mut head = vec![0; STREAM_BUF_SIZE];
+        let len = read_chunk(input, &mut head)?;
+        head.truncate(len);
+        let codec = self
+            .codec
+            .or_else(|| detect_codec(&head))
+            .ok_or_else(|| anyhow!("unknown compression format, use --codec"))?;
+
+        match self.decompress(codec, &mut head.as_slice().chain(input), output) {
+            // I/O errors are real errors, others mean invalid data
+            Err(e) if e.downcast_ref::<io::Error>().is_some() => Err(e),
+            Err(e) => {
+                self.report_error(&e.to_string());
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            codec: None,
+            lzma_props: hex::decode(DEFAULT_LZMA_PROPS).unwrap(),
+            quiet: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODECS: [Codec; 8] = [
+        Codec::Xz,
+        Codec::Lzma,
+        Codec::LzmaRaw,
+        Codec::Bzip2,
+        Codec::Zstd,
+        Codec::Lz4,
+        Codec::Lz4Block,
+        Codec::Brotli,
+    ];
+
+    fn test_data() -> Vec<u8> {
+        (0..3 * STREAM_BUF_SIZE)
+            .map(|i| ((i / 7) % 13 + i % 3) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let data = test_data();
+        for codec in CODECS {
+            let comp = CompressApplet::with_options(codec, None, DEFAULT_LZMA_PROPS).unwrap();
+            let compressed = comp.process(data.clone()).unwrap();
+            assert!(compressed.len() < data.len());
+            let decomp =
+                DecompressApplet::with_options(Some(codec), DEFAULT_LZMA_PROPS, true).unwrap();
+            assert_eq!(
+                decomp.process(compressed.clone()).unwrap(),
+                data,
+                "{:?}",
+                codec
+            );
+
+            let detected = detect_codec(&compressed);
+            match codec {
+                Codec::LzmaRaw | Codec::Lz4Block | Codec::Brotli => assert_eq!(detected, None),
+                _ => assert_eq!(detected, Some(codec)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        assert!(CompressApplet::with_options(Codec::Bzip2, Some(10), DEFAULT_LZMA_PROPS).is_err());
+        assert!(CompressApplet::with_options(Codec::Lz4, Some(1), DEFAULT_LZMA_PROPS).is_err());
+        assert!(
+            CompressApplet::with_options(Codec::Lz4Block, Some(1), DEFAULT_LZMA_PROPS).is_err()
+        );
+        assert!(CompressApplet::with_options(Codec::Xz, None, "5d").is_err());
+        assert!(CompressApplet::with_options(Codec::Xz, None, "e100008000").is_err());
+        assert!(CompressApplet::with_options(Codec::Xz, None, "5d00001000").is_ok());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = test_data();
+        let comp = CompressApplet::with_options(Codec::Xz, Some(0), DEFAULT_LZMA_PROPS).unwrap();
+        let compressed = comp.process(data.clone()).unwrap();
+        let decomp = DecompressApplet::with_options(None, DEFAULT_LZMA_PROPS, true).unwrap();
+        let partial = decomp
+            .process(compressed[..compressed.len() / 2].to_vec())
+            .unwrap();
+        assert!(!partial.is_empty());
+        assert!(data.starts_with(&partial));
+        assert!(decompress_all(&compressed[..compressed.len() / 2], None).is_err());
+        assert_eq!(decompress_all(&compressed, None).unwrap(), data);
+    }
+
+    // Fails with an I/O error after returning all its data
+    struct FailingReader<'a>(&'a [u8]);
+
+    impl Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("read failed"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_io_error() {
+        let data = test_data();
+        for codec in CODECS {
+            let comp = CompressApplet::with_options(codec, None, DEFAULT_LZMA_PROPS).unwrap();
+            let compressed = comp.process(data.clone()).unwrap();
+            let decomp =
+                DecompressApplet::with_options(Some(codec), DEFAULT_LZMA_PROPS, true).unwrap();
+            let mut input = FailingReader(&compressed[..compressed.len() / 2]);
+            let err = decomp
+                .decompress(codec, &mut input, &mut vec![])
+                .unwrap_err();
+            assert!(err.downcast_ref::<io::Error>().is_some(), "{:?}", codec);
+        }
+    }
+
+    #[test]
+    fn test_zstd_skippable() {
+        let mut data = b"\x50\x2a\x4d\x18\x04\x00\x00\x00SKIP".to_vec();
+        data.extend(zstd::encode_all(&b"test"[..], 3).unwrap());
+        assert_eq!(detect_codec(&data), Some(Codec::Zstd));
+        assert_eq!(decompress_all(&data, None).unwrap(), b"test");
+    }
+
+    #[test]
+    fn test_lz4_block() {
+        let mut res = vec![];
+        // "aaaaaaaaaab": 1 literal, then a match of 9 at offset 1, then 1 literal
+        lz4_block_decompress(b"\x15a\x01\x00\x10b", &mut res).unwrap();
+        assert_eq!(res, b"aaaaaaaaaab");
+
+        // Truncated literals are kept
+        res.clear();
+        assert!(lz4_block_decompress(b"\x15a\x01\x00\x30bc", &mut res).is_err());
+        assert_eq!(res, b"aaaaaaaaaabc");
+
+        res.clear();
+        assert!(lz4_block_decompress(b"\x15a\x02\x00", &mut res).is_err());
+    }
+}
@@ -1,11 +1,13 @@
 extern crate crc;
-use crate::applet::Applet;
-use anyhow::{bail, Result};
-use clap::{arg, Command};
+use crate::applet::{Applet, FromStrWithRadix};
+use anyhow::{bail, Context, Result};
+use clap::{arg, value_parser, Command};
 use crc::*;
+use std::fmt;
 use std::process;
 
-const ALL_CRCS: [&str; 111] = [
+/// Names of all the CRC algorithms supported by `do_crc`, see <https://docs.rs/crc/>
+pub const ALL_CRCS: [&str; 111] = [
     "CRC_3_GSM",
     "CRC_3_ROHC",
     "CRC_4_G_704",
@@ -167,8 +169,223 @@ impl Applet for CRC32Applet {
     }
 }
 
+/// Parameters of a CRC algorithm which is not in `ALL_CRCS`, using the same
+/// model as `crc::Algorithm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrcParams {
+    /// Width in bits, from 3 to 64
+    pub width: u8,
+    pub poly: u64,
+    pub init: u64,
+    /// Process input bytes LSB first
+    pub refin: bool,
+    /// Reflect the final CRC value
+    pub refout: bool,
+    pub xorout: u64,
+}
+
+/// Reflect the `width` low bits of `value`.
+pub fn reflect(value: u64, width: u8) -> u64 {
+    value.reverse_bits() >> (64 - width)
+}
+
+/// CRC register after processing `data`, without refout and xorout. Bitwise, as
+/// building tables for each custom CRC (or `crcrev` candidate) would be slower.
+pub fn register(width: u8, poly: u64, init: u64, refin: bool, data: &[u8]) -> u64 {
+    let top = 1 << (width - 1);
+    let mask = u64::MAX >> (64 - width);
+    let mut reg = init;
+    for &byte in data {
+        let byte = if refin { byte.reverse_bits() } else { byte };
+        for j in (0..8).rev() {
+            let feedback = (reg & top != 0) ^ (byte >> j & 1 == 1);
+            reg = (reg << 1) & mask;
+            if feedback {
+                reg ^= poly;
+            }
+        }
+    }
+    reg
+}
+
+impl CrcParams {
+    /// Check that `width` is supported and that the values fit in it.
+    pub fn new(
+        width: u8,
+        poly: u64,
+        init: u64,
+        refin: bool,
+        refout: bool,
+        xorout: u64,
+    ) -> Result<Self> {
+        if !(3..=64).contains(&width) {
+            bail!("CRC width must be between 3 and 64");
+        }
+        for (name, value) in [("poly", poly), ("init", init), ("xorout", xorout)] {
+            if width < 64 && value >> width != 0 {
+                bail!("{} 0x{:x} does not fit in {} bits", name, value, width);
+            }
+        }
+        Ok(Self {
+            width,
+            poly,
+            init,
+            refin,
+            refout,
+            xorout,
+        })
+    }
+
+    /// Compute the CRC of `val`.
+    pub fn compute(&self, val: &[u8]) -> u64 {
+        let reg = register(self.width, self.poly, self.init, self.refin, val);
+        let reg = if self.refout {
+            reflect(reg, self.width)
+        } else {
+            reg
+        };
+        reg ^ self.xorout
+    }
+
+    /// Compute the CRC of `val`, returned in hex like `do_crc`.
+    pub fn checksum(&self, val: &[u8]) -> String {
+        let crc = self.compute(val);
+        match self.width {
+            0..=8 => format!("{:02x}", crc),
+            9..=16 => format!("{:04x}", crc),
+            17..=32 => format!("{:08x}", crc),
+            _ => format!("{:016x}", crc),
+        }
+    }
+}
+
+/* Arguments of the `crc` applet */
+impl fmt::Display for CrcParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = (self.width as usize).div_ceil(4);
+        write!(
+            f,
+            "--width {} --poly 0x{:0digits$x} --init 0x{:0digits$x}",
+            self.width,
+            self.poly,
+            self.init,
+            digits = digits
+        )?;
+        if self.refin {
+            write!(f, " --refin")?;
+        }
+        if self.refout {
+            write!(f, " --refout")?;
+        }
+        write!(f, " --xorout 0x{:0digits$x}", self.xorout, digits = digits)
+    }
+}
+
+/// A CRC algorithm, known or custom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrcAlg {
+    /// One of `ALL_CRCS`
+    Known(&'static str),
+    Custom(CrcParams),
+}
+
+impl CrcAlg {
+    /// Look up `name` in `ALL_CRCS`.
+    pub fn known(name: &str) -> Result<Self> {
+        match ALL_CRCS.iter().find(|&&alg| alg == name) {
+            Some(alg) => Ok(CrcAlg::Known(alg)),
+            None => bail!("Unknown CRC algorithm"),
+        }
+    }
+
+    /// Width of the CRC, in bits.
+    pub fn width(&self) -> u8 {
+        match self {
+            CrcAlg::Known(name) => name.split('_').nth(1).unwrap().parse().unwrap(),
+            CrcAlg::Custom(params) => params.width,
+        }
+    }
+
+    /// Compute the CRC of `val`.
+    pub fn compute(&self, val: &[u8]) -> Result<u128> {
+        match self {
+            CrcAlg::Known(name) => Ok(u128::from_str_radix(&do_crc(name, val)?, 16)?),
+            CrcAlg::Custom(params) => Ok(params.compute(val).into()),
+        }
+    }
+}
+
+/* Arguments of the `crc` applet */
+impl fmt::Display for CrcAlg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrcAlg::Known(name) => write!(f, "{}", name),
+            CrcAlg::Custom(params) => write!(f, "{}", params),
+        }
+    }
+}
+
+/// Add the arguments defining a custom CRC to `cmd`, see [`parse_custom_crc`].
+pub fn custom_crc_args(cmd: Command) -> Command {
+    cmd.arg(
+        arg!(-w --width <width> "width of a custom CRC")
+            .value_parser(value_parser!(u8).range(3..=64))
+            .requires("poly"),
+    )
+    .arg(
+        arg!(-p --poly <poly> "polynomial of the custom CRC, without the top bit")
+            .requires("width"),
+    )
+    .arg(arg!(--init <init> "initial value of the custom CRC").requires("width"))
+    .arg(arg!(--refin "reflect input bytes of the custom CRC").requires("width"))
+    .arg(arg!(--refout "reflect the custom CRC value before xorout").requires("width"))
+    .arg(arg!(--xorout <xorout> "value XORed with the custom CRC").requires("width"))
+}
+
+/// Parse the arguments added by [`custom_crc_args`], if `--width` is given.
+pub fn parse_custom_crc(args: &clap::ArgMatches) -> Result<Option<CrcParams>> {
+    let width = match args.get_one::<u8>("width") {
+        Some(width) => *width,
+        None => return Ok(None),
+    };
+    let value = |name: &str| -> Result<u64> {
+        match args.get_one::<String>(name) {
+            Some(v) => u64::from_str_with_radix(v)
+                .with_context(|| format!("Invalid {} value \"{}\"", name, v)),
+            None => Ok(0),
+        }
+    };
+    Ok(Some(CrcParams::new(
+        width,
+        value("poly")?,
+        value("init")?,
+        args.get_flag("refin"),
+        args.get_flag("refout"),
+        value("xorout")?,
+    )?))
+}
+
 pub struct CRCApplet {
     crctype: String,
+    params: Option<CrcParams>,
+}
+
+impl CRCApplet {
+    /// Build a `CRCApplet` computing `crctype`, one of `ALL_CRCS`, or "all".
+    pub fn with_options(crctype: &str) -> Self {
+        Self {
+            crctype: crctype.to_string(),
+            params: None,
+        }
+    }
+
+    /// Build a `CRCApplet` computing a custom CRC defined by `params`.
+    pub fn with_params(params: CrcParams) -> Self {
+        Self {
+            crctype: String::new(),
+            params: Some(params),
+        }
+    }
 }
 
 macro_rules! algs {
@@ -191,96 +408,120 @@ impl Applet for CRCApplet {
     fn new() -> Box<dyn Applet> {
         Box::new(Self {
             crctype: "lol".to_string(),
+            params: None,
         })
     }
 
     fn clap_command(&self) -> Command {
-        Command::new(self.command())
-            .about(self.description())
-            .arg(arg!(-l --list  "List supported CRC algorithms"))
-            .arg(
-                arg!([type] "CRC type to compute. Use 'all' to compute all known algorithms.")
-                    .required_unless_present("list"),
-            )
-            .arg(arg!([value]  "input value, reads from stdin if not present"))
+        custom_crc_args(
+            Command::new(self.command())
+                .about(self.description())
+                .arg(arg!(-l --list  "List supported CRC algorithms"))
+                .arg(
+                    arg!([type] "CRC type to compute. Use 'all' to compute all known algorithms.")
+                        .required_unless_present_any(["list", "width"]),
+                )
+                .arg(arg!([value]  "input value, reads from stdin if not present")),
+        )
+        .after_help("With --width, <type> is omitted: the first argument is the input value.")
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        /* No type is given for custom CRCs, so the value is the first argument */
+        if self.params.is_some() {
+            Some("type")
+        } else {
+            Some("value")
+        }
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        if let Some(params) = parse_custom_crc(args)? {
+            if args.contains_id("value") {
+                bail!("No CRC type can be given with custom parameters");
+            }
+            return Ok(Box::new(Self::with_params(params)));
+        }
+
         if args.get_flag("list") {
             println!("Supported algorithms:");
             println!("{}", ALL_CRCS.join("\n"));
             println!("\nSee https://docs.rs/crc/ for more info");
             process::exit(0);
         }
-        Ok(Box::new(Self {
-            crctype: args.get_one::<String>("type").unwrap().to_string(),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("type").unwrap(),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(params) = &self.params {
+            return Ok(params.checksum(&val).as_bytes().to_vec());
+        }
         let alg_name: &str = self.crctype.as_str();
         if alg_name == "all" {
             let mut res = String::new();
             for alg in ALL_CRCS.iter() {
-                res.push_str(format!("{}: 0x{}\n", alg, self.do_crc(alg, &val)?.as_str()).as_str());
+                res.push_str(format!("{}: 0x{}\n", alg, do_crc(alg, &val)?.as_str()).as_str());
             }
             Ok(res.as_bytes().to_vec())
         } else {
-            Ok(self.do_crc(alg_name, &val)?.as_bytes().to_vec())
+            Ok(do_crc(alg_name, &val)?.as_bytes().to_vec())
         }
     }
 }
 
-impl CRCApplet {
-    fn do_crc(&self, alg_name: &str, val: &[u8]) -> Result<String> {
-        let alg_size: u8 = (*alg_name.split('_').collect::<Vec<&str>>().get(1).unwrap())
-            .parse()
-            .unwrap();
-        match alg_size {
-            0..=8 => {
-                let crc8 = algs!(alg_name; u8;
-                    CRC_3_GSM, CRC_3_ROHC, CRC_4_G_704, CRC_4_INTERLAKEN, CRC_5_EPC_C1G2, CRC_5_G_704, CRC_5_USB,
-                    CRC_6_CDMA2000_A, CRC_6_CDMA2000_B, CRC_6_DARC, CRC_6_GSM, CRC_6_G_704, CRC_7_MMC, CRC_7_ROHC,
-                    CRC_7_UMTS, CRC_8_AUTOSAR, CRC_8_BLUETOOTH, CRC_8_CDMA2000, CRC_8_DARC, CRC_8_DVB_S2, CRC_8_GSM_A,
-                    CRC_8_GSM_B, CRC_8_HITAG, CRC_8_I_432_1, CRC_8_I_CODE, CRC_8_LTE, CRC_8_MAXIM_DOW,
-                    CRC_8_MIFARE_MAD, CRC_8_NRSC_5, CRC_8_OPENSAFETY, CRC_8_ROHC, CRC_8_SAE_J1850, CRC_8_SMBUS,
-                    CRC_8_TECH_3250, CRC_8_WCDMA);
-                Ok(format!("{:02x}", crc8.checksum(val)))
-            }
-            9..=16 => {
-                let crc16 = algs!(alg_name; u16;
-                            CRC_10_ATM, CRC_10_CDMA2000, CRC_10_GSM, CRC_11_FLEXRAY, CRC_11_UMTS,
-                            CRC_12_CDMA2000, CRC_12_DECT, CRC_12_GSM, CRC_12_UMTS, CRC_13_BBC,
-                            CRC_14_DARC, CRC_14_GSM, CRC_15_CAN, CRC_15_MPT1327, CRC_16_ARC,
-                            CRC_16_CDMA2000, CRC_16_CMS, CRC_16_DDS_110, CRC_16_DECT_R,
-                            CRC_16_DECT_X, CRC_16_DNP, CRC_16_EN_13757, CRC_16_GENIBUS, CRC_16_GSM,
-                            CRC_16_IBM_3740, CRC_16_IBM_SDLC, CRC_16_ISO_IEC_14443_3_A,
-                            CRC_16_KERMIT, CRC_16_LJ1200, CRC_16_M17, CRC_16_MAXIM_DOW,
-                            CRC_16_MCRF4XX, CRC_16_MODBUS, CRC_16_NRSC_5, CRC_16_OPENSAFETY_A,
-                            CRC_16_OPENSAFETY_B, CRC_16_PROFIBUS, CRC_16_RIELLO,
-                            CRC_16_SPI_FUJITSU, CRC_16_T10_DIF, CRC_16_TELEDISK, CRC_16_TMS37157,
-                            CRC_16_UMTS, CRC_16_USB, CRC_16_XMODEM);
-                Ok(format!("{:04x}", crc16.checksum(val)))
-            }
-            17..=32 => {
-                let crc32 = algs!(alg_name; u32;
-                        CRC_17_CAN_FD, CRC_21_CAN_FD, CRC_24_BLE, CRC_24_FLEXRAY_A, CRC_24_FLEXRAY_B, CRC_24_INTERLAKEN,
-                        CRC_24_LTE_A, CRC_24_LTE_B, CRC_24_OPENPGP, CRC_24_OS_9, CRC_30_CDMA, CRC_31_PHILIPS, CRC_32_AIXM,
-                        CRC_32_AUTOSAR, CRC_32_BASE91_D, CRC_32_BZIP2, CRC_32_CD_ROM_EDC, CRC_32_CKSUM, CRC_32_ISCSI,
-                        CRC_32_ISO_HDLC, CRC_32_JAMCRC, CRC_32_MEF, CRC_32_MPEG_2, CRC_32_XFER);
-                Ok(format!("{:08x}", crc32.checksum(val)))
-            }
-            33..=64 => {
-                let crc64 = algs!(alg_name; u64; CRC_40_GSM, CRC_64_ECMA_182, CRC_64_GO_ISO, CRC_64_MS, CRC_64_REDIS, CRC_64_WE, CRC_64_XZ);
-                Ok(format!("{:016x}", crc64.checksum(val)))
-            }
-            65..=128 => {
-                let crc128 = algs!(alg_name; u128; CRC_82_DARC);
-                Ok(format!("{:032x}", crc128.checksum(val)))
-            }
-            _ => {
-                bail!("Unknown CRC algorithm");
-            }
+/// Compute the CRC of `val` with the `alg_name` algorithm, one of `ALL_CRCS`.
+/// The result is returned in hex, padded to the CRC size.
+pub fn do_crc(alg_name: &str, val: &[u8]) -> Result<String> {
+    let alg_size: u8 = match alg_name.split('_').nth(1).map(|s| s.parse()) {
+        Some(Ok(size)) => size,
+        _ => bail!("Unknown CRC algorithm"),
+    };
+    match alg_size {
+        0..=8 => {
+            let crc8 = algs!(alg_name; u8;
+                CRC_3_GSM, CRC_3_ROHC, CRC_4_G_704, CRC_4_INTERLAKEN, CRC_5_EPC_C1G2, CRC_5_G_704, CRC_5_USB,
+                CRC_6_CDMA2000_A, CRC_6_CDMA2000_B, CRC_6_DARC, CRC_6_GSM, CRC_6_G_704, CRC_7_MMC, CRC_7_ROHC,
+                CRC_7_UMTS, CRC_8_AUTOSAR, CRC_8_BLUETOOTH, CRC_8_CDMA2000, CRC_8_DARC, CRC_8_DVB_S2, CRC_8_GSM_A,
+                CRC_8_GSM_B, CRC_8_HITAG, CRC_8_I_432_1, CRC_8_I_CODE, CRC_8_LTE, CRC_8_MAXIM_DOW,
+                CRC_8_MIFARE_MAD, CRC_8_NRSC_5, CRC_8_OPENSAFETY, CRC_8_ROHC, CRC_8_SAE_J1850, CRC_8_SMBUS,
+                CRC_8_TECH_3250, CRC_8_WCDMA);
+            Ok(format!("{:02x}", crc8.checksum(val)))
+        }
+        9..=16 => {
+            let crc16 = algs!(alg_name; u16;
+                        CRC_10_ATM, CRC_10_CDMA2000, CRC_10_GSM, CRC_11_FLEXRAY, CRC_11_UMTS,
+                        CRC_12_CDMA2000, CRC_12_DECT, CRC_12_GSM, CRC_12_UMTS, CRC_13_BBC,
+                        CRC_14_DARC, CRC_14_GSM, CRC_15_CAN, CRC_15_MPT1327, CRC_16_ARC,
+                        CRC_16_CDMA2000, CRC_16_CMS, CRC_16_DDS_110, CRC_16_DECT_R,
+                        CRC_16_DECT_X, CRC_16_DNP, CRC_16_EN_13757, CRC_16_GENIBUS, CRC_16_GSM,
+                        CRC_16_IBM_3740, CRC_16_IBM_SDLC, CRC_16_ISO_IEC_14443_3_A,
+                        CRC_16_KERMIT, CRC_16_LJ1200, CRC_16_M17, CRC_16_MAXIM_DOW,
+                        CRC_16_MCRF4XX, CRC_16_MODBUS, CRC_16_NRSC_5, CRC_16_OPENSAFETY_A,
+                        CRC_16_OPENSAFETY_B, CRC_16_PROFIBUS, CRC_16_RIELLO,
+                        CRC_16_SPI_FUJITSU, CRC_16_T10_DIF, CRC_16_TELEDISK, CRC_16_TMS37157,
+                        CRC_16_UMTS, CRC_16_USB, CRC_16_XMODEM);
+            Ok(format!("{:04x}", crc16.checksum(val)))
+        }
+        17..=32 => {
+            let crc32 = algs!(alg_name; u32;
+                    CRC_17_CAN_FD, CRC_21_CAN_FD, CRC_24_BLE, CRC_24_FLEXRAY_A, CRC_24_FLEXRAY_B, CRC_24_INTERLAKEN,
+                    CRC_24_LTE_A, CRC_24_LTE_B, CRC_24_OPENPGP, CRC_24_OS_9, CRC_30_CDMA, CRC_31_PHILIPS, CRC_32_AIXM,
+                    CRC_32_AUTOSAR, CRC_32_BASE91_D, CRC_32_BZIP2, CRC_32_CD_ROM_EDC, CRC_32_CKSUM, CRC_32_ISCSI,
+                    CRC_32_ISO_HDLC, CRC_32_JAMCRC, CRC_32_MEF, CRC_32_MPEG_2, CRC_32_XFER);
+            Ok(format!("{:08x}", crc32.checksum(val)))
+        }
+        33..=64 => {
+            let crc64 = algs!(alg_name; u64; CRC_40_GSM, CRC_64_ECMA_182, CRC_64_GO_ISO, CRC_64_MS, CRC_64_REDIS, CRC_64_WE, CRC_64_XZ);
+            Ok(format!("{:016x}", crc64.checksum(val)))
+        }
+        65..=128 => {
+            let crc128 = algs!(alg_name; u128; CRC_82_DARC);
+            Ok(format!("{:032x}", crc128.checksum(val)))
+        }
+        _ => {
+            bail!("Unknown CRC algorithm");
         }
     }
 }
@@ -300,15 +541,73 @@ mod tests {
 
     #[test]
     fn test_crc() {
-        let crc = CRCApplet {
-            crctype: "CRC_32_AIXM".to_string(),
-        };
+        let crc = CRCApplet::with_options("CRC_32_AIXM");
         assert_eq!(
             "fa83f52a".as_bytes().to_vec(),
             crc.process_test("toto".as_bytes().to_vec())
         );
     }
 
+    #[test]
+    fn test_do_crc() {
+        assert_eq!(do_crc("CRC_32_AIXM", b"toto").unwrap(), "fa83f52a");
+        assert!(do_crc("CRC_32_NOPE", b"toto").is_err());
+        assert!(do_crc("NOPE", b"toto").is_err());
+    }
+
+    #[test]
+    fn test_custom_crc() {
+        // CRC_16_MODBUS
+        let params = CrcParams::new(16, 0x8005, 0xffff, true, true, 0).unwrap();
+        assert_eq!(params.checksum(b"123456789"), "4b37");
+        assert_eq!(
+            CRCApplet::with_params(params).process_test(b"toto".to_vec()),
+            do_crc("CRC_16_MODBUS", b"toto").unwrap().as_bytes()
+        );
+        // CRC_3_GSM
+        let params = CrcParams::new(3, 0x3, 0, false, false, 0x7).unwrap();
+        assert_eq!(params.checksum(b"123456789"), "04");
+        // CRC_64_XZ
+        let params =
+            CrcParams::new(64, 0x42f0e1eba9ea3693, u64::MAX, true, true, u64::MAX).unwrap();
+        assert_eq!(params.checksum(b"123456789"), "995dc9bbdf1939fa");
+
+        assert_eq!(
+            params.to_string(),
+            "--width 64 --poly 0x42f0e1eba9ea3693 --init 0xffffffffffffffff --refin --refout --xorout 0xffffffffffffffff"
+        );
+        assert_eq!(
+            CrcParams::new(12, 0x80f, 0, false, true, 0)
+                .unwrap()
+                .to_string(),
+            "--width 12 --poly 0x80f --init 0x000 --refout --xorout 0x000"
+        );
+
+        assert!(CrcParams::new(2, 0x1, 0, false, false, 0).is_err());
+        assert!(CrcParams::new(65, 0x1, 0, false, false, 0).is_err());
+        assert!(CrcParams::new(8, 0x107, 0, false, false, 0).is_err());
+    }
+
+    #[test]
+    fn test_custom_crc_compute() {
+        for (name, params) in [
+            ("CRC_16_MODBUS", (16, 0x8005, 0xffff, true, true, 0)),
+            ("CRC_12_UMTS", (12, 0x80f, 0, false, true, 0)),
+            (
+                "CRC_64_XZ",
+                (64, 0x42f0e1eba9ea3693, u64::MAX, true, true, u64::MAX),
+            ),
+        ] {
+            let (width, poly, init, refin, refout, xorout) = params;
+            let params = CrcParams::new(width, poly, init, refin, refout, xorout).unwrap();
+            let expected = u64::from_str_radix(&do_crc(name, b"toto").unwrap(), 16).unwrap();
+            // Nothing is built or leaked for each computation
+            for _ in 0..10000 {
+                assert_eq!(params.compute(b"toto"), expected);
+            }
+        }
+    }
+
     #[test]
     fn test_crc16() {
         let crc16 = CRC16Applet {};
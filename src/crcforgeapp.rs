@@ -0,0 +1,221 @@
+use crate::applet::Applet;
+use crate::bgrepapp::map_input;
+use crate::crcapp::{custom_crc_args, parse_custom_crc, CrcAlg};
+use crate::crcrevapp::solve;
+use crate::patchapp::{patch, PatchMode};
+use crate::sliceapp::{parse_value_with_prefix, Position};
+use anyhow::{bail, Context, Result};
+use clap::{arg, ArgGroup, Command};
+
+/// Compute the bytes which, written into `val` according to `mode` (see
+/// [`patch`]), make its CRC `target`. As many bytes as needed to hold the CRC
+/// are forged: since the CRC is affine in the data, they are the solution of a
+/// linear system.
+pub fn forge(val: &[u8], alg: &CrcAlg, target: u128, mode: &PatchMode) -> Result<Vec<u8>> {
+    let width = alg.width();
+    if target >> width != 0 {
+        bail!("Target CRC 0x{:x} does not fit in {} bits", target, width);
+    }
+    let len = (width as usize).div_ceil(8);
+    let crc_with = |bytes: &[u8]| -> Result<u128> {
+        let mut patched = val.to_vec();
+        patch(&mut patched, bytes, mode, true)?;
+        alg.compute(&patched)
+    };
+
+    /* Contribution of each bit of the forged bytes to the CRC */
+    let base = crc_with(&vec![0; len])?;
+    let mut cols = vec![];
+    for bit in 0..len * 8 {
+        let mut bytes = vec![0; len];
+        bytes[bit / 8] = 1 << (bit % 8);
+        cols.push(crc_with(&bytes)? ^ base);
+    }
+    let rows = (0..width)
+        .map(|j| {
+            let coefs = cols
+                .iter()
+                .enumerate()
+                .fold(0, |c, (i, col)| c | (col >> j & 1) << i);
+            (coefs, (target ^ base) >> j & 1 == 1)
+        })
+        .collect();
+    let (solution, _) = solve(rows, (len * 8) as u8)
+        .with_context(|| "No bytes at this position can give the target CRC")?;
+    Ok((0..len).map(|i| (solution >> (8 * i)) as u8).collect())
+}
+
+pub struct CrcForgeApplet {
+    file: String,
+    alg: CrcAlg,
+    target: Option<u128>,
+    mode: PatchMode,
+    bytes_only: bool,
+}
+
+impl CrcForgeApplet {
+    /// Build a `CrcForgeApplet` forging bytes in `file` ("-" for stdin) so that
+    /// its `alg` CRC becomes `target`, or stays the same if not set. The bytes
+    /// are written at `position`, or appended if not set, and inserted instead
+    /// of overwriting data if `insert` is set. If `bytes_only` is set, only the
+    /// forged bytes are output, else the patched data.
+    pub fn with_options(
+        file: &str,
+        alg: CrcAlg,
+        target: Option<u128>,
+        position: Option<Position>,
+        insert: bool,
+        bytes_only: bool,
+    ) -> Self {
+        let mode = match position {
+            Some(position) => PatchMode::Write { position, insert },
+            None => PatchMode::Write {
+                position: Position {
+                    offset: 0,
+                    relative: false,
+                    from_end: true,
+                },
+                insert: true,
+            },
+        };
+        Self {
+            file: file.to_string(),
+            alg,
+            target,
+            mode,
+            bytes_only,
+        }
+    }
+}
+
+impl Applet for CrcForgeApplet {
+    fn command(&self) -> &'static str {
+        "crcforge"
+    }
+
+    fn description(&self) -> &'static str {
+        "forge bytes giving a chosen CRC"
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        custom_crc_args(
+            Command::new(self.command())
+                .about(self.description())
+                .arg(arg!(-a --alg <type> "CRC algorithm, see crc --list"))
+                .arg(arg!(-t --target <crc> "CRC to obtain, in hex (default: the CRC of the input, which stays unchanged)"))
+                .arg(arg!(-i --insert "insert forged bytes instead of overwriting data").requires("offset"))
+                .arg(arg!(-b --bytes "only output the forged bytes"))
+                .arg(arg!(<file> "file to patch, - for stdin"))
+                .arg(arg!([offset] "where to write the forged bytes, relative to end of file if negative (default: append them)")),
+        )
+        .group(
+            ArgGroup::new("algorithm")
+                .args(["alg", "width"])
+                .required(true),
+        )
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self::with_options(
+            "",
+            CrcAlg::Known("CRC_32_ISO_HDLC"),
+            None,
+            None,
+            false,
+            false,
+        ))
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let alg = match parse_custom_crc(args)? {
+            Some(params) => CrcAlg::Custom(params),
+            None => CrcAlg::known(args.get_one::<String>("alg").unwrap())?,
+        };
+        let target = match args.get_one::<String>("target") {
+            Some(t) => Some(
+                u128::from_str_radix(t.trim_start_matches("0x"), 16)
+                    .with_context(|| format!("Invalid target CRC \"{}\"", t))?,
+            ),
+            None => None,
+        };
+        let position = match args.get_one::<String>("offset") {
+            Some(offset) => Some(parse_value_with_prefix(offset)?),
+            None => None,
+        };
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            alg,
+            target,
+            position,
+            args.get_flag("insert"),
+            args.get_flag("bytes"),
+        )))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut val = map_input(&self.file)?.to_vec();
+        let target = match self.target {
+            Some(target) => target,
+            None => self.alg.compute(&val)?,
+        };
+        let forged = forge(&val, &self.alg, target, &self.mode)?;
+        if self.bytes_only {
+            return Ok(forged);
+        }
+        patch(&mut val, &forged, &self.mode, true)?;
+        Ok(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crcapp::CrcParams;
+
+    fn at(pos: &str, insert: bool) -> PatchMode {
+        PatchMode::Write {
+            position: parse_value_with_prefix(pos).unwrap(),
+            insert,
+        }
+    }
+
+    fn forged(val: &[u8], alg: &CrcAlg, target: u128, mode: &PatchMode) -> Vec<u8> {
+        let mut val = val.to_vec();
+        let bytes = forge(&val, alg, target, mode).unwrap();
+        patch(&mut val, &bytes, mode, true).unwrap();
+        val
+    }
+
+    #[test]
+    fn test_forge() {
+        let val = b"some firmware image, with padding: XXXX";
+        let crc32 = CrcAlg::known("CRC_32_ISO_HDLC").unwrap();
+
+        let res = forged(val, &crc32, 0xdeadbeef, &at("-4", false));
+        assert_eq!(res.len(), val.len());
+        assert_eq!(res[..val.len() - 4], val[..val.len() - 4]);
+        assert_eq!(crc32.compute(&res).unwrap(), 0xdeadbeef);
+
+        let res = forged(val, &crc32, 0x12345678, &at("4", true));
+        assert_eq!(res.len(), val.len() + 4);
+        assert_eq!(crc32.compute(&res).unwrap(), 0x12345678);
+
+        // Widths which are not a multiple of 8, and wider than 64 bits
+        for name in ["CRC_5_USB", "CRC_12_DECT", "CRC_82_DARC"] {
+            let alg = CrcAlg::known(name).unwrap();
+            let res = forged(val, &alg, 0x15, &at("0", false));
+            assert_eq!(alg.compute(&res).unwrap(), 0x15);
+        }
+
+        let custom =
+            CrcAlg::Custom(CrcParams::new(16, 0xc867, 0x1234, true, true, 0xffff).unwrap());
+        let res = forged(val, &custom, 0xabcd, &at("10", false));
+        assert_eq!(custom.compute(&res).unwrap(), 0xabcd);
+
+        assert!(forge(val, &custom, 0x10000, &at("10", false)).is_err());
+    }
+}
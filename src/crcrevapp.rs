@@ -0,0 +1,611 @@
+use crate::applet::Applet;
+use crate::bgrepapp::map_input;
+use crate::crcapp::{reflect, register, CrcAlg, CrcParams, ALL_CRCS};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{arg, value_parser, Command};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A message and the bytes of its CRC
+pub type Sample = (Vec<u8>, Vec<u8>);
+
+/// Parse samples, one `hexmessage hexcrc` per line. If `trailing` is set, lines
+/// only contain the message, ending with its CRC, which is returned empty.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_samples(text: &str, trailing: bool) -> Result<Vec<Sample>> {
+    let mut res = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (message, crc) = match (trailing, words.as_slice()) {
+            (true, [message]) => (message, ""),
+            (false, [message, crc]) => (message, *crc),
+            _ => bail!("Invalid sample line \"{}\"", line),
+        };
+        res.push((
+            hex::decode(message).with_context(|| format!("Invalid hex message \"{}\"", message))?,
+            hex::decode(crc).with_context(|| format!("Invalid hex CRC \"{}\"", crc))?,
+        ));
+    }
+    if res.is_empty() {
+        bail!("No samples");
+    }
+    Ok(res)
+}
+
+/// Algorithm matching all the samples given to [`find_known`] or [`search`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Found {
+    pub alg: CrcAlg,
+    /// The CRCs are stored in little endian
+    pub little_endian: bool,
+}
+
+/* Arguments of the `crc` applet computing the algorithm */
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.alg)?;
+        if self.little_endian {
+            write!(f, "  # little endian")?;
+        }
+        Ok(())
+    }
+}
+
+/* Value of the CRC bytes, which are little endian if `little` is set */
+fn crc_value(crc: &[u8], little: bool) -> u128 {
+    let fold = |acc: u128, b: &u8| acc << 8 | *b as u128;
+    if little {
+        crc.iter().rev().fold(0, fold)
+    } else {
+        crc.iter().fold(0, fold)
+    }
+}
+
+/* Byte orders to try for CRCs of `len` bytes */
+fn endiannesses(len: usize) -> &'static [bool] {
+    if len > 1 {
+        &[false, true]
+    } else {
+        &[false]
+    }
+}
+
+/// Find the algorithms of `ALL_CRCS` which give the CRC of all `samples`, in
+/// either endianness. If `width` is set, only algorithms of that width are
+/// checked.
+pub fn find_known(samples: &[Sample], width: Option<u8>) -> Result<Vec<Found>> {
+    let mut res = vec![];
+    for name in ALL_CRCS.iter() {
+        let alg = CrcAlg::Known(name);
+        let alg_width = alg.width() as usize;
+        if width.is_some_and(|w| w as usize != alg_width)
+            || samples
+                .iter()
+                .any(|(_, crc)| crc.len() != alg_width.div_ceil(8))
+        {
+            continue;
+        }
+        for &little in endiannesses(samples[0].1.len()) {
+            let mut matches = true;
+            for (message, crc) in samples {
+                if alg.compute(message)? != crc_value(crc, little) {
+                    matches = false;
+                    break;
+                }
+            }
+            if matches {
+                res.push(Found {
+                    alg,
+                    little_endian: little,
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+/* Polynomials over GF(2), as little endian bit vectors: bit i is the
+ * coefficient of x^i */
+type Poly = Vec<u64>;
+
+fn degree(p: &[u64]) -> Option<usize> {
+    p.iter()
+        .rposition(|&w| w != 0)
+        .map(|i| i * 64 + 63 - p[i].leading_zeros() as usize)
+}
+
+fn set_bit(p: &mut Poly, i: usize) {
+    if p.len() <= i / 64 {
+        p.resize(i / 64 + 1, 0);
+    }
+    p[i / 64] |= 1 << (i % 64);
+}
+
+/* p += q * x^shift */
+fn add_shifted(p: &mut Poly, q: &[u64], shift: usize) {
+    let (words, bits) = (shift / 64, shift % 64);
+    if p.len() < q.len() + words + 1 {
+        p.resize(q.len() + words + 1, 0);
+    }
+    for (i, &w) in q.iter().enumerate() {
+        p[i + words] ^= w << bits;
+        if bits > 0 {
+            p[i + words + 1] ^= w >> (64 - bits);
+        }
+    }
+}
+
+/* Quotient and remainder of a / b, b must not be zero */
+fn divmod(a: &[u64], b: &[u64]) -> (Poly, Poly) {
+    let db = degree(b).unwrap();
+    let mut quotient = vec![];
+    let mut rem = a.to_vec();
+    while let Some(dr) = degree(&rem).filter(|&dr| dr >= db) {
+        add_shifted(&mut rem, b, dr - db);
+        set_bit(&mut quotient, dr - db);
+    }
+    (quotient, rem)
+}
+
+fn gcd(mut a: Poly, mut b: Poly) -> Poly {
+    while degree(&b).is_some() {
+        let rem = divmod(&a, &b).1;
+        a = b;
+        b = rem;
+    }
+    a
+}
+
+/* M(x).x^width + reg(x), where M(x) has the bits of `data` in the order they
+ * are processed, the first one being the highest degree term: the register of
+ * a CRC with a zero init is `reg` after processing `data` iff its polynomial
+ * divides it */
+fn message_poly(data: &[u8], refin: bool, width: u8, reg: u64) -> Poly {
+    let nbits = data.len() * 8;
+    let mut p = vec![0; (nbits + width as usize) / 64 + 1];
+    for (i, &byte) in data.iter().enumerate() {
+        let byte = if refin { byte.reverse_bits() } else { byte };
+        for j in 0..8 {
+            if byte & (0x80 >> j) != 0 {
+                set_bit(&mut p, nbits - 1 - (i * 8 + j) + width as usize);
+            }
+        }
+    }
+    p[0] ^= reg;
+    p
+}
+
+/// Solve a linear system over GF(2) of up to 128 unknowns, given as rows of
+/// coefficients (bit i for unknown i) and values. Returns a solution, with
+/// free unknowns set to 0, and a basis of the kernel, or None if there is no
+/// solution.
+pub fn solve(mut rows: Vec<(u128, bool)>, unknowns: u8) -> Option<(u128, Vec<u128>)> {
+    let mut pivots = vec![];
+    for col in 0..unknowns {
+        let r = pivots.len();
+        if let Some(i) = (r..rows.len()).find(|&i| rows[i].0 >> col & 1 == 1) {
+            rows.swap(r, i);
+            let pivot = rows[r];
+            for (j, row) in rows.iter_mut().enumerate() {
+                if j != r && row.0 >> col & 1 == 1 {
+                    row.0 ^= pivot.0;
+                    row.1 ^= pivot.1;
+                }
+            }
+            pivots.push(col);
+        }
+    }
+    /* Remaining rows are 0 = value */
+    if rows[pivots.len()..].iter().any(|row| row.1) {
+        return None;
+    }
+    let mut solution = 0;
+    for (i, &col) in pivots.iter().enumerate() {
+        if rows[i].1 {
+            solution |= 1 << col;
+        }
+    }
+    let kernel = (0..unknowns)
+        .filter(|col| !pivots.contains(col))
+        .map(|free| {
+            pivots
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| rows[*i].0 >> free & 1 == 1)
+                .fold(1 << free, |v, (_, &col)| v | 1 << col)
+        })
+        .collect();
+    Some((solution, kernel))
+}
+
+/* Degree of the largest cofactor of the polynomial enumerated in `polys` */
+const MAX_COFACTOR_DEGREE: usize = 16;
+/* Largest kernel, in bits, enumerated when solving init */
+const MAX_KERNEL: usize = 8;
+
+/* Candidate polynomials (without the x^width term) given the GCD `g` of the
+ * message polynomials */
+fn polys(g: &[u64], width: u8) -> Result<Vec<u64>> {
+    let w = width as usize;
+    let dg = match degree(g) {
+        Some(dg) if dg >= w => dg,
+        _ => return Ok(vec![]),
+    };
+    let mask = u64::MAX >> (64 - width);
+    let mut res = vec![];
+    if dg - w <= MAX_COFACTOR_DEGREE {
+        for cofactor in 1u64 << (dg - w)..1 << (dg - w + 1) {
+            let (quotient, rem) = divmod(g, &[cofactor]);
+            if degree(&rem).is_none() {
+                res.push(quotient[0] & mask);
+            }
+        }
+    } else if width <= 16 {
+        for poly in 0..1 << width {
+            if degree(&divmod(g, &[poly | 1 << width]).1).is_none() {
+                res.push(poly);
+            }
+        }
+    } else {
+        bail!(
+            "Not enough samples of the same length to find a {}-bit polynomial",
+            width
+        );
+    }
+    Ok(res)
+}
+
+/* Search the parameters of `width` bits CRCs matching `samples`, given as
+ * messages and CRC values, with the given reflections */
+fn search_params(
+    samples: &[(&[u8], u64)],
+    width: u8,
+    refin: bool,
+    refout: bool,
+) -> Result<Vec<CrcParams>> {
+    let refl = |v: u64| if refout { reflect(v, width) } else { v };
+
+    /* The CRCs of two messages of the same length differ by the CRC of their
+     * XOR, without init nor xorout */
+    let mut by_len: BTreeMap<usize, Vec<&(&[u8], u64)>> = BTreeMap::new();
+    for sample in samples {
+        by_len.entry(sample.0.len()).or_default().push(sample);
+    }
+    let mut g: Option<Poly> = None;
+    for group in by_len.values() {
+        for (message, crc) in &group[1..] {
+            let diff: Vec<u8> = message.iter().zip(group[0].0).map(|(a, b)| a ^ b).collect();
+            let f = message_poly(&diff, refin, width, refl(crc ^ group[0].1));
+            g = Some(match g {
+                Some(g) => gcd(g, f),
+                None => f,
+            });
+        }
+    }
+    let g = g.with_context(|| {
+        "At least two samples of the same length are needed to search for the polynomial"
+    })?;
+
+    let mut res = vec![];
+    for poly in polys(&g, width)? {
+        /* Without init and xorout, what remains of the CRCs is linear in
+         * init: f_len(init) ^ xorout */
+        let f = |init: u64, len: usize| refl(register(width, poly, init, refin, &vec![0; len]));
+        let rest: Vec<u64> = samples
+            .iter()
+            .map(|(message, crc)| crc ^ refl(register(width, poly, 0, refin, message)))
+            .collect();
+        let (first_len, first_rest) = (samples[0].0.len(), rest[0]);
+
+        let mut rows = vec![];
+        for ((message, _), r) in samples.iter().zip(&rest) {
+            if message.len() == first_len {
+                continue;
+            }
+            let cols: Vec<u64> = (0..width)
+                .map(|k| f(1 << k, first_len) ^ f(1 << k, message.len()))
+                .collect();
+            for bit in 0..width {
+                let coefs = cols
+                    .iter()
+                    .enumerate()
+                    .fold(0, |c, (k, col)| c | (col >> bit & 1) << k);
+                rows.push((coefs.into(), (first_rest ^ r) >> bit & 1 == 1));
+            }
+        }
+        /* With a single length, init cannot be told from xorout: assume 0 */
+        let (solution, kernel) = if rows.is_empty() {
+            (0, vec![])
+        } else {
+            match solve(rows, width) {
+                Some(solved) => solved,
+                None => continue,
+            }
+        };
+        let kernel = &kernel[..kernel.len().min(MAX_KERNEL)];
+        for combination in 0..1u64 << kernel.len() {
+            let init = kernel
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| combination >> i & 1 == 1)
+                .fold(solution, |init, (_, k)| init ^ k) as u64;
+            let params = CrcParams::new(
+                width,
+                poly,
+                init,
+                refin,
+                refout,
+                first_rest ^ f(init, first_len),
+            )?;
+            if samples.iter().all(|(message, crc)| {
+                refl(register(width, poly, params.init, refin, message)) ^ params.xorout == *crc
+            }) {
+                res.push(params);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Search for the polynomial, init, reflections and xorout of `width` bits CRCs
+/// matching `samples`, in either endianness. The polynomial is found from the
+/// GCD of the differences of samples of the same length, like CRC RevEng does,
+/// then init and xorout from samples of different lengths. If all messages have
+/// the same length, init is assumed to be 0.
+pub fn search(samples: &[Sample], width: u8) -> Result<Vec<Found>> {
+    if samples
+        .iter()
+        .any(|(_, crc)| crc.len() != (width as usize).div_ceil(8))
+    {
+        bail!(
+            "{}-bit CRCs must be {} bytes long",
+            width,
+            width.div_ceil(8)
+        );
+    }
+    let mut res = vec![];
+    for &little in endiannesses(samples[0].1.len()) {
+        let values: Vec<(&[u8], u64)> = samples
+            .iter()
+            .map(|(message, crc)| (&message[..], crc_value(crc, little) as u64))
+            .collect();
+        /* The CRC values must fit in `width` bits */
+        if width < 64 && values.iter().any(|(_, crc)| crc >> width != 0) {
+            continue;
+        }
+        for (refin, refout) in [(false, false), (true, true), (false, true), (true, false)] {
+            for params in search_params(&values, width, refin, refout)? {
+                res.push(Found {
+                    alg: CrcAlg::Custom(params),
+                    little_endian: little,
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+/* Widths tried when the CRC is at the end of the messages */
+const TRAILING_WIDTHS: [u8; 4] = [8, 16, 32, 64];
+
+pub struct CrcRevApplet {
+    file: String,
+    trailing: bool,
+    width: Option<u8>,
+}
+
+impl CrcRevApplet {
+    /// Build a `CrcRevApplet` reading samples from `file` ("-" for stdin), see
+    /// [`parse_samples`] for `trailing`. If `width` is set, only CRCs of this
+    /// width are considered.
+    pub fn with_options(file: &str, trailing: bool, width: Option<u8>) -> Self {
+        Self {
+            file: file.to_string(),
+            trailing,
+            width,
+        }
+    }
+
+    /* Samples with CRCs of `len` bytes, split from the end of messages if
+     * `trailing` is set */
+    fn samples(&self, lines: &[Sample], len: usize) -> Option<Vec<Sample>> {
+        if !self.trailing {
+            return Some(lines.to_vec()).filter(|s| s.iter().all(|(_, crc)| crc.len() == len));
+        }
+        lines
+            .iter()
+            .map(|(message, _)| {
+                let split = message.len().checked_sub(len)?;
+                Some((message[..split].to_vec(), message[split..].to_vec()))
+            })
+            .collect()
+    }
+}
+
+impl Applet for CrcRevApplet {
+    fn command(&self) -> &'static str {
+        "crcrev"
+    }
+
+    fn description(&self) -> &'static str {
+        "find the CRC algorithm of sample messages"
+    }
+
+    fn returns_data(&self) -> bool {
+        false
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(arg!(-t --trailing "the CRC is at the end of each message, lines only contain the message"))
+            .arg(
+                arg!(-w --width <width> "only consider CRCs of this width")
+                    .value_parser(value_parser!(u8).range(3..=64)),
+            )
+            .arg(arg!(<file> "file of \"hexmessage hexcrc\" lines, - for stdin"))
+            .after_help("Known algorithms are checked first. If none matches, the parameters are searched, which requires several samples of the same length, and of different lengths to find init.")
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file: String::new(),
+            trailing: false,
+            width: None,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            args.get_flag("trailing"),
+            args.get_one::<u8>("width").copied(),
+        )))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let data = map_input(&self.file)?;
+        let lines = parse_samples(&String::from_utf8_lossy(&data), self.trailing)?;
+        if lines.iter().any(|(_, crc)| crc.len() != lines[0].1.len()) {
+            bail!("All CRCs must have the same length");
+        }
+
+        let mut found = vec![];
+        if self.trailing {
+            /* Known algorithms have various widths */
+            for len in 1..=16 {
+                if let Some(samples) = self.samples(&lines, len) {
+                    found.extend(find_known(&samples, self.width)?);
+                }
+            }
+        } else {
+            found.extend(find_known(&lines, self.width)?);
+        }
+
+        let mut error = None;
+        if found.is_empty() {
+            let widths = match self.width {
+                Some(width) => vec![width],
+                None if self.trailing => TRAILING_WIDTHS.to_vec(),
+                None if lines[0].1.len() > 8 => {
+                    bail!("CRCs longer than 64 bits cannot be searched")
+                }
+                None => vec![lines[0].1.len() as u8 * 8],
+            };
+            if lines
+                .iter()
+                .all(|(message, _)| message.len() == lines[0].0.len())
+            {
+                eprintln!("All messages have the same length, init cannot be told from xorout: assuming it is 0");
+            }
+            for width in widths {
+                let samples = match self.samples(&lines, (width as usize).div_ceil(8)) {
+                    Some(samples) => samples,
+                    None => continue,
+                };
+                match search(&samples, width) {
+                    Ok(res) => found.extend(res),
+                    Err(e) => error = Some(e),
+                }
+            }
+        }
+
+        if found.is_empty() {
+            return Err(error.unwrap_or_else(|| anyhow!("No CRC algorithm found")));
+        }
+        for f in found {
+            println!("{}", f);
+        }
+
+        /* Return empty Vec as we output directly on stdout */
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_samples(params: &CrcParams, messages: &[&[u8]], little: bool) -> Vec<Sample> {
+        let len = (params.width as usize).div_ceil(8);
+        messages
+            .iter()
+            .map(|m| {
+                let crc = params.compute(m).to_be_bytes()[8 - len..].to_vec();
+                let crc = if little {
+                    crc.into_iter().rev().collect()
+                } else {
+                    crc
+                };
+                (m.to_vec(), crc)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_samples() {
+        let samples = parse_samples("# comment\n0102 abcd\n\n03 ef\n", false).unwrap();
+        assert_eq!(
+            samples,
+            [(vec![1, 2], vec![0xab, 0xcd]), (vec![3], vec![0xef])]
+        );
+        assert_eq!(parse_samples("0102", true).unwrap(), [(vec![1, 2], vec![])]);
+        assert!(parse_samples("0102", false).is_err());
+        assert!(parse_samples("01 zz", false).is_err());
+        assert!(parse_samples("", false).is_err());
+    }
+
+    #[test]
+    fn test_find_known() {
+        let modbus = CrcParams::new(16, 0x8005, 0xffff, true, true, 0).unwrap();
+        let samples = make_samples(&modbus, &[b"123456789", b"toto"], true);
+        assert_eq!(
+            find_known(&samples, None).unwrap(),
+            [Found {
+                alg: CrcAlg::Known("CRC_16_MODBUS"),
+                little_endian: true
+            }]
+        );
+        assert_eq!(find_known(&samples, Some(32)).unwrap(), []);
+    }
+
+    #[test]
+    fn test_search() {
+        let messages: [&[u8]; 6] = [
+            b"first msg",
+            b"other msg",
+            b"3rd msg!!",
+            b"short",
+            b"longer message",
+            b"abc",
+        ];
+        let params = CrcParams::new(16, 0xc867, 0x1234, true, true, 0xffff).unwrap();
+        let samples = make_samples(&params, &messages, false);
+        assert_eq!(
+            search(&samples, 16).unwrap(),
+            [Found {
+                alg: CrcAlg::Custom(params),
+                little_endian: false
+            }]
+        );
+
+        let params = CrcParams::new(32, 0x1edc6f41, 0xdeadbeef, false, true, 0).unwrap();
+        let samples = make_samples(&params, &messages, true);
+        let found = search(&samples, 32).unwrap();
+        assert!(found.contains(&Found {
+            alg: CrcAlg::Custom(params),
+            little_endian: true
+        }));
+
+        // Not enough samples of the same length
+        assert!(search(&samples[2..], 32).is_err());
+        assert!(search(&samples, 16).is_err());
+    }
+}
@@ -1,20 +1,100 @@
-use crate::applet::Applet;
-use anyhow::Result;
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
+use anyhow::{bail, Context, Result};
+use clap::{arg, value_parser, Command};
+use std::io::{Read, Write};
 
-pub struct EntropyApplet {}
+/// Width of the bars drawn by `entropy --graph`
+const GRAPH_WIDTH: usize = 64;
 
-fn entropy(val: &[u8]) -> f64 {
-    if val.is_empty() {
-        return 0.0;
+/// Only report windows whose entropy is above or below a value
+#[derive(Clone, Copy, Debug)]
+pub enum Threshold {
+    Above(f64),
+    Below(f64),
+}
+
+impl Threshold {
+    fn matches(&self, entropy: f64) -> bool {
+        match self {
+            Threshold::Above(t) => entropy >= *t,
+            Threshold::Below(t) => entropy <= *t,
+        }
+    }
+}
+
+pub struct EntropyApplet {
+    /// Block size and step, for the windowed mode
+    window: Option<(usize, usize)>,
+    threshold: Option<Threshold>,
+    graph: bool,
+}
+
+impl EntropyApplet {
+    /// Build an `EntropyApplet` computing the entropy of the whole input, or,
+    /// if `block_size` is set, of each `block_size` bytes window, every `step`
+    /// bytes (defaults to `block_size`).
+    /// In windowed mode, `threshold` selects the windows to report, merged into
+    /// ranges, and `graph` draws the entropy of each window as a bar.
+    pub fn with_options(
+        block_size: Option<usize>,
+        step: Option<usize>,
+        threshold: Option<Threshold>,
+        graph: bool,
+    ) -> Result<Self> {
+        let window = match (block_size, step) {
+            (Some(0), _) | (_, Some(0)) => bail!("block size and step must not be 0"),
+            (Some(size), step) => Some((size, step.unwrap_or(size))),
+            (None, None) if threshold.is_none() && !graph => None,
+            (None, _) => bail!("windowed options require a block size"),
+        };
+        if graph && threshold.is_some() {
+            bail!("graph output is not supported with a threshold");
+        }
+        Ok(Self {
+            window,
+            threshold,
+            graph,
+        })
+    }
+
+    fn write_window(&self, output: &mut dyn Write, offset: u64, entropy: f64) -> Result<()> {
+        if self.graph {
+            let bar = "#".repeat((entropy * GRAPH_WIDTH as f64).round() as usize);
+            write!(
+                output,
+                "0x{:08x} {:.3} |{:<width$}|",
+                offset,
+                entropy,
+                bar,
+                width = GRAPH_WIDTH
+            )?;
+        } else {
+            write!(output, "0x{:x} {:.3}", offset, entropy)?;
+        }
+        Ok(())
     }
+}
+
+/// Compute the Shannon entropy of `val`, normalized between 0 and 1.
+pub fn entropy(val: &[u8]) -> f64 {
     /* Compute how many times each value appears */
-    let mut counts: [i64; 256] = [0; 256];
+    let mut counts: [u64; 256] = [0; 256];
     for v in val.iter() {
         counts[*v as usize] += 1
     }
+    entropy_from_counts(&counts)
+}
+
+/// Compute the Shannon entropy, normalized between 0 and 1, of data in which
+/// each byte value `b` appears `counts[b]` times.
+pub fn entropy_from_counts(counts: &[u64; 256]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
 
     let mut res: f64 = 0.0;
-    let len: f64 = val.len() as f64;
+    let len: f64 = total as f64;
 
     /* Compute entropy */
     for count in counts.iter() {
@@ -26,6 +106,59 @@ fn entropy(val: &[u8]) -> f64 {
     res
 }
 
+/// Compute the entropy of the `block_size` bytes windows of `input`, every
+/// `step` bytes, calling `found` with the offset, length and entropy of each
+/// window. The last window is shorter if the input does not end on a window.
+pub fn windowed_entropy(
+    input: &mut dyn Read,
+    block_size: usize,
+    step: usize,
+    found: &mut dyn FnMut(u64, usize, f64) -> Result<()>,
+) -> Result<()> {
+    let mut chunk = vec![0; STREAM_BUF_SIZE];
+    /* Data from input offset buf_offset, and offset of the next window */
+    let mut buf = Vec::new();
+    let mut buf_offset = 0u64;
+    let mut offset = 0u64;
+    let mut covered = 0u64;
+    loop {
+        let len = read_chunk(input, &mut chunk)?;
+        buf.extend_from_slice(&chunk[..len]);
+        let end = buf_offset + buf.len() as u64;
+        while offset + block_size as u64 <= end {
+            let start = (offset - buf_offset) as usize;
+            found(offset, block_size, entropy(&buf[start..start + block_size]))?;
+            covered = offset + block_size as u64;
+            offset += step as u64;
+        }
+        if len < chunk.len() {
+            if offset < end && covered < end {
+                let start = (offset - buf_offset) as usize;
+                found(offset, buf.len() - start, entropy(&buf[start..]))?;
+            }
+            return Ok(());
+        }
+        /* Drop data before the next window */
+        let consumed = offset.min(end) - buf_offset;
+        buf.drain(..consumed as usize);
+        buf_offset += consumed;
+    }
+}
+
+/// Printable ASCII chars, including whitespace
+pub fn is_printable(c: u8) -> bool {
+    c.is_ascii_graphic() || c.is_ascii_whitespace()
+}
+
+/// Compute the ratio of printable ASCII chars (including whitespace) in `val`.
+pub fn printable_ratio(val: &[u8]) -> f64 {
+    if val.is_empty() {
+        return 0.0;
+    }
+    let printable = val.iter().filter(|c| is_printable(**c)).count();
+    printable as f64 / val.len() as f64
+}
+
 impl Applet for EntropyApplet {
     fn command(&self) -> &'static str {
         "entropy"
@@ -34,19 +167,140 @@ impl Applet for EntropyApplet {
         "compute file entropy"
     }
 
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-b --"block-size" <size> "compute the entropy of each <size> bytes window")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                arg!(-s --step <step> "offset between windows, defaults to the block size")
+                    .value_parser(value_parser!(usize))
+                    .requires("block-size"),
+            )
+            .arg(
+                arg!(-a --above <entropy> "only print ranges with an entropy above <entropy>")
+                    .value_parser(value_parser!(f64))
+                    .requires("block-size")
+                    .conflicts_with("below"),
+            )
+            .arg(
+                arg!(-B --below <entropy> "only print ranges with an entropy below <entropy>")
+                    .value_parser(value_parser!(f64))
+                    .requires("block-size"),
+            )
+            .arg(
+                arg!(-g --graph "draw the entropy of each window as a bar")
+                    .requires("block-size")
+                    .conflicts_with_all(["above", "below"]),
+            )
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
     fn new() -> Box<dyn Applet> {
-        Box::new(Self {})
+        Box::new(Self {
+            window: None,
+            threshold: None,
+            graph: false,
+        })
     }
 
-    fn parse_args(&self, _args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {}))
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let threshold = match (args.get_one::<f64>("above"), args.get_one::<f64>("below")) {
+            (Some(t), _) => Some(Threshold::Above(*t)),
+            (_, Some(t)) => Some(Threshold::Below(*t)),
+            _ => None,
+        };
+        Ok(Box::new(Self::with_options(
+            args.get_one::<usize>("block-size").copied(),
+            args.get_one::<usize>("step").copied(),
+            threshold,
+            args.get_flag("graph"),
+        )?))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        if self.window.is_some() {
+            let mut res = vec![];
+            self.process_stream(&mut val.as_slice(), &mut res)?;
+            return Ok(res);
+        }
         Ok(format!("{:.3}", entropy(val.as_slice()))
             .as_bytes()
             .to_vec())
     }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let (block_size, step) = match self.window {
+            Some(window) => window,
+            None => {
+                let mut val = vec![];
+                input
+                    .read_to_end(&mut val)
+                    .context("Reading input failed")?;
+                output.write_all(&self.process(val)?)?;
+                return Ok(());
+            }
+        };
+
+        /* Lines are separated, not terminated, by newlines, like other applets' output */
+        let mut first = true;
+        let mut newline = |output: &mut dyn Write| -> Result<()> {
+            if !first {
+                output.write_all(b"\n")?;
+            }
+            first = false;
+            Ok(())
+        };
+
+        let threshold = match self.threshold {
+            Some(threshold) => threshold,
+            None => {
+                return windowed_entropy(input, block_size, step, &mut |offset, _, ent| {
+                    newline(output)?;
+                    self.write_window(output, offset, ent)
+                })
+            }
+        };
+
+        /* Merge consecutive matching windows: start, end, sum of entropies, count */
+        let mut range: Option<(u64, u64, f64, usize)> = None;
+        let mut write_range =
+            |output: &mut dyn Write, range: (u64, u64, f64, usize)| -> Result<()> {
+                newline(output)?;
+                let (start, end, sum, count) = range;
+                write!(
+                    output,
+                    "0x{:x}-0x{:x} {:.3}",
+                    start,
+                    end,
+                    sum / count as f64
+                )?;
+                Ok(())
+            };
+        windowed_entropy(input, block_size, step, &mut |offset, len, ent| {
+            let end = offset + len as u64;
+            range = match range {
+                Some((start, range_end, sum, count))
+                    if threshold.matches(ent) && offset <= range_end =>
+                {
+                    Some((start, end, sum + ent, count + 1))
+                }
+                _ => {
+                    if let Some(range) = range {
+                        write_range(output, range)?;
+                    }
+                    threshold.matches(ent).then_some((offset, end, ent, 1))
+                }
+            };
+            Ok(())
+        })?;
+        if let Some(range) = range {
+            write_range(output, range)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -59,7 +313,7 @@ mod tests {
 
     #[test]
     fn test() {
-        let ent = EntropyApplet {};
+        let ent = EntropyApplet::with_options(None, None, None, false).unwrap();
         assert_eq!(run_ent(&ent, Vec::new()), "0.000");
         assert_eq!(run_ent(&ent, vec![1, 2, 3, 4]), "0.250");
         let mut all_bytes: Vec<u8> = Vec::with_capacity(256);
@@ -68,4 +322,63 @@ mod tests {
         }
         assert_eq!(run_ent(&ent, all_bytes), "0.999");
     }
+
+    #[test]
+    fn test_windowed_entropy() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 4) as u8).collect();
+        let mut windows = vec![];
+        windowed_entropy(&mut data.as_slice(), 100_000, 30_000, &mut |o, l, e| {
+            windows.push((o, l, e));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            windows,
+            [
+                (0, 100_000, 0.25),
+                (30_000, 100_000, 0.25),
+                (60_000, 100_000, 0.25),
+                (90_000, 100_000, 0.25),
+                (120_000, 80_000, 0.25)
+            ]
+        );
+
+        let ent = EntropyApplet::with_options(Some(4), Some(8), None, false).unwrap();
+        assert_eq!(
+            run_ent(&ent, b"aaaaxxxxabcdxxxxab".to_vec()),
+            "0x0 0.000\n0x8 0.250\n0x10 0.125"
+        );
+        let ent = EntropyApplet::with_options(Some(4), None, None, true).unwrap();
+        assert_eq!(
+            run_ent(&ent, b"abcd".to_vec()),
+            format!("0x00000000 0.250 |{:<64}|", "#".repeat(16))
+        );
+        assert!(EntropyApplet::with_options(Some(0), None, None, false).is_err());
+        assert!(EntropyApplet::with_options(None, None, None, true).is_err());
+    }
+
+    #[test]
+    fn test_entropy_threshold() {
+        let mut data = vec![0u8; 64];
+        data.extend(0..=255u8);
+        data.extend([0u8; 64]);
+        data.extend(0..=255u8);
+        let above =
+            EntropyApplet::with_options(Some(32), Some(16), Some(Threshold::Above(0.5)), false)
+                .unwrap();
+        assert_eq!(
+            run_ent(&above, data.clone()),
+            "0x40-0x140 0.625\n0x180-0x280 0.625"
+        );
+        let below = EntropyApplet::with_options(Some(32), None, Some(Threshold::Below(0.0)), false)
+            .unwrap();
+        assert_eq!(run_ent(&below, data), "0x0-0x40 0.000\n0x140-0x180 0.000");
+    }
+
+    #[test]
+    fn test_printable_ratio() {
+        assert_eq!(printable_ratio(b""), 0.0);
+        assert_eq!(printable_ratio(b"ab c\n"), 1.0);
+        assert_eq!(printable_ratio(b"ab\x00\xff"), 0.5);
+    }
 }
@@ -4,8 +4,9 @@ use anyhow::{Context, Result};
 use clap::{arg, Command};
 use htmlentity::entity::{decode, encode, CharacterSet, EncodeType};
 
+/// Escape types supported by `escape`
 #[derive(clap::ValueEnum, Clone, Default, Debug)]
-enum EscType {
+pub enum EscType {
     #[default]
     Generic,
     Single,
@@ -24,7 +25,8 @@ const SHELL_CHARS: &[u8; 4] = b"`$\"\\";
 // > escaped using a backslash. The backslash preceding the ‘!’ is not removed.
 const BASH_CHARS: &[u8; 5] = b"`$\"\\!";
 
-trait SliceEsc {
+/// Escape byte slices
+pub trait SliceEsc {
     fn escape(&self, esc_type: &EscType) -> Vec<u8>;
     fn escape_chars(&self, chars: &[u8]) -> Vec<u8>;
     fn escape_bash_single(&self) -> Vec<u8>;
@@ -78,6 +80,26 @@ pub struct EscapeApplet {
     multiline: bool,
 }
 
+impl EscapeApplet {
+    /// Build an `EscapeApplet` escaping for `esc_type`.
+    /// Surrounding quotes are detected, and kept, unless `no_detect` is set.
+    /// Unless `no_quote` is set, the result is quoted.
+    /// Unless `multiline` is set, the input is trimmed.
+    pub fn with_options(
+        esc_type: EscType,
+        no_quote: bool,
+        no_detect: bool,
+        multiline: bool,
+    ) -> Self {
+        Self {
+            esc_type,
+            no_quote,
+            no_detect,
+            multiline,
+        }
+    }
+}
+
 impl Applet for EscapeApplet {
     fn command(&self) -> &'static str {
         "escape"
@@ -101,12 +123,12 @@ impl Applet for EscapeApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            esc_type: args.get_one::<EscType>("type").unwrap().clone(),
-            no_quote: args.get_flag("no-quote"),
-            no_detect: args.get_flag("no-detect"),
-            multiline: args.get_flag("multiline"),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_one::<EscType>("type").unwrap().clone(),
+            args.get_flag("no-quote"),
+            args.get_flag("no-detect"),
+            args.get_flag("multiline"),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
@@ -151,14 +173,15 @@ impl Applet for EscapeApplet {
         };
 
         let escaped = to_escape_nq.escape(&self.esc_type);
-        if self.no_quote || quote.is_none() {
-            Ok(escaped)
-        } else {
-            let mut res = Vec::<u8>::with_capacity(escaped.len() + 2);
-            res.push(quote.unwrap());
-            res.extend(escaped);
-            res.push(quote.unwrap());
-            Ok(res)
+        match quote {
+            Some(quote) if !self.no_quote => {
+                let mut res = Vec::<u8>::with_capacity(escaped.len() + 2);
+                res.push(quote);
+                res.extend(escaped);
+                res.push(quote);
+                Ok(res)
+            }
+            _ => Ok(escaped),
         }
     }
 
@@ -177,6 +200,17 @@ pub struct UnEscapeApplet {
     html_entities: bool,
 }
 
+impl UnEscapeApplet {
+    /// Build an `UnEscapeApplet`, unescaping HTML entities instead of backslash escapes
+    /// if `html_entities` is set. Unless `multiline` is set, the input is trimmed.
+    pub fn with_options(multiline: bool, html_entities: bool) -> Self {
+        Self {
+            multiline,
+            html_entities,
+        }
+    }
+}
+
 impl Applet for UnEscapeApplet {
     fn command(&self) -> &'static str {
         "unescape"
@@ -201,20 +235,13 @@ impl Applet for UnEscapeApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            multiline: args.get_flag("multiline"),
-            html_entities: args.get_flag("html"),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_flag("multiline"),
+            args.get_flag("html"),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
-        enum EscapeState {
-            Backslash,
-            Hex1,
-            Hex2,
-            Normal,
-        }
-
         let to_unescape = if self.multiline {
             val
         } else {
@@ -226,51 +253,64 @@ impl Applet for UnEscapeApplet {
             return Ok(decode(&to_unescape).into_bytes());
         };
 
-        let mut res = Vec::with_capacity(to_unescape.len());
-        let mut state = EscapeState::Normal;
-        let mut hexchars: [u8; 2] = [0, 0];
-        for c in to_unescape.iter() {
-            state = match (state, c) {
-                (EscapeState::Normal, b'\\') => EscapeState::Backslash,
-                (EscapeState::Normal, c) => {
-                    res.push(*c);
-                    EscapeState::Normal
-                }
-                (EscapeState::Backslash, b'x') => EscapeState::Hex1,
-                (EscapeState::Backslash, b't') => {
-                    res.push(0x9);
-                    EscapeState::Normal
-                }
-                (EscapeState::Backslash, b'n') => {
-                    res.push(0xA);
-                    EscapeState::Normal
-                }
-                (EscapeState::Backslash, b'r') => {
-                    res.push(0xD);
-                    EscapeState::Normal
-                }
-                (EscapeState::Backslash, c) => {
-                    res.push(*c);
-                    EscapeState::Normal
-                }
-                (EscapeState::Hex1, c) => {
-                    hexchars[0] = *c;
-                    EscapeState::Hex2
-                }
-                (EscapeState::Hex2, c) => {
-                    hexchars[1] = *c;
-                    res.push(
-                        u8::from_str_radix(
-                            std::str::from_utf8(&hexchars)
-                                .context("invalid hex chars in escaped string")?,
-                            16,
-                        )
-                        .context("invalid hex char in escaped string")?,
-                    );
-                    EscapeState::Normal
-                }
-            };
-        }
-        Ok(res)
+        unescape(&to_unescape)
+    }
+}
+
+/// Unescape backslash-escaped chars: `\t`, `\n`, `\r`, `\xNN`, and any other
+/// escaped char which is kept as is.
+pub fn unescape(val: &[u8]) -> Result<Vec<u8>> {
+    enum EscapeState {
+        Backslash,
+        Hex1,
+        Hex2,
+        Normal,
+    }
+
+    let mut res = Vec::with_capacity(val.len());
+    let mut state = EscapeState::Normal;
+    let mut hexchars: [u8; 2] = [0, 0];
+    for c in val.iter() {
+        state = match (state, c) {
+            (EscapeState::Normal, b'\\') => EscapeState::Backslash,
+            (EscapeState::Normal, c) => {
+                res.push(*c);
+                EscapeState::Normal
+            }
+            (EscapeState::Backslash, b'x') => EscapeState::Hex1,
+            (EscapeState::Backslash, b't') => {
+                res.push(0x9);
+                EscapeState::Normal
+            }
+            (EscapeState::Backslash, b'n') => {
+                res.push(0xA);
+                EscapeState::Normal
+            }
+            (EscapeState::Backslash, b'r') => {
+                res.push(0xD);
+                EscapeState::Normal
+            }
+            (EscapeState::Backslash, c) => {
+                res.push(*c);
+                EscapeState::Normal
+            }
+            (EscapeState::Hex1, c) => {
+                hexchars[0] = *c;
+                EscapeState::Hex2
+            }
+            (EscapeState::Hex2, c) => {
+                hexchars[1] = *c;
+                res.push(
+                    u8::from_str_radix(
+                        std::str::from_utf8(&hexchars)
+                            .context("invalid hex chars in escaped string")?,
+                        16,
+                    )
+                    .context("invalid hex char in escaped string")?,
+                );
+                EscapeState::Normal
+            }
+        };
     }
+    Ok(res)
 }
@@ -21,7 +21,8 @@ pub struct FindSoApplet {
     skip_symlinks: bool,
 }
 
-fn parse_ld_so_conf(ldconf_path: &str) -> Result<Vec<PathBuf>> {
+/// Get the list of paths from an `ld.so.conf` file, following `include` directives.
+pub fn parse_ld_so_conf(ldconf_path: &str) -> Result<Vec<PathBuf>> {
     let conf_file = fs::read_to_string(ldconf_path)
         .with_context(|| format!("Could not read config file \"{}\"", ldconf_path))?;
     let conf_lines = conf_file.split('\n');
@@ -46,6 +47,29 @@ fn parse_ld_so_conf(ldconf_path: &str) -> Result<Vec<PathBuf>> {
     Ok(ldpaths)
 }
 
+impl FindSoApplet {
+    /// Build a `FindSoApplet` looking for `function` in `files`.
+    /// If `is_ref` is set, the first file is an ELF whose libraries are also searched.
+    /// Relative library names are resolved using `paths`, if set.
+    pub fn with_options(
+        function: &str,
+        files: Vec<PathBuf>,
+        is_ref: bool,
+        paths: Option<Vec<PathBuf>>,
+        quiet: bool,
+        skip_symlinks: bool,
+    ) -> Self {
+        Self {
+            function: Some(function.to_string()),
+            files: Some(files),
+            is_ref,
+            paths,
+            quiet,
+            skip_symlinks,
+        }
+    }
+}
+
 impl Applet for FindSoApplet {
     fn command(&self) -> &'static str {
         "findso"
@@ -144,14 +168,14 @@ impl Applet for FindSoApplet {
             );
         }
 
-        Ok(Box::new(Self {
-            files: Some(filenames),
-            function: Some(function_val.to_string()),
-            is_ref: args.get_flag("ref"),
+        Ok(Box::new(Self::with_options(
+            function_val,
+            filenames,
+            args.get_flag("ref"),
             paths,
-            quiet: args.get_flag("quiet"),
-            skip_symlinks: args.get_flag("skip-symlinks"),
-        }))
+            args.get_flag("quiet"),
+            args.get_flag("skip-symlinks"),
+        )))
     }
 
     fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
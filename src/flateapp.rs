@@ -1,11 +1,100 @@
-use crate::applet::Applet;
-use anyhow::Result;
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
+use adler2::Adler32;
+use anyhow::{bail, Result};
 use clap::{arg, value_parser, Command};
-use miniz_oxide::{deflate, inflate, DataFormat};
+use crc::{Crc, CRC_32_ISO_HDLC};
+use miniz_oxide::deflate::core::{create_comp_flags_from_zip_params, CompressorOxide};
+use miniz_oxide::inflate::stream::InflateState;
+use miniz_oxide::{deflate, inflate, DataFormat, MZError, MZFlush, MZStatus};
+use std::io::{Read, Write};
+
+/// Container around deflate data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlateFormat {
+    Raw,
+    Zlib,
+    Gzip,
+}
+
+// gzip magic, followed by the deflate method
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 8];
+const GZIP_FHCRC: u8 = 2;
+const GZIP_FEXTRA: u8 = 4;
+const GZIP_FNAME: u8 = 8;
+const GZIP_FCOMMENT: u8 = 16;
+
+static CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+    // deflate method, window of 32K max, no preset dictionary, valid check bits
+    cmf & 0xf == 8
+        && cmf >> 4 <= 7
+        && flg & 0x20 == 0
+        && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0
+}
+
+/// Detect the format of compressed data from its first bytes.
+pub fn detect_format(val: &[u8]) -> FlateFormat {
+    if val.starts_with(&GZIP_MAGIC) {
+        FlateFormat::Gzip
+    } else if val.len() >= 2 && is_zlib_header(val[0], val[1]) {
+        FlateFormat::Zlib
+    } else {
+        FlateFormat::Raw
+    }
+}
 
 pub struct DeflateApplet {
-    format: DataFormat,
+    format: FlateFormat,
     level: u8,
+    name: Option<Vec<u8>>,
+    mtime: u32,
+}
+
+impl DeflateApplet {
+    /// Build a `DeflateApplet` producing `format` data, with compression `level` (1 to 10).
+    /// `name` and `mtime` are stored in the gzip header.
+    pub fn with_options(
+        format: FlateFormat,
+        level: u8,
+        name: Option<&str>,
+        mtime: u32,
+    ) -> Result<Self> {
+        if name.is_some_and(|n| n.contains('\0')) {
+            bail!("gzip file name cannot contain NUL bytes");
+        }
+        Ok(Self {
+            format,
+            level,
+            name: name.map(|n| n.as_bytes().to_vec()),
+            mtime,
+        })
+    }
+
+    fn gzip_header(&self) -> Vec<u8> {
+        let mut header = GZIP_MAGIC.to_vec();
+        header.push(if self.name.is_some() { GZIP_FNAME } else { 0 });
+        header.extend(self.mtime.to_le_bytes());
+        // Extra flags: maximum compression or fastest algorithm
+        header.push(match self.level {
+            1 => 4,
+            9.. => 2,
+            _ => 0,
+        });
+        // OS: unknown
+        header.push(255);
+        if let Some(name) = &self.name {
+            header.extend(name);
+            header.push(0);
+        }
+        header
+    }
+}
+
+fn gzip_trailer(crc: u32, len: u32) -> Vec<u8> {
+    let mut trailer = crc.to_le_bytes().to_vec();
+    trailer.extend(len.to_le_bytes());
+    trailer
 }
 
 impl Applet for DeflateApplet {
@@ -22,43 +111,356 @@ impl Applet for DeflateApplet {
                     .default_value("6"),
             )
             .arg(arg!(-z --zlib "add Zlib header"))
+            .arg(arg!(-g --gzip "add gzip header").conflicts_with("zlib"))
+            .arg(arg!(-n --name <name> "file name to store in the gzip header").requires("gzip"))
+            .arg(
+                arg!(-m --mtime <mtime> "modification time (epoch) to store in the gzip header")
+                    .value_parser(value_parser!(u32))
+                    .requires("gzip"),
+            )
             .arg(arg!([value]  "input value, reads from stdin if not present"))
     }
 
     fn description(&self) -> &'static str {
-        "(raw) deflate compression"
+        "deflate compression (raw, Zlib or gzip)"
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
         let f = if args.get_flag("zlib") {
-            DataFormat::Zlib
+            FlateFormat::Zlib
+        } else if args.get_flag("gzip") {
+            FlateFormat::Gzip
         } else {
-            DataFormat::Raw
+            FlateFormat::Raw
         };
         let l: &u8 = args.get_one("level").unwrap();
-        Ok(Box::new(Self {
-            format: f,
-            level: *l,
-        }))
+        Ok(Box::new(Self::with_options(
+            f,
+            *l,
+            args.get_one::<String>("name").map(|n| n.as_str()),
+            *args.get_one::<u32>("mtime").unwrap_or(&0),
+        )?))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
         match self.format {
-            DataFormat::Raw => Ok(deflate::compress_to_vec(val.as_slice(), self.level)),
-            _ => Ok(deflate::compress_to_vec_zlib(val.as_slice(), self.level)),
+            FlateFormat::Raw => Ok(deflate::compress_to_vec(val.as_slice(), self.level)),
+            FlateFormat::Zlib => Ok(deflate::compress_to_vec_zlib(val.as_slice(), self.level)),
+            FlateFormat::Gzip => {
+                let mut res = self.gzip_header();
+                res.extend(deflate::compress_to_vec(val.as_slice(), self.level));
+                // The size is stored modulo 2^32
+                res.extend(gzip_trailer(CRC32.checksum(&val), val.len() as u32));
+                Ok(res)
+            }
+        }
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        // Same parameters as `compress_to_vec` and `compress_to_vec_zlib`
+        let window_bits = if self.format == FlateFormat::Zlib {
+            1
+        } else {
+            0
+        };
+        let flags = create_comp_flags_from_zip_params(self.level.into(), window_bits, 0);
+        let mut compressor = Box::new(CompressorOxide::new(flags));
+
+        let gzip = self.format == FlateFormat::Gzip;
+        let mut crc = CRC32.digest();
+        let mut size: u32 = 0;
+        if gzip {
+            output.write_all(&self.gzip_header())?;
         }
+
+        let mut inbuf = vec![0; STREAM_BUF_SIZE];
+        let mut outbuf = vec![0; STREAM_BUF_SIZE];
+        'outer: loop {
+            let len = read_chunk(input, &mut inbuf)?;
+            let flush = if len == 0 {
+                MZFlush::Finish
+            } else {
+                MZFlush::None
+            };
+            if gzip {
+                crc.update(&inbuf[..len]);
+                size = size.wrapping_add(len as u32);
+            }
+            let mut next_in = &inbuf[..len];
+            loop {
+                let res = deflate::stream::deflate(&mut compressor, next_in, &mut outbuf, flush);
+                output.write_all(&outbuf[..res.bytes_written])?;
+                next_in = &next_in[res.bytes_consumed..];
+                match res.status {
+                    Ok(MZStatus::StreamEnd) => break 'outer,
+                    Ok(_) if !next_in.is_empty() || flush == MZFlush::Finish => continue,
+                    // Everything was consumed, or no progress could be made: get more input
+                    Ok(_) | Err(MZError::Buf) => break,
+                    Err(e) => bail!("Compression failed: {:?}", e),
+                }
+            }
+        }
+        if gzip {
+            output.write_all(&gzip_trailer(crc.finalize(), size))?;
+        }
+        Ok(())
     }
 
     fn new() -> Box<dyn Applet> {
         Box::new(Self {
-            format: DataFormat::Raw,
+            format: FlateFormat::Raw,
             level: 6,
+            name: None,
+            mtime: 0,
+        })
+    }
+}
+
+/// Buffered input, to parse the headers and trailers around deflate data
+struct FlateInput<'a> {
+    input: &'a mut dyn Read,
+    buf: Vec<u8>,
+    pos: usize,
+    end: usize,
+    // Total number of bytes consumed
+    consumed: usize,
+}
+
+impl<'a> FlateInput<'a> {
+    fn new(input: &'a mut dyn Read) -> Self {
+        Self {
+            input,
+            buf: vec![0; STREAM_BUF_SIZE],
+            pos: 0,
+            end: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Return the buffered data, reading more if needed. Empty at end of input.
+    fn data(&mut self) -> Result<&[u8]> {
+        if self.pos == self.end {
+            self.end = read_chunk(self.input, &mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.end])
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+        self.consumed += len;
+    }
+
+    /// Read exactly `len` bytes.
+    fn take(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut res = Vec::with_capacity(len);
+        while res.len() < len {
+            let data = self.data()?;
+            if data.is_empty() {
+                bail!("truncated stream");
+            }
+            let n = data.len().min(len - res.len());
+            res.extend_from_slice(&data[..n]);
+            self.consume(n);
+        }
+        Ok(res)
+    }
+
+    /// Skip a NUL-terminated string.
+    fn skip_cstr(&mut self) -> Result<()> {
+        while self.take(1)?[0] != 0 {}
+        Ok(())
+    }
+
+    fn take_u32(&mut self, big_endian: bool) -> Result<u32> {
+        let b = self.take(4)?;
+        let bytes = [b[0], b[1], b[2], b[3]];
+        Ok(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
         })
     }
 }
 
+fn parse_gzip_header(input: &mut FlateInput) -> Result<()> {
+    let header = input.take(10)?;
+    if !header.starts_with(&GZIP_MAGIC) {
+        bail!("invalid gzip header");
+    }
+    let flags = header[3];
+    if flags & GZIP_FEXTRA != 0 {
+        let len = input.take(2)?;
+        input.take(u16::from_le_bytes([len[0], len[1]]).into())?;
+    }
+    if flags & GZIP_FNAME != 0 {
+        input.skip_cstr()?;
+    }
+    if flags & GZIP_FCOMMENT != 0 {
+        input.skip_cstr()?;
+    }
+    if flags & GZIP_FHCRC != 0 {
+        input.take(2)?;
+    }
+    Ok(())
+}
+
+/// Inflate raw deflate data from `input` up to the end of the deflate stream,
+/// passing the decompressed data to `check` as well as `output`.
+fn inflate_raw(
+    input: &mut FlateInput,
+    output: &mut dyn Write,
+    check: &mut dyn FnMut(&[u8]),
+) -> Result<()> {
+    let mut state = InflateState::new_boxed(DataFormat::Raw);
+    let mut outbuf = vec![0; STREAM_BUF_SIZE];
+    loop {
+        let data = input.data()?;
+        let res = inflate::stream::inflate(&mut state, data, &mut outbuf, MZFlush::None);
+        input.consume(res.bytes_consumed);
+        output.write_all(&outbuf[..res.bytes_written])?;
+        check(&outbuf[..res.bytes_written]);
+        match res.status {
+            Ok(MZStatus::StreamEnd) => return Ok(()),
+            Ok(_) if res.bytes_written > 0 || res.bytes_consumed > 0 => continue,
+            // No progress: the decompressor needs more input
+            Ok(_) | Err(MZError::Buf) => bail!("truncated stream"),
+            Err(e) => bail!("{:?}", e),
+        }
+    }
+}
+
+/// Inflate a gzip member, after its header.
+fn inflate_gzip_body(
+    input: &mut FlateInput,
+    output: &mut dyn Write,
+    report: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let mut crc = CRC32.digest();
+    let mut size: u32 = 0;
+    inflate_raw(input, output, &mut |data| {
+        crc.update(data);
+        size = size.wrapping_add(data.len() as u32);
+    })?;
+    let (expected_crc, expected_size) = (input.take_u32(false)?, input.take_u32(false)?);
+    let crc = crc.finalize();
+    if expected_crc != crc {
+        report(&format!(
+            "CRC32 mismatch: expected {:08x}, got {:08x}",
+            expected_crc, crc
+        ));
+    }
+    if expected_size != size {
+        report(&format!(
+            "size mismatch: expected {}, got {}",
+            expected_size, size
+        ));
+    }
+    Ok(())
+}
+
+/// Inflate a single `format` stream, passing checksum mismatches to `report`.
+fn inflate_member(
+    input: &mut FlateInput,
+    format: FlateFormat,
+    output: &mut dyn Write,
+    report: &mut dyn FnMut(&str),
+) -> Result<()> {
+    match format {
+        FlateFormat::Raw => inflate_raw(input, output, &mut |_| {}),
+        FlateFormat::Zlib => {
+            let header = input.take(2)?;
+            if !is_zlib_header(header[0], header[1]) {
+                bail!("invalid Zlib header");
+            }
+            let mut adler = Adler32::new();
+            inflate_raw(input, output, &mut |data| adler.write_slice(data))?;
+            let expected = input.take_u32(true)?;
+            if expected != adler.checksum() {
+                report(&format!(
+                    "Adler-32 mismatch: expected {:08x}, got {:08x}",
+                    expected,
+                    adler.checksum()
+                ));
+            }
+            Ok(())
+        }
+        FlateFormat::Gzip => {
+            parse_gzip_header(input)?;
+            inflate_gzip_body(input, output, report)
+        }
+    }
+}
+
+/* Decompress `input` to `output`, detecting its format if `format` is None.
+ * Checksum mismatches and trailing data are passed to `report` and do not
+ * stop decompression. Errors are either I/O errors or invalid data, in which
+ * case what could be decompressed has already been written to `output`.
+ */
+fn inflate_stream(
+    format: Option<FlateFormat>,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    report: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let mut input = FlateInput::new(input);
+    let format = match format {
+        Some(format) => format,
+        None => detect_format(input.data()?),
+    };
+    inflate_member(&mut input, format, output, report)?;
+    // Concatenated gzip members
+    while format == FlateFormat::Gzip && !input.data()?.is_empty() {
+        if let Err(e) = parse_gzip_header(&mut input) {
+            report(&format!("trailing data after gzip member ignored ({})", e));
+            return Ok(());
+        }
+        inflate_gzip_body(&mut input, output, report)?;
+    }
+    if !input.data()?.is_empty() {
+        let name = if format == FlateFormat::Zlib {
+            "Zlib"
+        } else {
+            "deflate"
+        };
+        report(&format!("trailing data after {} stream ignored", name));
+    }
+    Ok(())
+}
+
+/// Decompress the single stream at the start of `val`, ignoring trailing data.
+/// Return the decompressed data and the length of the stream, including headers
+/// and trailers. Fail on any error, including checksum mismatches.
+pub fn inflate_prefix(val: &[u8], format: FlateFormat) -> Result<(Vec<u8>, usize)> {
+    let mut reader = val;
+    let mut input = FlateInput::new(&mut reader);
+    let mut res = vec![];
+    let mut error = None;
+    inflate_member(&mut input, format, &mut res, &mut |msg| {
+        error.get_or_insert_with(|| msg.to_string());
+    })?;
+    match error {
+        Some(msg) => bail!(msg),
+        None => Ok((res, input.consumed)),
+    }
+}
+
+/// Decompress `val`, detecting its format if `format` is None. Unlike the
+/// applet, fail on any error, including checksum mismatches.
+pub fn inflate_all(val: &[u8], format: Option<FlateFormat>) -> Result<Vec<u8>> {
+    let mut res = vec![];
+    let mut error = None;
+    inflate_stream(format, &mut &val[..], &mut res, &mut |msg| {
+        error.get_or_insert_with(|| msg.to_string());
+    })?;
+    match error {
+        Some(msg) => bail!(msg),
+        None => Ok(res),
+    }
+}
+
 pub struct InflateApplet {
-    format: DataFormat,
+    format: Option<FlateFormat>,
     quiet: bool,
 }
 
@@ -70,54 +472,76 @@ impl Applet for InflateApplet {
     fn clap_command(&self) -> Command {
         Command::new(self.command())
             .about(self.description())
-            .arg(arg!(-z --zlib "expect Zlib header"))
+            .arg(arg!(-r --raw "expect raw deflate data").conflicts_with_all(["zlib", "gzip"]))
+            .arg(arg!(-z --zlib "expect Zlib header").conflicts_with("gzip"))
+            .arg(arg!(-g --gzip "expect gzip header"))
             .arg(arg!(-q --quiet "don't output error message on stderr if decompression failed"))
             .arg(arg!([value]  "input value, reads from stdin if not present"))
+            .after_help("The format is detected from the header, unless specified.")
     }
 
     fn description(&self) -> &'static str {
-        "(raw) inflate decompression"
+        "inflate decompression (raw, Zlib or gzip)"
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        let f = if args.get_flag("zlib") {
-            DataFormat::Zlib
+        let f = if args.get_flag("raw") {
+            Some(FlateFormat::Raw)
+        } else if args.get_flag("zlib") {
+            Some(FlateFormat::Zlib)
+        } else if args.get_flag("gzip") {
+            Some(FlateFormat::Gzip)
         } else {
-            DataFormat::Raw
+            None
         };
-        Ok(Box::new(Self {
-            format: f,
-            quiet: args.get_flag("quiet"),
-        }))
+        Ok(Box::new(Self::with_options(f, args.get_flag("quiet"))))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
-        let dec_res = match self.format {
-            DataFormat::Raw => inflate::decompress_to_vec(val.as_slice()),
-            _ => inflate::decompress_to_vec_zlib(val.as_slice()),
-        };
-        match dec_res {
-            Ok(r) => Ok(r),
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        match inflate_stream(self.format, input, output, &mut |msg| {
+            self.report_error(msg)
+        }) {
+            // I/O errors are real errors, others mean invalid data
+            Err(e) if e.downcast_ref::<std::io::Error>().is_some() => Err(e),
             Err(e) => {
-                if !self.quiet {
-                    eprintln!(
-                        "Decompression error: {:?} (still outputing data to stdout)",
-                        e.status
-                    );
-                }
-                Ok(e.output)
+                self.report_error(&e.to_string());
+                Ok(())
             }
+            Ok(()) => Ok(()),
         }
     }
 
     fn new() -> Box<dyn Applet> {
         Box::new(Self {
-            format: DataFormat::Raw,
+            format: None,
             quiet: false,
         })
     }
 }
 
+impl InflateApplet {
+    /// Build an `InflateApplet` decompressing `format` data, detected from the
+    /// header if None. Unless `quiet` is set, errors are reported on stderr.
+    pub fn with_options(format: Option<FlateFormat>, quiet: bool) -> Self {
+        Self { format, quiet }
+    }
+
+    fn report_error(&self, err: &str) {
+        if !self.quiet {
+            eprintln!(
+                "Decompression error: {} (still outputing data to stdout)",
+                err
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,7 +550,7 @@ mod tests {
     fn test_inflate() {
         let inf = InflateApplet {
             quiet: true,
-            format: DataFormat::Raw,
+            format: Some(FlateFormat::Raw),
         };
         assert_eq!(
             inf.process([0x2b, 0x49, 0x2d, 0x2e, 0x29, 0x01, 0x62, 0x2e, 0x00].to_vec())
@@ -135,16 +559,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_inflate_stream() {
+        let inf = InflateApplet {
+            quiet: true,
+            format: Some(FlateFormat::Raw),
+        };
+        assert_eq!(
+            inf.process_stream_test(
+                [0x2b, 0x49, 0x2d, 0x2e, 0x29, 0x01, 0x62, 0x2e, 0x00].to_vec()
+            ),
+            "testtest\n".as_bytes().to_vec()
+        );
+        // Streaming only outputs what could actually be decompressed
+        assert_eq!(
+            inf.process_stream_test([0x2b, 0x49, 0x2d, 0x2e, 0x29].to_vec()),
+            "test".as_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn test_deflate_inflate_stream() {
+        let data: Vec<u8> = (0..3 * STREAM_BUF_SIZE).map(|i| (i % 251) as u8).collect();
+        for format in [FlateFormat::Raw, FlateFormat::Zlib, FlateFormat::Gzip] {
+            let def = DeflateApplet::with_options(format, 6, Some("name"), 1234).unwrap();
+            let inf = InflateApplet {
+                quiet: true,
+                format: Some(format),
+            };
+            let compressed = def.process_stream_test(data.clone());
+            assert_eq!(compressed, def.process_test(data.clone()));
+            assert_eq!(inf.process_stream_test(compressed), data);
+        }
+    }
+
     #[test]
     fn test_inflate_trunc() {
         let inf = InflateApplet {
             quiet: true,
-            format: DataFormat::Raw,
+            format: Some(FlateFormat::Raw),
         };
         assert_eq!(
             inf.process([0x2b, 0x49, 0x2d, 0x2e, 0x29].to_vec())
                 .unwrap(),
-            [116, 101, 115, 116, 0, 0, 0, 0, 0, 0].to_vec()
+            b"test"
         );
     }
 
@@ -152,12 +610,110 @@ mod tests {
     fn test_inflate_no_header() {
         let inf = InflateApplet {
             quiet: true,
-            format: DataFormat::Zlib,
+            format: Some(FlateFormat::Zlib),
         };
         assert_eq!(
             inf.process([0x2b, 0x49, 0x2d, 0x2e, 0x29].to_vec())
                 .unwrap(),
-            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0].to_vec()
+            b""
+        );
+    }
+
+    // "testtest\n", compressed by Python's gzip with "test.txt" as name
+    const GZIP_DATA: [u8; 36] = [
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x74, 0x65, 0x73, 0x74, 0x2e,
+        0x74, 0x78, 0x74, 0x00, 0x2b, 0x49, 0x2d, 0x2e, 0x29, 0x01, 0x62, 0x2e, 0x00, 0x12, 0x6f,
+        0xe5, 0x63, 0x09, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_gzip() {
+        let def = DeflateApplet::with_options(FlateFormat::Gzip, 9, Some("test.txt"), 0).unwrap();
+        assert_eq!(def.process(b"testtest\n".to_vec()).unwrap(), GZIP_DATA);
+        assert!(DeflateApplet::with_options(FlateFormat::Gzip, 9, Some("a\0b"), 0).is_err());
+
+        let inf = InflateApplet::with_options(None, true);
+        assert_eq!(inf.process(GZIP_DATA.to_vec()).unwrap(), b"testtest\n");
+        assert_eq!(inflate_all(&GZIP_DATA, None).unwrap(), b"testtest\n");
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        let inf = InflateApplet::with_options(Some(FlateFormat::Gzip), true);
+        let mut data = GZIP_DATA.to_vec();
+        data.extend(GZIP_DATA);
+        assert_eq!(
+            inf.process_stream_test(data.clone()),
+            b"testtest\ntesttest\n"
+        );
+
+        // Trailing data is ignored
+        data.extend(b"\0\0\0");
+        assert_eq!(
+            inf.process_stream_test(data.clone()),
+            b"testtest\ntesttest\n"
         );
+        assert!(inflate_all(&data, None).is_err());
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut reports = vec![];
+        for format in [FlateFormat::Raw, FlateFormat::Zlib, FlateFormat::Gzip] {
+            let def = DeflateApplet::with_options(format, 6, None, 0).unwrap();
+            let mut data = def.process(b"testtest\n".to_vec()).unwrap();
+            data.extend(b"trailing");
+            let mut res = vec![];
+            inflate_stream(Some(format), &mut data.as_slice(), &mut res, &mut |msg| {
+                reports.push(msg.to_string())
+            })
+            .unwrap();
+            assert_eq!(res, b"testtest\n");
+            assert!(inflate_all(&data, Some(format)).is_err());
+        }
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0], "trailing data after deflate stream ignored");
+        assert_eq!(reports[1], "trailing data after Zlib stream ignored");
+        assert!(reports[2].starts_with("trailing data after gzip member ignored"));
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let inf = InflateApplet::with_options(None, true);
+        let mut data = GZIP_DATA.to_vec();
+        data[28] ^= 1;
+        assert_eq!(inf.process_stream_test(data.clone()), b"testtest\n");
+        assert!(inflate_all(&data, None).is_err());
+
+        let def = DeflateApplet::with_options(FlateFormat::Zlib, 6, None, 0).unwrap();
+        let mut data = def.process(b"testtest\n".to_vec()).unwrap();
+        assert_eq!(inflate_all(&data, None).unwrap(), b"testtest\n");
+        *data.last_mut().unwrap() ^= 1;
+        assert_eq!(inf.process_stream_test(data.clone()), b"testtest\n");
+        assert!(inflate_all(&data, None).is_err());
+    }
+
+    #[test]
+    fn test_inflate_prefix() {
+        let mut data = GZIP_DATA.to_vec();
+        data.extend(b"trailing");
+        assert_eq!(
+            inflate_prefix(&data, FlateFormat::Gzip).unwrap(),
+            (b"testtest\n".to_vec(), GZIP_DATA.len())
+        );
+        assert_eq!(
+            inflate_prefix(&data[19..], FlateFormat::Raw).unwrap(),
+            (b"testtest\n".to_vec(), 9)
+        );
+        assert!(inflate_prefix(&data, FlateFormat::Zlib).is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(&GZIP_DATA), FlateFormat::Gzip);
+        assert_eq!(detect_format(&[0x78, 0x9c, 0x2b]), FlateFormat::Zlib);
+        assert_eq!(detect_format(&[0x78, 0x9d, 0x2b]), FlateFormat::Raw);
+        assert_eq!(detect_format(&[0x2b, 0x49, 0x2d]), FlateFormat::Raw);
+        assert_eq!(detect_format(&[]), FlateFormat::Raw);
     }
 }
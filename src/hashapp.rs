@@ -0,0 +1,240 @@
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
+use anyhow::{bail, Result};
+use clap::{arg, Command};
+use digest::DynDigest;
+use std::io::{Read, Write};
+
+/// Hash algorithms supported by `hash`
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HashAlg {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    #[value(name = "sha3-256")]
+    Sha3_256,
+    /// BLAKE2b-512
+    Blake2b,
+    Blake3,
+    /// Compute all the algorithms
+    All,
+}
+
+impl HashAlg {
+    /// All the actual algorithms, in the order they are listed by `-a all`
+    pub const ALL: [HashAlg; 7] = [
+        HashAlg::Md5,
+        HashAlg::Sha1,
+        HashAlg::Sha256,
+        HashAlg::Sha512,
+        HashAlg::Sha3_256,
+        HashAlg::Blake2b,
+        HashAlg::Blake3,
+    ];
+
+    /// Name of the algorithm, as given on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlg::Md5 => "md5",
+            HashAlg::Sha1 => "sha1",
+            HashAlg::Sha256 => "sha256",
+            HashAlg::Sha512 => "sha512",
+            HashAlg::Sha3_256 => "sha3-256",
+            HashAlg::Blake2b => "blake2b",
+            HashAlg::Blake3 => "blake3",
+            HashAlg::All => "all",
+        }
+    }
+}
+
+/// Incremental hash computation, for any `HashAlg` but `All`.
+pub enum Hasher {
+    Digest(Box<dyn DynDigest>),
+    /* blake3 only implements the `digest` traits of another version */
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn new(alg: HashAlg) -> Result<Self> {
+        Ok(match alg {
+            HashAlg::Md5 => Hasher::Digest(Box::new(md5::Md5::default())),
+            HashAlg::Sha1 => Hasher::Digest(Box::new(sha1::Sha1::default())),
+            HashAlg::Sha256 => Hasher::Digest(Box::new(sha2::Sha256::default())),
+            HashAlg::Sha512 => Hasher::Digest(Box::new(sha2::Sha512::default())),
+            HashAlg::Sha3_256 => Hasher::Digest(Box::new(sha3::Sha3_256::default())),
+            HashAlg::Blake2b => Hasher::Digest(Box::new(blake2::Blake2b512::default())),
+            HashAlg::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlg::All => bail!("\"all\" is not a hash algorithm"),
+        })
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Digest(d) => d.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Digest(d) => d.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+/// Feed all the data read from `input` to `hashers`, in chunks.
+pub fn update_from(hashers: &mut [Hasher], input: &mut dyn Read) -> Result<()> {
+    let mut buf = vec![0; STREAM_BUF_SIZE];
+    loop {
+        let len = read_chunk(input, &mut buf)?;
+        if len == 0 {
+            return Ok(());
+        }
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buf[..len]);
+        }
+    }
+}
+
+/// Compute the `alg` hash of `val`.
+pub fn hash(alg: HashAlg, val: &[u8]) -> Result<Vec<u8>> {
+    let mut hasher = Hasher::new(alg)?;
+    hasher.update(val);
+    Ok(hasher.finalize())
+}
+
+pub struct HashApplet {
+    alg: HashAlg,
+    raw: bool,
+}
+
+impl HashApplet {
+    /// Build a `HashApplet` computing `alg`, or all algorithms. The hash is
+    /// output in hex, or as raw bytes if `raw` is set (not supported by `All`).
+    pub fn with_options(alg: HashAlg, raw: bool) -> Result<Self> {
+        if raw && alg == HashAlg::All {
+            bail!("Raw output is not supported with all algorithms");
+        }
+        Ok(Self { alg, raw })
+    }
+}
+
+impl Applet for HashApplet {
+    fn command(&self) -> &'static str {
+        "hash"
+    }
+
+    fn description(&self) -> &'static str {
+        "cryptographic hash (MD5, SHA-1, SHA-2, SHA-3, BLAKE2, BLAKE3)"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-a --alg <alg> "hash algorithm, 'all' to compute all of them")
+                    .value_parser(clap::builder::EnumValueParser::<HashAlg>::new())
+                    .default_value("sha256"),
+            )
+            .arg(arg!(-r --raw "output the raw hash instead of hex"))
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            alg: HashAlg::Sha256,
+            raw: false,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            *args.get_one::<HashAlg>("alg").unwrap(),
+            args.get_flag("raw"),
+        )?))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let algs = if self.alg == HashAlg::All {
+            HashAlg::ALL.to_vec()
+        } else {
+            vec![self.alg]
+        };
+        let mut hashers = algs
+            .iter()
+            .map(|alg| Hasher::new(*alg))
+            .collect::<Result<Vec<_>>>()?;
+        update_from(&mut hashers, input)?;
+
+        if self.alg == HashAlg::All {
+            for (alg, hasher) in algs.iter().zip(hashers) {
+                writeln!(output, "{}: {}", alg.name(), hex::encode(hasher.finalize()))?;
+            }
+        } else {
+            let res = hashers.pop().unwrap().finalize();
+            if self.raw {
+                output.write_all(&res)?;
+            } else {
+                output.write_all(hex::encode(res).as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        let expected = [
+            "900150983cd24fb0d6963f7d28e17f72",
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        ];
+        for (alg, exp) in HashAlg::ALL.iter().zip(expected) {
+            assert_eq!(hex::encode(hash(*alg, b"abc").unwrap()), exp);
+            let app = HashApplet::with_options(*alg, false).unwrap();
+            assert_eq!(app.process_test(b"abc".to_vec()), exp.as_bytes());
+        }
+        assert!(hash(HashAlg::All, b"abc").is_err());
+
+        let raw = HashApplet::with_options(HashAlg::Md5, true).unwrap();
+        assert_eq!(hex::encode(raw.process_test(b"abc".to_vec())), expected[0]);
+        assert!(HashApplet::with_options(HashAlg::All, true).is_err());
+
+        let all = HashApplet::with_options(HashAlg::All, false).unwrap();
+        let res = String::from_utf8(all.process_test(b"abc".to_vec())).unwrap();
+        assert_eq!(res.lines().count(), 7);
+        assert!(res.starts_with("md5: 900150983cd24fb0d6963f7d28e17f72\nsha1: "));
+    }
+
+    #[test]
+    fn test_hash_stream() {
+        /* Larger than the stream buffer */
+        let data: Vec<u8> = (0..STREAM_BUF_SIZE * 3 + 17).map(|i| i as u8).collect();
+        for alg in HashAlg::ALL {
+            let app = HashApplet::with_options(alg, true).unwrap();
+            assert_eq!(
+                app.process_stream_test(data.clone()),
+                hash(alg, &data).unwrap()
+            );
+        }
+    }
+}
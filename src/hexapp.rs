@@ -1,7 +1,10 @@
-use crate::applet::Applet;
 use crate::applet::SliceExt;
-use anyhow::{Context, Result};
-use clap::{arg, Command};
+use crate::applet::{read_chunk, Applet, FromStrWithRadix, STREAM_BUF_SIZE};
+use crate::escapeapp::unescape;
+use anyhow::{bail, Context, Result};
+use clap::{arg, value_parser, Command};
+use regex::Regex;
+use std::io::{self, Read, Write};
 
 pub struct HexApplet {}
 
@@ -21,77 +24,448 @@ impl Applet for HexApplet {
         Ok(hex::encode(val).as_bytes().to_vec())
     }
 
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut buf = vec![0; STREAM_BUF_SIZE];
+        loop {
+            let len = read_chunk(input, &mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+            output.write_all(hex::encode(&buf[..len]).as_bytes())?;
+        }
+    }
+
     fn new() -> Box<dyn Applet> {
         Box::new(Self {})
     }
 }
 
+/// Output formats of `hexdump`
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum DumpFormat {
+    /// Like `hexdump -C`
+    #[default]
+    Canonical,
+    /// Like `xxd`
+    Xxd,
+    /// C array
+    C,
+    /// Python bytes literal
+    Python,
+    /// Rust slice
+    Rust,
+}
+
+const COLOR_RESET: &str = "\x1b[0m";
+
+/* Color of bytes: null, printable, other ASCII, high bytes */
+fn byte_color(b: u8) -> &'static str {
+    match b {
+        0 => "\x1b[90m",
+        b if b.is_ascii_graphic() || b == b' ' => "\x1b[32m",
+        b if b.is_ascii() => "\x1b[33m",
+        _ => "\x1b[31m",
+    }
+}
+
+pub struct HexdumpApplet {
+    format: DumpFormat,
+    width: usize,
+    group: usize,
+    start: u64,
+    length: Option<u64>,
+    color: bool,
+}
+
+impl HexdumpApplet {
+    /// Build a `HexdumpApplet` dumping `width` bytes per line in the given `format`.
+    /// `group` is the number of bytes between spaces in the canonical and xxd formats
+    /// (1 and 2 by default). The dump starts at offset `start` of the input, and stops
+    /// after `length` bytes if set. If `color` is set, bytes are colored by type.
+    pub fn with_options(
+        format: DumpFormat,
+        width: usize,
+        group: Option<usize>,
+        start: u64,
+        length: Option<u64>,
+        color: bool,
+    ) -> Result<Self> {
+        let group = group.unwrap_or(if format == DumpFormat::Xxd { 2 } else { 1 });
+        if width == 0 || group == 0 {
+            bail!("width and group size must not be 0");
+        }
+        Ok(Self {
+            format,
+            width,
+            group,
+            start,
+            length,
+            color,
+        })
+    }
+
+    fn header(&self) -> Option<&'static str> {
+        match self.format {
+            DumpFormat::Canonical | DumpFormat::Xxd => None,
+            DumpFormat::C => Some("unsigned char data[] = {"),
+            DumpFormat::Python => Some("data = ("),
+            DumpFormat::Rust => Some("let data: &[u8] = &["),
+        }
+    }
+
+    fn trailer(&self, len: u64) -> Option<String> {
+        match self.format {
+            DumpFormat::Canonical if len > 0 => Some(format!("{:08x}", self.start + len)),
+            DumpFormat::Canonical | DumpFormat::Xxd => None,
+            DumpFormat::C => Some(format!("}};\nunsigned int data_len = {};", len)),
+            DumpFormat::Python if len == 0 => Some("    b\"\"\n)".to_string()),
+            DumpFormat::Python => Some(")".to_string()),
+            DumpFormat::Rust => Some("];".to_string()),
+        }
+    }
+
+    fn format_line(&self, offset: u64, line: &[u8]) -> String {
+        match self.format {
+            DumpFormat::Canonical => format!(
+                "{:08x}  {}  |{}|",
+                offset,
+                self.hex_column(line, 8),
+                self.ascii_column(line)
+            ),
+            DumpFormat::Xxd => format!(
+                "{:08x}: {}  {}",
+                offset,
+                self.hex_column(line, 0),
+                self.ascii_column(line)
+            ),
+            DumpFormat::C => {
+                let bytes: Vec<String> = line.iter().map(|b| format!("0x{:02x},", b)).collect();
+                format!("  {}", bytes.join(" "))
+            }
+            DumpFormat::Python => {
+                let bytes: String = line.iter().map(|b| format!("\\x{:02x}", b)).collect();
+                format!("    b\"{}\"", bytes)
+            }
+            DumpFormat::Rust => {
+                let bytes: Vec<String> = line.iter().map(|b| format!("0x{:02x},", b)).collect();
+                format!("    {}", bytes.join(" "))
+            }
+        }
+    }
+
+    /// Format `data`, found at `offset`, as dump lines, without header nor trailer.
+    pub fn format_lines(&self, offset: u64, data: &[u8]) -> Vec<String> {
+        data.chunks(self.width)
+            .enumerate()
+            .map(|(i, line)| self.format_line(offset + (i * self.width) as u64, line))
+            .collect()
+    }
+
+    /* Hex bytes, `group` bytes between spaces, with an extra space every `extra`
+     * bytes if not 0. Short lines are padded. */
+    fn hex_column(&self, line: &[u8], extra: usize) -> String {
+        let mut res = String::new();
+        for i in 0..self.width {
+            if i > 0 && i % self.group == 0 {
+                res.push(' ');
+            }
+            if extra > 0 && i > 0 && i % extra == 0 {
+                res.push(' ');
+            }
+            match line.get(i) {
+                Some(b) if self.color => {
+                    res += &format!("{}{:02x}{}", byte_color(*b), b, COLOR_RESET)
+                }
+                Some(b) => res += &format!("{:02x}", b),
+                None => res += "  ",
+            }
+        }
+        res
+    }
+
+    fn ascii_column(&self, line: &[u8]) -> String {
+        let mut res = String::new();
+        for b in line {
+            let c = if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            };
+            if self.color {
+                res += &format!("{}{}{}", byte_color(*b), c, COLOR_RESET);
+            } else {
+                res.push(c);
+            }
+        }
+        res
+    }
+}
+
+impl Applet for HexdumpApplet {
+    fn command(&self) -> &'static str {
+        "hexdump"
+    }
+    fn description(&self) -> &'static str {
+        "hex dump (canonical, xxd, C, Python or Rust)"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-f --format <format> "output format")
+                    .value_parser(clap::builder::EnumValueParser::<DumpFormat>::new())
+                    .default_value("canonical"),
+            )
+            .arg(
+                arg!(-w --width <width> "number of bytes per line")
+                    .value_parser(value_parser!(usize))
+                    .default_value("16"),
+            )
+            .arg(
+                arg!(-g --group <size> "number of bytes per group (canonical and xxd)")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(arg!(-s --start <offset> "start dumping at <offset>"))
+            .arg(arg!(-n --length <length> "dump only <length> bytes"))
+            .arg(arg!(--color "color bytes: null, printable, other ASCII, high bytes"))
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            format: DumpFormat::Canonical,
+            width: 16,
+            group: 1,
+            start: 0,
+            length: None,
+            color: false,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let start = match args.get_one::<String>("start") {
+            Some(start) => u64::from_str_with_radix(start).context("Invalid start offset")?,
+            None => 0,
+        };
+        let length = match args.get_one::<String>("length") {
+            Some(length) => Some(u64::from_str_with_radix(length).context("Invalid length")?),
+            None => None,
+        };
+        Ok(Box::new(Self::with_options(
+            *args.get_one::<DumpFormat>("format").unwrap(),
+            *args.get_one::<usize>("width").unwrap(),
+            args.get_one::<usize>("group").copied(),
+            start,
+            length,
+            args.get_flag("color"),
+        )?))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        io::copy(&mut (&mut *input).take(self.start), &mut io::sink())
+            .context("Reading input failed")?;
+        let mut input = input.take(self.length.unwrap_or(u64::MAX));
+
+        /* Lines are separated, not terminated, by newlines, like other applets' output */
+        let mut lines = 0u64;
+        let mut write_line = |output: &mut dyn Write, line: &str| -> Result<()> {
+            if lines > 0 {
+                output.write_all(b"\n")?;
+            }
+            lines += 1;
+            output.write_all(line.as_bytes())?;
+            Ok(())
+        };
+        if let Some(header) = self.header() {
+            write_line(output, header)?;
+        }
+
+        let mut buf = vec![0; STREAM_BUF_SIZE];
+        let mut pending = vec![];
+        let mut offset = 0u64;
+        loop {
+            let len = read_chunk(&mut input, &mut buf)?;
+            pending.extend_from_slice(&buf[..len]);
+            let eof = len < buf.len();
+            let mut pos = 0;
+            while pending.len() - pos >= self.width || (eof && pos < pending.len()) {
+                let end = (pos + self.width).min(pending.len());
+                let line = self.format_line(self.start + offset, &pending[pos..end]);
+                write_line(output, &line)?;
+                offset += (end - pos) as u64;
+                pos = end;
+            }
+            pending.drain(..pos);
+            if eof {
+                break;
+            }
+        }
+
+        if let Some(trailer) = self.trailer(offset) {
+            write_line(output, &trailer)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct UnHexApplet {
     hexonly: bool,
     strict: bool,
+    reverse: bool,
 }
 
 impl UnHexApplet {
-    fn hex_decode_hexonly(&self, val: Vec<u8>) -> Result<Vec<u8>> {
-        let mut trimmed: Vec<u8> = val.trim().into();
-        let res = hex::decode(&trimmed);
-        if self.strict {
-            return res.with_context(|| "Invalid hex input");
-        }
-        /* remove spaces */
-        trimmed.retain(|&x| x != 0x20);
-        let res = hex::decode(&trimmed);
-        match res {
-            Ok(decoded) => Ok(decoded),
-            Err(e) => match e {
-                hex::FromHexError::InvalidHexCharacter { c: _, index } => {
-                    let mut end = trimmed.split_off(index);
-                    let mut decoded = self.hex_decode_hexonly(trimmed)?;
-                    decoded.append(&mut end);
-                    Ok(decoded)
-                }
-                hex::FromHexError::OddLength => {
-                    // TODO: refactor
-                    let mut end = trimmed.split_off(trimmed.len() - 1);
-                    let mut decoded = self.hex_decode_hexonly(trimmed)?;
-                    decoded.append(&mut end);
-                    Ok(decoded)
-                }
-                _ => panic!("{}", e),
-            },
+    /// Build an `UnHexApplet`, see [`hex_decode_hexonly`] for `hexonly` and `strict`,
+    /// [`hex_decode_dump`] for `reverse` and [`hex_decode_all`] for the default behaviour.
+    pub fn with_options(hexonly: bool, strict: bool, reverse: bool) -> Self {
+        Self {
+            hexonly: hexonly || strict,
+            strict,
+            reverse,
         }
     }
+}
 
-    fn hex_decode_all(&self, hexval: Vec<u8>) -> Result<Vec<u8>> {
-        let mut res: Vec<u8> = vec![];
-        let iter = &mut hexval.windows(2);
-        let mut last: &[u8] = &[];
-        loop {
-            let chro = iter.next();
-            let chr = match chro {
-                None => {
-                    res.extend_from_slice(last);
-                    return Ok(res);
-                }
-                Some(a) => a,
+/// Decode hex data until the first non-hex byte and copy the rest as is, except spaces.
+/// If `strict` is set, fail on invalid data instead.
+pub fn hex_decode_hexonly(val: &[u8], strict: bool) -> Result<Vec<u8>> {
+    let mut trimmed: Vec<u8> = val.trim().into();
+    let res = hex::decode(&trimmed);
+    if strict {
+        return res.with_context(|| "Invalid hex input");
+    }
+    /* remove spaces */
+    trimmed.retain(|&x| x != 0x20);
+    let res = hex::decode(&trimmed);
+    match res {
+        Ok(decoded) => Ok(decoded),
+        Err(e) => match e {
+            hex::FromHexError::InvalidHexCharacter { c: _, index } => {
+                let mut end = trimmed.split_off(index);
+                let mut decoded = hex_decode_hexonly(&trimmed, strict)?;
+                decoded.append(&mut end);
+                Ok(decoded)
+            }
+            hex::FromHexError::OddLength => {
+                // TODO: refactor
+                let mut end = trimmed.split_off(trimmed.len() - 1);
+                let mut decoded = hex_decode_hexonly(&trimmed, strict)?;
+                decoded.append(&mut end);
+                Ok(decoded)
+            }
+            _ => panic!("{}", e),
+        },
+    }
+}
+
+/// Decode all the hex pairs found in `hexval`, regardless of garbage in-between,
+/// which is copied as is.
+pub fn hex_decode_all(hexval: &[u8]) -> Result<Vec<u8>> {
+    let mut res: Vec<u8> = vec![];
+    let iter = &mut hexval.windows(2);
+    let mut last: &[u8] = &[];
+    loop {
+        let chro = iter.next();
+        let chr = match chro {
+            None => {
+                res.extend_from_slice(last);
+                return Ok(res);
+            }
+            Some(a) => a,
+        };
+
+        if (chr[0] as char).is_ascii_hexdigit() && (chr[1] as char).is_ascii_hexdigit() {
+            res.append(&mut hex::decode(chr).with_context(|| "hex decoding failed")?);
+            /* make sure we dont miss the last char if we have something like
+             * "41 " as input */
+            let next_win = iter.next().unwrap_or(&[]);
+            if next_win.len() > 1 {
+                last = &next_win[1..2]
+            } else {
+                last = &[]
             };
+        } else {
+            res.extend_from_slice(&chr[0..1]);
+            last = &chr[1..2];
+        }
+    }
+}
+
+/// Decode a hex dump, in any of the `hexdump` formats, or from `xxd`, `xxd -p` or
+/// `hexdump -C`: offsets, ASCII columns, colors and declarations are ignored.
+/// Lines repeated by `hexdump` (shown as `*`) are restored.
+pub fn hex_decode_dump(val: &[u8]) -> Result<Vec<u8>> {
+    let colors = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+    /* Offset, ":" in xxd output, and the rest of the line */
+    let dump_line = Regex::new("^([0-9a-fA-F]{4,})(:?)(?:[ \t]+(.*))?$").unwrap();
+    let code_byte = Regex::new("0[xX]([0-9a-fA-F]{1,2})(?:[^0-9a-fA-F]|$)").unwrap();
 
-            if (chr[0] as char).is_ascii_hexdigit() && (chr[1] as char).is_ascii_hexdigit() {
-                res.append(&mut hex::decode(chr).with_context(|| "hex decoding failed")?);
-                /* make sure we dont miss the last char if we have something like
-                 * "41 " as input */
-                let next_win = iter.next().unwrap_or(&[]);
-                if next_win.len() > 1 {
-                    last = &next_win[1..2]
-                } else {
-                    last = &[]
-                };
+    let text = String::from_utf8_lossy(val);
+    let text = colors.replace_all(&text, "");
+    let mut res = vec![];
+    /* To restore repeated lines: offset of the first line, previous line */
+    let mut first_offset = None;
+    let mut previous: Vec<u8> = vec![];
+    let mut repeat = false;
+    for line in text.lines().map(|l| l.trim()) {
+        if line == "*" {
+            repeat = true;
+        } else if let Some(caps) = dump_line.captures(line).filter(|caps| {
+            /* Plain hex can look like an offset: only take it as one when followed
+             * by ":", by an ASCII column, or alone at the end of a dump */
+            &caps[2] == ":"
+                || caps
+                    .get(3)
+                    .map_or(first_offset.is_some(), |rest| rest.as_str().contains('|'))
+        }) {
+            let offset = u64::from_str_radix(&caps[1], 16).context("Invalid offset in dump")?;
+            let first_offset = *first_offset.get_or_insert(offset);
+            if repeat && !previous.is_empty() {
+                while first_offset + (res.len() as u64) < offset {
+                    let missing = offset - first_offset - res.len() as u64;
+                    res.extend(previous.iter().take(missing as usize));
+                }
+            }
+            repeat = false;
+            let rest = caps.get(3).map_or("", |m| m.as_str());
+            // The ASCII column is separated by two spaces in xxd output, and by "|" in hexdump's
+            let hex_column = if &caps[2] == ":" {
+                rest.split("  ").next().unwrap()
             } else {
-                res.extend_from_slice(&chr[0..1]);
-                last = &chr[1..2];
+                rest.split('|').next().unwrap()
+            };
+            previous = hex_column
+                .split_whitespace()
+                .map_while(|group| hex::decode(group).ok())
+                .flatten()
+                .collect();
+            res.extend(&previous);
+        } else if line
+            .bytes()
+            .all(|c| c.is_ascii_hexdigit() || c.is_ascii_whitespace())
+        {
+            // Plain hex, as output by `xxd -p`
+            let hex: Vec<u8> = line.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+            res.extend(hex_decode_all(&hex)?);
+        } else if let Some(start) = line.find("b\"").or_else(|| line.find("b'")) {
+            let quote = &line[start + 1..start + 2];
+            let literal = &line[start + 2..];
+            let end = literal.rfind(quote).unwrap_or(literal.len());
+            res.extend(unescape(&literal.as_bytes()[..end])?);
+        } else {
+            for caps in code_byte.captures_iter(line) {
+                res.push(u8::from_str_radix(&caps[1], 16)?);
             }
         }
     }
+    Ok(res)
 }
 
 impl Applet for UnHexApplet {
@@ -106,6 +480,7 @@ impl Applet for UnHexApplet {
         Box::new(Self {
             hexonly: false,
             strict: false,
+            reverse: false,
         })
     }
 
@@ -113,22 +488,26 @@ impl Applet for UnHexApplet {
         Command::new(self.command()).about(self.description())
              .arg(arg!(-o --"hex-only"  "expect only hex data, stop at first non-hex byte (but copy the rest, except spaces)"))
              .arg(arg!(-s --strict  "strict decoding, error on invalid data"))
+             .arg(arg!(-r --reverse "reverse a hex dump (hexdump, xxd, C, Python or Rust), ignoring offsets and ASCII").conflicts_with_all(["hex-only", "strict"]))
              .arg(arg!([value]  "input value, reads from stdin if not present"))
              .after_help("By default, decode all hex data in the input, regardless of garbage in-between.")
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            hexonly: args.get_flag("hex-only") || args.get_flag("strict"),
-            strict: args.get_flag("strict"),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_flag("hex-only"),
+            args.get_flag("strict"),
+            args.get_flag("reverse"),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
-        if self.hexonly {
-            self.hex_decode_hexonly(val)
+        if self.reverse {
+            hex_decode_dump(&val)
+        } else if self.hexonly {
+            hex_decode_hexonly(&val, self.strict)
         } else {
-            self.hex_decode_all(val)
+            hex_decode_all(&val)
         }
     }
 }
@@ -146,11 +525,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hex_stream() {
+        let hex = HexApplet {};
+        let data: Vec<u8> = (0..STREAM_BUF_SIZE + 10).map(|i| i as u8).collect();
+        assert_eq!(
+            hex.process_stream_test(data.clone()),
+            hex.process_test(data)
+        );
+    }
+
     #[test]
     fn test_unhex_hexonly() {
         let unhex = UnHexApplet {
             strict: false,
             hexonly: true,
+            reverse: false,
         };
         assert_eq!(
             unhex
@@ -171,6 +561,7 @@ mod tests {
         let unhex = UnHexApplet {
             strict: false,
             hexonly: false,
+            reverse: false,
         };
         assert_eq!(
             unhex.process("test52af ".as_bytes().to_vec()).unwrap(),
@@ -189,4 +580,97 @@ mod tests {
             [0x21, 0x35, 0x20, 0x2a, 0x66]
         );
     }
+
+    fn dump(format: DumpFormat, val: &[u8]) -> String {
+        let hexdump = HexdumpApplet::with_options(format, 16, None, 0, None, false).unwrap();
+        String::from_utf8(hexdump.process_test(val.to_vec())).unwrap()
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(
+            dump(DumpFormat::Canonical, b"hello world\n"),
+            "00000000  68 65 6c 6c 6f 20 77 6f  72 6c 64 0a              |hello world.|\n0000000c"
+        );
+        assert_eq!(
+            dump(DumpFormat::Xxd, b"hello world\n"),
+            "00000000: 6865 6c6c 6f20 776f 726c 640a            hello world."
+        );
+        assert_eq!(
+            dump(DumpFormat::C, b"\x00\xff"),
+            "unsigned char data[] = {\n  0x00, 0xff,\n};\nunsigned int data_len = 2;"
+        );
+        assert_eq!(
+            dump(DumpFormat::Python, b"\x00\xff"),
+            "data = (\n    b\"\\x00\\xff\"\n)"
+        );
+        assert_eq!(
+            dump(DumpFormat::Rust, b"\x00\xff"),
+            "let data: &[u8] = &[\n    0x00, 0xff,\n];"
+        );
+
+        let hexdump =
+            HexdumpApplet::with_options(DumpFormat::Xxd, 4, Some(1), 0x11, Some(6), false).unwrap();
+        let data: Vec<u8> = (0..=255u8).collect();
+        assert_eq!(
+            hexdump.process_stream_test(data),
+            b"00000011: 11 12 13 14  ....\n00000015: 15 16        .."
+        );
+
+        let dumper =
+            HexdumpApplet::with_options(DumpFormat::Canonical, 4, None, 0, None, false).unwrap();
+        assert_eq!(
+            dumper.format_lines(0x10, b"abcdef"),
+            [
+                "00000010  61 62 63 64  |abcd|",
+                "00000014  65 66        |ef|"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hex_decode_dump() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        for format in [
+            DumpFormat::Canonical,
+            DumpFormat::Xxd,
+            DumpFormat::C,
+            DumpFormat::Python,
+            DumpFormat::Rust,
+        ] {
+            for color in [false, true] {
+                let hexdump =
+                    HexdumpApplet::with_options(format, 13, None, 0, None, color).unwrap();
+                let dumped = hexdump.process_test(data.clone());
+                assert_eq!(hex_decode_dump(&dumped).unwrap(), data);
+            }
+        }
+
+        // Lines crossing the input chunks
+        let data: Vec<u8> = (0..STREAM_BUF_SIZE + 100).map(|i| (i * 7) as u8).collect();
+        let hexdump =
+            HexdumpApplet::with_options(DumpFormat::Xxd, 13, None, 0, None, false).unwrap();
+        let dumped = hexdump.process_stream_test(data.clone());
+        assert_eq!(hex_decode_dump(&dumped).unwrap(), data);
+
+        // hexdump -C with repeated lines
+        let squeezed =
+            b"00000000  41 41 41 41 41 41 41 41  41 41 41 41 41 41 41 41  |AAAAAAAAAAAAAAAA|
+*
+00000024  42 42                                             |BB|
+00000026";
+        let mut expected = vec![b'A'; 0x24];
+        expected.extend(b"BB");
+        assert_eq!(hex_decode_dump(squeezed).unwrap(), expected);
+
+        // Plain hex, without offsets
+        assert_eq!(hex_decode_dump(b"4142 4344 4546").unwrap(), b"ABCDEF");
+        assert_eq!(
+            hex_decode_dump(b"41424344\n45464748\n").unwrap(),
+            b"ABCDEFGH"
+        );
+        let data: Vec<u8> = (0..100u8).collect();
+        let xxd_p: Vec<String> = data.chunks(30).map(hex::encode).collect();
+        assert_eq!(hex_decode_dump(xxd_p.join("\n").as_bytes()).unwrap(), data);
+    }
 }
@@ -0,0 +1,214 @@
+use crate::applet::Applet;
+use crate::hashapp::{update_from, HashAlg, Hasher};
+use anyhow::{bail, Context, Result};
+use clap::builder::TypedValueParser;
+use clap::{arg, Command, ValueEnum};
+use digest::core_api::BlockSizeUser;
+use digest::{Digest, FixedOutputReset, Reset};
+use hmac::{Mac, SimpleHmac};
+use std::convert::TryInto;
+use std::fs;
+use std::io::{Read, Write};
+
+fn hmac<D>(key: &[u8]) -> Hasher
+where
+    D: Digest + BlockSizeUser + FixedOutputReset + Reset + Clone + 'static,
+{
+    /* HMAC accepts keys of any length */
+    Hasher::Digest(Box::new(
+        <SimpleHmac<D> as Mac>::new_from_slice(key).unwrap(),
+    ))
+}
+
+/// Build a `Hasher` computing the HMAC of `alg` with `key`. BLAKE3 has its own
+/// keyed mode, which requires a 32 bytes key.
+pub fn keyed_hasher(alg: HashAlg, key: &[u8]) -> Result<Hasher> {
+    Ok(match alg {
+        HashAlg::Md5 => hmac::<md5::Md5>(key),
+        HashAlg::Sha1 => hmac::<sha1::Sha1>(key),
+        HashAlg::Sha256 => hmac::<sha2::Sha256>(key),
+        HashAlg::Sha512 => hmac::<sha2::Sha512>(key),
+        HashAlg::Sha3_256 => hmac::<sha3::Sha3_256>(key),
+        HashAlg::Blake2b => hmac::<blake2::Blake2b512>(key),
+        HashAlg::Blake3 => {
+            let key: &[u8; 32] = match key.try_into() {
+                Ok(key) => key,
+                Err(_) => bail!("BLAKE3 keys are 32 bytes long, not {}", key.len()),
+            };
+            Hasher::Blake3(Box::new(blake3::Hasher::new_keyed(key)))
+        }
+        HashAlg::All => bail!("\"all\" is not a hash algorithm"),
+    })
+}
+
+pub struct HmacApplet {
+    alg: HashAlg,
+    key: Vec<u8>,
+    raw: bool,
+    verify: Option<Vec<u8>>,
+}
+
+impl HmacApplet {
+    /// Build a `HmacApplet` computing the `alg` HMAC of its input with `key`.
+    /// The result is output in hex, or as raw bytes if `raw` is set. If `verify`
+    /// is set, nothing is output and an error is returned if the result differs.
+    pub fn with_options(
+        alg: HashAlg,
+        key: Vec<u8>,
+        raw: bool,
+        verify: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        /* Check the key now, to fail before reading the input */
+        keyed_hasher(alg, &key)?;
+        Ok(Self {
+            alg,
+            key,
+            raw,
+            verify,
+        })
+    }
+}
+
+impl Applet for HmacApplet {
+    fn command(&self) -> &'static str {
+        "hmac"
+    }
+
+    fn description(&self) -> &'static str {
+        "compute or verify a HMAC (or BLAKE3 keyed hash)"
+    }
+
+    fn returns_data(&self) -> bool {
+        self.verify.is_none()
+    }
+
+    fn clap_command(&self) -> Command {
+        let algs = HashAlg::ALL.map(|alg| alg.name());
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-a --alg <alg> "hash algorithm")
+                    .value_parser(
+                        clap::builder::PossibleValuesParser::new(algs)
+                            .map(|alg| HashAlg::from_str(&alg, false).unwrap()),
+                    )
+                    .default_value("sha256"),
+            )
+            .arg(
+                arg!(-k --key <KEY>  "Key in hex format")
+                    .required_unless_present("keyfile")
+                    .conflicts_with("keyfile"),
+            )
+            .arg(arg!(-f --keyfile <keyfile>  "File to use as key"))
+            .arg(arg!(-r --raw "output the raw HMAC instead of hex").conflicts_with("verify"))
+            .arg(arg!(--verify <hex> "expected HMAC: fail if the computed one differs"))
+            .arg(arg!([value]  "input value, reads from stdin if not present"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            alg: HashAlg::Sha256,
+            key: vec![],
+            raw: false,
+            verify: None,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let key = if args.contains_id("key") {
+            hex::decode(args.get_one::<String>("key").unwrap().replace(' ', ""))
+                .with_context(|| "Key decoding failed")?
+        } else {
+            fs::read(args.get_one::<String>("keyfile").unwrap())
+                .with_context(|| "Could not read keyfile")?
+        };
+        let verify = match args.get_one::<String>("verify") {
+            Some(v) => Some(
+                hex::decode(v.replace(' ', "")).with_context(|| "Expected HMAC decoding failed")?,
+            ),
+            None => None,
+        };
+        Ok(Box::new(Self::with_options(
+            *args.get_one::<HashAlg>("alg").unwrap(),
+            key,
+            args.get_flag("raw"),
+            verify,
+        )?))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut hashers = [keyed_hasher(self.alg, &self.key)?];
+        update_from(&mut hashers, input)?;
+        let [hasher] = hashers;
+        let res = hasher.finalize();
+
+        if let Some(expected) = &self.verify {
+            if *expected != res {
+                bail!("HMAC mismatch, computed {}", hex::encode(res));
+            }
+        } else if self.raw {
+            output.write_all(&res)?;
+        } else {
+            output.write_all(hex::encode(res).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac() {
+        let msg = b"The quick brown fox jumps over the lazy dog";
+        let expected = [
+            "80070713463e7749b90c2dc24911e275",
+            "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9",
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8",
+            "b42af09057bac1e2d41708e48a902e09b5ff7f12ab428a4fe86653c73dd248fb\
+             82f948a549f7b791a5b41915ee4d1ec3935357e4e2317250d0372afa2ebeeb3a",
+            "8c6e0683409427f8931711b10ca92a506eb1fafa48fadd66d76126f47ac2c333",
+            "92294f92c0dfb9b00ec9ae8bd94d7e7d8a036b885a499f149dfe2fd2199394aa\
+             af6b8894a1730cccb2cd050f9bcf5062a38b51b0dab33207f8ef35ae2c9df51b",
+        ];
+        for (alg, exp) in HashAlg::ALL.iter().zip(expected) {
+            let app = HmacApplet::with_options(*alg, b"key".to_vec(), false, None).unwrap();
+            assert_eq!(app.process_test(msg.to_vec()), exp.as_bytes());
+        }
+
+        // RFC 4231 test case 2, raw output
+        let app = HmacApplet::with_options(HashAlg::Sha256, b"Jefe".to_vec(), true, None).unwrap();
+        assert_eq!(
+            hex::encode(app.process_test(b"what do ya want for nothing?".to_vec())),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // BLAKE3 keyed hash of an empty input, from the official test vectors
+        let key = b"whats the Elvish word for friend".to_vec();
+        let app = HmacApplet::with_options(HashAlg::Blake3, key, false, None).unwrap();
+        assert_eq!(
+            app.process_test(vec![]),
+            b"92b2b75604ed3c761f9d6f62392c8a9227ad0ea3f09573e783f1498a4ed60d26"
+        );
+        assert!(HmacApplet::with_options(HashAlg::Blake3, b"key".to_vec(), false, None).is_err());
+        assert!(HmacApplet::with_options(HashAlg::All, b"key".to_vec(), false, None).is_err());
+    }
+
+    #[test]
+    fn test_hmac_verify() {
+        let msg = b"The quick brown fox jumps over the lazy dog".to_vec();
+        let mac = hex::decode(b"de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9").unwrap();
+        let app =
+            HmacApplet::with_options(HashAlg::Sha1, b"key".to_vec(), false, Some(mac)).unwrap();
+        assert_eq!(app.process_test(msg.clone()), b"");
+        assert!(app.process(b"the quick brown fox".to_vec()).is_err());
+        assert!(!app.returns_data());
+    }
+}
@@ -0,0 +1,148 @@
+//! rsbkb's applets, usable as a library.
+//!
+//! Every applet implements the [`applet::Applet`] trait, so it can be built
+//! from command line arguments, but applets also have typed constructors
+//! (`with_options`) and their modules expose the underlying functions
+//! (lenient decoders, entropy, CRC computation...).
+//!
+//! ```
+//! use rsbkb::applet::Applet;
+//! use rsbkb::hexapp::{hex_decode_all, UnHexApplet};
+//!
+//! assert_eq!(hex_decode_all(b"x=4142").unwrap(), b"x=AB");
+//!
+//! let unhex = UnHexApplet::with_options(true, true, false);
+//! assert_eq!(unhex.process(b"4142".to_vec()).unwrap(), b"AB");
+//! ```
+
+pub mod applet;
+use applet::Applet;
+
+pub mod chain;
+pub mod recipe;
+
+pub mod hexapp;
+use hexapp::HexApplet;
+use hexapp::HexdumpApplet;
+use hexapp::UnHexApplet;
+
+pub mod urlapp;
+use urlapp::UrlDecApplet;
+use urlapp::UrlEncApplet;
+
+pub mod b64app;
+use b64app::B64DecApplet;
+use b64app::B64EncApplet;
+
+pub mod crcapp;
+use crcapp::CRC16Applet;
+use crcapp::CRC32Applet;
+use crcapp::CRCApplet;
+pub mod crcrevapp;
+use crcrevapp::CrcRevApplet;
+pub mod crcforgeapp;
+use crcforgeapp::CrcForgeApplet;
+
+pub mod hashapp;
+use hashapp::HashApplet;
+pub mod hmacapp;
+use hmacapp::HmacApplet;
+
+pub mod xorapp;
+use xorapp::XorApplet;
+
+pub mod sliceapp;
+use sliceapp::SliceApplet;
+pub mod patchapp;
+use patchapp::PatchApplet;
+pub mod splitapp;
+use splitapp::SplitApplet;
+
+pub mod timeapp;
+use timeapp::TsDecApplet;
+use timeapp::TsEncApplet;
+
+pub mod patternapp;
+use patternapp::BofPattGenApplet;
+use patternapp::BofPattOffApplet;
+
+pub mod entropyapp;
+use entropyapp::EntropyApplet;
+
+pub mod statsapp;
+use statsapp::StatsApplet;
+
+pub mod bgrepapp;
+use bgrepapp::BgrepApplet;
+
+pub mod zscanapp;
+use zscanapp::ZScanApplet;
+
+pub mod carveapp;
+use carveapp::CarveApplet;
+pub mod bdiffapp;
+use bdiffapp::BdiffApplet;
+
+pub mod findsoapp;
+use findsoapp::FindSoApplet;
+
+pub mod flateapp;
+use flateapp::DeflateApplet;
+use flateapp::InflateApplet;
+
+pub mod baseapp;
+use baseapp::BaseIntApplet;
+
+pub mod escapeapp;
+use escapeapp::EscapeApplet;
+use escapeapp::UnEscapeApplet;
+
+pub mod compressapp;
+use compressapp::CompressApplet;
+use compressapp::DecompressApplet;
+
+pub mod magicapp;
+use magicapp::MagicApplet;
+
+/// Build the list of all registered applets.
+pub fn applets() -> Vec<Box<dyn Applet>> {
+    vec![
+        HexApplet::new(),
+        UnHexApplet::new(),
+        HexdumpApplet::new(),
+        UrlEncApplet::new(),
+        UrlDecApplet::new(),
+        CRC16Applet::new(),
+        CRC32Applet::new(),
+        CRCApplet::new(),
+        CrcRevApplet::new(),
+        CrcForgeApplet::new(),
+        HashApplet::new(),
+        HmacApplet::new(),
+        B64EncApplet::new(),
+        B64DecApplet::new(),
+        BofPattOffApplet::new(),
+        BofPattGenApplet::new(),
+        XorApplet::new(),
+        EntropyApplet::new(),
+        StatsApplet::new(),
+        SliceApplet::new(),
+        PatchApplet::new(),
+        SplitApplet::new(),
+        BgrepApplet::new(),
+        ZScanApplet::new(),
+        CarveApplet::new(),
+        BdiffApplet::new(),
+        FindSoApplet::new(),
+        TsDecApplet::new(),
+        TsEncApplet::new(),
+        DeflateApplet::new(),
+        InflateApplet::new(),
+        BaseIntApplet::new(),
+        EscapeApplet::new(),
+        UnEscapeApplet::new(),
+        CompressApplet::new(),
+        DecompressApplet::new(),
+        MagicApplet::new(),
+    ]
+}
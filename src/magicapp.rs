@@ -0,0 +1,269 @@
+use crate::applet::Applet;
+use crate::b64app::B64DecApplet;
+use crate::compressapp::decompress_all;
+use crate::entropyapp::{entropy, printable_ratio};
+use crate::escapeapp::unescape;
+use crate::flateapp::{inflate_all, FlateFormat};
+use crate::hexapp::hex_decode_hexonly;
+use crate::urlapp::url_decode;
+use anyhow::{bail, Result};
+use clap::{arg, value_parser, Command};
+
+type Decoder = fn(&[u8]) -> Option<Vec<u8>>;
+
+fn d64(val: &[u8], url_safe: bool) -> Option<Vec<u8>> {
+    B64DecApplet::with_options(url_safe, None, true)
+        .ok()?
+        .process(val.to_vec())
+        .ok()
+}
+
+/* Decoders tried at each step, named after the equivalent applet invocation.
+ * They must fail (return None) on data which is not encoded, as lenient
+ * decoding would succeed on anything. */
+const DECODERS: &[(&str, Decoder)] = &[
+    ("unhex", |val| hex_decode_hexonly(val, true).ok()),
+    ("d64", |val| d64(val, false)),
+    ("d64 -u", |val| d64(val, true)),
+    ("urldec", |val| {
+        if val.contains(&b'%') {
+            Some(url_decode(val))
+        } else {
+            None
+        }
+    }),
+    ("inflate -r", |val| {
+        inflate_all(val, Some(FlateFormat::Raw)).ok()
+    }),
+    ("inflate -z", |val| {
+        inflate_all(val, Some(FlateFormat::Zlib)).ok()
+    }),
+    ("inflate -g", |val| {
+        inflate_all(val, Some(FlateFormat::Gzip)).ok()
+    }),
+    // Only tries codecs which can be detected
+    ("decompress", |val| decompress_all(val, None).ok()),
+    ("unescape", |val| {
+        if val.contains(&b'\\') {
+            unescape(val).ok()
+        } else {
+            None
+        }
+    }),
+];
+
+// Minimum printable ratio for data to be considered as text
+const TEXT_RATIO: f64 = 0.95;
+
+/// Maximum number of decoders to chain, as each level multiplies the decodings
+pub const MAX_DEPTH: u32 = 8;
+
+// Stop exploring once this number of decodings is found
+const MAX_RESULTS: usize = 10000;
+
+// Encodings such as hex or base64 are printable too: prefer deeper
+// chains when they decode to text.
+const DEPTH_BONUS: f64 = 0.1;
+
+/// Result of a sequence of successful decoders.
+pub struct Decoding {
+    /// Decoders applied, named like the matching applet invocation, such as `inflate -z`
+    pub chain: Vec<&'static str>,
+    pub data: Vec<u8>,
+    pub entropy: f64,
+    pub printable: f64,
+}
+
+impl Decoding {
+    pub fn is_text(&self) -> bool {
+        self.printable >= TEXT_RATIO
+    }
+
+    /// Higher is better: printable, low entropy, data.
+    pub fn score(&self) -> f64 {
+        let mut score = self.printable - self.entropy;
+        if self.is_text() {
+            score += DEPTH_BONUS * self.chain.len() as f64;
+        }
+        score
+    }
+}
+
+fn explore(val: &[u8], chain: &mut Vec<&'static str>, depth: u32, res: &mut Vec<Decoding>) {
+    if depth == 0 {
+        return;
+    }
+    let mut outputs: Vec<Vec<u8>> = vec![];
+    for (name, decoder) in DECODERS {
+        if res.len() >= MAX_RESULTS {
+            return;
+        }
+        let decoded = match decoder(val) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        // Skip useless decodings, and duplicates (d64 and d64 -u often agree)
+        if decoded.is_empty() || decoded == val || outputs.contains(&decoded) {
+            continue;
+        }
+        chain.push(name);
+        explore(&decoded, chain, depth - 1, res);
+        res.push(Decoding {
+            chain: chain.clone(),
+            entropy: entropy(&decoded),
+            printable: printable_ratio(&decoded),
+            data: decoded.clone(),
+        });
+        chain.pop();
+        outputs.push(decoded);
+    }
+}
+
+/// Recursively try all decoders on `val`, up to `depth` levels (at most
+/// [`MAX_DEPTH`]), and return the successful decodings, best first.
+pub fn decode_all(val: &[u8], depth: u32) -> Vec<Decoding> {
+    let mut res = vec![];
+    explore(val, &mut vec![], depth.min(MAX_DEPTH), &mut res);
+    res.sort_by(|a, b| b.score().total_cmp(&a.score()));
+    res
+}
+
+pub struct MagicApplet {
+    depth: u32,
+    best: bool,
+}
+
+impl MagicApplet {
+    /// Build a `MagicApplet` trying decoders recursively up to `depth` levels,
+    /// clamped to [`MAX_DEPTH`]. If `best` is set, output the best decoded data
+    /// instead of the ranked chains.
+    pub fn with_options(depth: u32, best: bool) -> Self {
+        Self {
+            depth: depth.min(MAX_DEPTH),
+            best,
+        }
+    }
+}
+
+impl Applet for MagicApplet {
+    fn command(&self) -> &'static str {
+        "magic"
+    }
+
+    fn description(&self) -> &'static str {
+        "detect encodings and decode recursively"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(
+                arg!(-d --depth <depth> "maximum number of decoders to chain")
+                    .value_parser(value_parser!(u32).range(1..=i64::from(MAX_DEPTH)))
+                    .default_value("3"),
+            )
+            .arg(arg!(-b --best "output the best decoded data instead of the ranked chains"))
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            *args.get_one::<u32>("depth").unwrap(),
+            args.get_flag("best"),
+        )))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let decodings = decode_all(&val, self.depth);
+        if decodings.is_empty() {
+            bail!("No decoding found");
+        }
+        if self.best {
+            return Ok(decodings.into_iter().next().unwrap().data);
+        }
+        let lines: Vec<String> = decodings
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} -> {} (entropy {:.2}, {} bytes)",
+                    d.chain.join(" -> "),
+                    if d.is_text() { "text" } else { "binary" },
+                    d.entropy,
+                    d.data.len()
+                )
+            })
+            .collect();
+        Ok(lines.join("\n").into_bytes())
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            depth: 3,
+            best: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressapp::{Codec, CompressApplet};
+
+    #[test]
+    fn test_magic_hex() {
+        let decodings = decode_all(b"7465737420737472696e67\n", 3);
+        assert_eq!(decodings[0].chain, ["unhex"]);
+        assert_eq!(decodings[0].data, b"test string");
+        assert!(decodings[0].is_text());
+    }
+
+    #[test]
+    fn test_magic_b64_zlib() {
+        // b64 of zlib compressed "testtesttesttest\n"
+        let decodings = decode_all(b"eJwrSS0uKUHCXABCfwcL", 3);
+        assert_eq!(decodings[0].chain, ["d64", "inflate -z"]);
+        assert_eq!(decodings[0].data, b"testtesttesttest\n");
+    }
+
+    #[test]
+    fn test_magic_limits() {
+        // Nested hex, explored with an unlimited depth
+        let mut val = b"test".to_vec();
+        for _ in 0..6 {
+            val = hex::encode(&val).into_bytes();
+        }
+        let decodings = decode_all(&val, u32::MAX);
+        assert!(decodings.len() <= MAX_RESULTS + MAX_DEPTH as usize);
+        assert!(decodings
+            .iter()
+            .all(|d| d.chain.len() <= MAX_DEPTH as usize));
+        assert!(decodings.iter().any(|d| d.data == b"test"));
+        assert_eq!(MagicApplet::with_options(100, false).depth, MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_magic_best() {
+        let magic = MagicApplet::with_options(3, true);
+        assert_eq!(
+            magic.process(b"dGVzdCUyMHN0cmluZw==".to_vec()).unwrap(),
+            b"test string"
+        );
+        let magic = MagicApplet::with_options(1, true);
+        assert_eq!(
+            magic.process(b"dGVzdCUyMHN0cmluZw==".to_vec()).unwrap(),
+            b"test%20string"
+        );
+        assert!(magic.process(b"not encoded".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_magic_decompress() {
+        let xz = CompressApplet::with_options(Codec::Xz, None, "5d00008000")
+            .unwrap()
+            .process(b"test string".to_vec())
+            .unwrap();
+        let decodings = decode_all(hex::encode(xz).as_bytes(), 3);
+        assert_eq!(decodings[0].chain, ["unhex", "decompress"]);
+        assert_eq!(decodings[0].data, b"test string");
+    }
+}
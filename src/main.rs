@@ -1,97 +1,13 @@
 use anyhow::{anyhow, Context, Result};
 
+use std::fs::File;
 use std::io::{self, IsTerminal};
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
-extern crate base64;
 extern crate clap;
-use clap::Command;
-extern crate crc;
-extern crate hex;
-extern crate percent_encoding;
-
-mod applet;
-use applet::Applet;
-
-mod hexapp;
-use hexapp::HexApplet;
-use hexapp::UnHexApplet;
-
-mod urlapp;
-use urlapp::UrlDecApplet;
-use urlapp::UrlEncApplet;
-
-mod b64app;
-use b64app::B64DecApplet;
-use b64app::B64EncApplet;
-
-mod crcapp;
-use crcapp::CRC16Applet;
-use crcapp::CRC32Applet;
-use crcapp::CRCApplet;
-
-mod xorapp;
-use xorapp::XorApplet;
-
-mod sliceapp;
-use sliceapp::SliceApplet;
-
-mod timeapp;
-use timeapp::TsDecApplet;
-use timeapp::TsEncApplet;
-
-mod patternapp;
-use patternapp::BofPattGenApplet;
-use patternapp::BofPattOffApplet;
-
-mod entropyapp;
-use entropyapp::EntropyApplet;
-
-mod bgrepapp;
-use bgrepapp::BgrepApplet;
-
-mod findsoapp;
-use findsoapp::FindSoApplet;
-
-mod flateapp;
-use flateapp::DeflateApplet;
-use flateapp::InflateApplet;
-
-mod baseapp;
-use baseapp::BaseIntApplet;
-
-mod escapeapp;
-use escapeapp::EscapeApplet;
-use escapeapp::UnEscapeApplet;
-
-/// Build the list of all registered applets.
-fn applets() -> Vec<Box<dyn Applet>> {
-    vec![
-        HexApplet::new(),
-        UnHexApplet::new(),
-        UrlEncApplet::new(),
-        UrlDecApplet::new(),
-        CRC16Applet::new(),
-        CRC32Applet::new(),
-        CRCApplet::new(),
-        B64EncApplet::new(),
-        B64DecApplet::new(),
-        BofPattOffApplet::new(),
-        BofPattGenApplet::new(),
-        XorApplet::new(),
-        EntropyApplet::new(),
-        SliceApplet::new(),
-        BgrepApplet::new(),
-        FindSoApplet::new(),
-        TsDecApplet::new(),
-        TsEncApplet::new(),
-        DeflateApplet::new(),
-        InflateApplet::new(),
-        BaseIntApplet::new(),
-        EscapeApplet::new(),
-        UnEscapeApplet::new(),
-    ]
-}
+use clap::{arg, Command};
+
+use rsbkb::{applets, chain, recipe};
 
 fn main() -> Result<()> {
     let apps = applets();
@@ -120,6 +36,27 @@ fn main() -> Result<()> {
                 .subcommands([
                     Command::new("symlink").about("create symbolic links for applets (Unix only)")
                 ])
+                .subcommands([Command::new("chain")
+                    .about("run applets in a pipeline, inside a single process")
+                    .arg(arg!(<stages>... "applets to run, with their arguments. Data is read from stdin"))
+                    .after_help("Example: rsbkb chain 'unhex' 'inflate -z' 'd64 -u'")])
+                .subcommands([Command::new("recipe")
+                    .about("run applet pipelines saved in recipe files")
+                    .subcommand_required(true)
+                    .subcommand(Command::new("run")
+                        .about("run a recipe")
+                        .arg(arg!(-p --param <PARAM> "set a recipe parameter, as KEY=VALUE").action(clap::ArgAction::Append))
+                        .arg(arg!(-i --input <file> "file to use as input, reads from stdin if not present"))
+                        .arg(arg!(<recipe> "recipe file"))
+                        .after_help("\
+Recipes list one stage per line, with the same syntax as 'rsbkb chain' arguments.
+Lines starting with '#' are comments. $KEY or ${KEY} are replaced with parameters values, $$ with '$'.
+
+Example:
+  # decode a compressed token
+  unhex
+  inflate -z
+  xor -x $KEY"))])
                 .subcommand_value_name("APPLET")
                 .subcommand_help_heading("APPLETS")
                 .subcommands(apps.iter().map(|app| app.clap_command())),
@@ -169,6 +106,25 @@ fn main() -> Result<()> {
             println!("symlink command is only supported on Unix-like operating systems.");
         }
         return Ok(());
+    } else if subcommand == "chain" {
+        let stages: Vec<&String> = sub_matches.get_many::<String>("stages").unwrap().collect();
+        let stages = chain::parse_stages(&apps, &stages)?;
+
+        return run_chain(&stages, None);
+    } else if subcommand == "recipe" {
+        // "run" is the only recipe subcommand
+        let (_, run_matches) = sub_matches.subcommand().unwrap();
+        let recipe_file = run_matches.get_one::<String>("recipe").unwrap();
+        let recipe = std::fs::read_to_string(recipe_file)
+            .with_context(|| format!("Could not read recipe \"{}\"", recipe_file))?;
+        let params: Vec<&String> = run_matches
+            .get_many::<String>("param")
+            .unwrap_or_default()
+            .collect();
+        let params = recipe::parse_params(&params)?;
+        let stages = recipe::parse_recipe(&apps, &recipe, &params)?;
+
+        return run_chain(&stages, run_matches.get_one::<String>("input"));
     }
 
     // Find corresponding app
@@ -177,14 +133,10 @@ fn main() -> Result<()> {
     // Parse applet args and get actual applet with options
     let selected_app = selected_app.parse_args(sub_matches)?;
 
-    let mut inputval = vec![];
-
-    if let Some(argname) = selected_app.arg_or_stdin() {
+    let mut input: Box<dyn Read> = if let Some(argname) = selected_app.arg_or_stdin() {
         /* Check if the given arg is present, else read from stdin */
         if !sub_matches.contains_id(argname) {
-            io::stdin()
-                .read_to_end(&mut inputval)
-                .context("Reading stdin failed")?;
+            Box::new(io::stdin().lock())
         } else {
             /* Check if the given argument could be a filename, which is probably not
              * what the user wants */
@@ -195,31 +147,63 @@ fn main() -> Result<()> {
                     argname_val
                 );
             }
-            inputval = argname_val.as_bytes().to_vec();
+            Box::new(argname_val.as_bytes())
         }
+    } else {
+        Box::new(io::empty())
     };
 
-    let res = selected_app.process(inputval)?;
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let res = selected_app
+        .process_stream(&mut input, &mut stdout)
+        .and_then(|_| Ok(stdout.flush()?));
 
-    if selected_app.returns_data() {
-        let mut stdout = io::stdout();
-        let write_res = stdout.write_all(&res);
+    write_output(res, selected_app.returns_data())
+}
 
+/// Run the stages of a chain on the content of `input_file` (or stdin if not set)
+/// and write the result on stdout.
+fn run_chain(stages: &[chain::Stage], input_file: Option<&String>) -> Result<()> {
+    let mut inputval = vec![];
+    if stages[0].reads_input() {
+        if let Some(input_file) = input_file {
+            File::open(input_file)
+                .and_then(|mut f| f.read_to_end(&mut inputval))
+                .with_context(|| format!("Could not read input file \"{}\"", input_file))?;
+        } else {
+            io::stdin()
+                .read_to_end(&mut inputval)
+                .context("Reading stdin failed")?;
+        }
+    }
+    let res = chain::run_stages(stages, inputval)?;
+
+    let last = stages.last().unwrap();
+    write_output(
+        io::stdout().write_all(&res).map_err(Into::into),
+        last.returns_data(),
+    )
+}
+
+/// Handle the result of writing an applet's output to stdout.
+fn write_output(res: Result<()>, returns_data: bool) -> Result<()> {
+    match res {
         // Ignore broken pipe
-        match write_res {
-            Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => {
-                return Err(err.into());
-            }
-            Err(_) => {
-                return Ok(());
-            }
-            Ok(_) => (),
-        };
+        Err(err) if is_broken_pipe(&err) => return Ok(()),
+        Err(err) => return Err(err),
+        Ok(_) => (),
+    };
 
-        /* Only add a newline when outputing to a terminal */
-        if std::io::stdout().is_terminal() {
-            println!();
-        }
+    /* Only add a newline when outputing to a terminal */
+    if returns_data && std::io::stdout().is_terminal() {
+        println!();
     }
     Ok(())
 }
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
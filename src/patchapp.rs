@@ -0,0 +1,333 @@
+use crate::applet::Applet;
+use crate::bgrepapp::{build_pattern, map_input};
+use crate::sliceapp::{parse_value_with_prefix, Position};
+use anyhow::{bail, Context, Result};
+use clap::{arg, value_parser, Command};
+use std::convert::TryFrom;
+use std::fs;
+
+/// What [`patch`] does with the data.
+pub enum PatchMode {
+    /// Overwrite the bytes at `position`, or insert the data before them if
+    /// `insert` is set.
+    Write { position: Position, insert: bool },
+    /// Replace the first occurrence of `pattern`, or all of them if `all` is set.
+    /// If `count` is set, fail unless `pattern` is found exactly `count` times,
+    /// and replace them all.
+    Replace {
+        pattern: Vec<u8>,
+        all: bool,
+        count: Option<usize>,
+    },
+}
+
+/// Source of the bytes written by `patch`.
+pub enum PatchData {
+    Bytes(Vec<u8>),
+    /// Path of a file, "-" for stdin
+    File(String),
+}
+
+/// Patch `val` with `data` according to `mode`. Writes which would extend past
+/// the end of `val` fail, unless `extend` is set: `val` is then padded with zeros
+/// if needed.
+pub fn patch(val: &mut Vec<u8>, data: &[u8], mode: &PatchMode, extend: bool) -> Result<()> {
+    match mode {
+        PatchMode::Write { position, insert } => {
+            if position.relative {
+                bail!("Relative positions are not supported");
+            }
+            let offset = usize::try_from(position.offset).with_context(|| "Invalid position")?;
+            let offset = if position.from_end {
+                val.len()
+                    .checked_sub(offset)
+                    .with_context(|| "Position is before beginning of file")?
+            } else {
+                offset
+            };
+            let end = if *insert { offset } else { offset + data.len() };
+            if end > val.len() && !extend {
+                bail!(
+                    "Writing at 0x{:x} would extend past end of file (0x{:x}), use --extend to allow it",
+                    offset,
+                    val.len()
+                );
+            }
+            if end > val.len() {
+                val.resize(end, 0);
+            }
+            if *insert {
+                val.splice(offset..offset, data.iter().copied());
+            } else {
+                val[offset..end].copy_from_slice(data);
+            }
+        }
+        PatchMode::Replace {
+            pattern,
+            all,
+            count,
+        } => {
+            if pattern.is_empty() {
+                bail!("Empty pattern");
+            }
+            let escaped: String = pattern.iter().map(|b| format!("\\x{:02x}", b)).collect();
+            let regex = build_pattern(&escaped)?;
+            let mut matches: Vec<_> = regex.find_iter(val).map(|m| m.range()).collect();
+            match count {
+                Some(count) if matches.len() != *count => {
+                    bail!("Pattern found {} times instead of {}", matches.len(), count)
+                }
+                None if matches.is_empty() => bail!("Pattern not found"),
+                None if !all => matches.truncate(1),
+                _ => (),
+            }
+            let mut res = Vec::with_capacity(val.len());
+            let mut last = 0;
+            for range in matches {
+                res.extend_from_slice(&val[last..range.start]);
+                res.extend_from_slice(data);
+                last = range.end;
+            }
+            res.extend_from_slice(&val[last..]);
+            *val = res;
+        }
+    }
+    Ok(())
+}
+
+pub struct PatchApplet {
+    file: String,
+    data: PatchData,
+    mode: PatchMode,
+    output: Option<String>,
+    extend: bool,
+}
+
+impl PatchApplet {
+    /// Build a `PatchApplet` patching `file` ("-" for stdin) with `data`, see
+    /// [`patch`] for `mode` and `extend`. The result is written to `output` ("-"
+    /// for stdout), or to `file` if not set, except for stdin which goes to stdout.
+    pub fn with_options(
+        file: &str,
+        data: PatchData,
+        mode: PatchMode,
+        output: Option<String>,
+        extend: bool,
+    ) -> Result<Self> {
+        if file == "-" && matches!(&data, PatchData::File(path) if path == "-") {
+            bail!("Cannot read both the file and the data from stdin");
+        }
+        let output = match output {
+            None if file == "-" => Some("-".to_string()),
+            _ => output,
+        };
+        Ok(Self {
+            file: file.to_string(),
+            data,
+            mode,
+            output,
+            extend,
+        })
+    }
+}
+
+impl Applet for PatchApplet {
+    fn command(&self) -> &'static str {
+        "patch"
+    }
+
+    fn description(&self) -> &'static str {
+        "write bytes into a file"
+    }
+
+    fn returns_data(&self) -> bool {
+        self.output.as_deref() == Some("-")
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(arg!(-x --hex <hex> "bytes to write, as hex"))
+            .arg(
+                arg!(-f --from <file> "file containing the bytes to write, - for stdin (default)")
+                    .conflicts_with("hex"),
+            )
+            .arg(arg!(-r --replace <pattern> "replace occurrences of hex <pattern> instead of writing at <position>"))
+            .arg(arg!(-a --all "replace all occurrences of <pattern>").requires("replace"))
+            .arg(
+                arg!(-c --count <n> "fail unless <pattern> is found exactly <n> times, and replace them all")
+                    .value_parser(value_parser!(usize))
+                    .requires("replace"),
+            )
+            .arg(arg!(-i --insert "insert bytes instead of overwriting them").conflicts_with("replace"))
+            .arg(arg!(-e --extend "allow writing past the end of the file"))
+            .arg(arg!(-o --output <file> "write the result to <file> instead of patching in place, - for stdout"))
+            .arg(arg!(<file> "file to patch, - for stdin (written to stdout)"))
+            .arg(
+                arg!([position] "where to write, relative to end of file if negative")
+                    .required_unless_present("replace")
+                    .conflicts_with("replace"),
+            )
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file: String::new(),
+            data: PatchData::Bytes(vec![]),
+            mode: PatchMode::Write {
+                position: Position {
+                    offset: 0,
+                    relative: false,
+                    from_end: false,
+                },
+                insert: false,
+            },
+            output: None,
+            extend: false,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let data = if let Some(hex_data) = args.get_one::<String>("hex") {
+            PatchData::Bytes(hex::decode(hex_data).with_context(|| "Invalid hex data")?)
+        } else {
+            PatchData::File(
+                args.get_one::<String>("from")
+                    .map_or("-", |s| s.as_str())
+                    .to_string(),
+            )
+        };
+        let mode = if let Some(pattern) = args.get_one::<String>("replace") {
+            PatchMode::Replace {
+                pattern: hex::decode(pattern).with_context(|| "Invalid hex pattern")?,
+                all: args.get_flag("all"),
+                count: args.get_one::<usize>("count").copied(),
+            }
+        } else {
+            PatchMode::Write {
+                position: parse_value_with_prefix(args.get_one::<String>("position").unwrap())?,
+                insert: args.get_flag("insert"),
+            }
+        };
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            data,
+            mode,
+            args.get_one::<String>("output").cloned(),
+            args.get_flag("extend"),
+        )?))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut val = map_input(&self.file)?.to_vec();
+        let from_file;
+        let data = match &self.data {
+            PatchData::Bytes(bytes) => bytes,
+            PatchData::File(path) => {
+                from_file = map_input(path)?;
+                &from_file[..]
+            }
+        };
+
+        patch(&mut val, data, &self.mode, self.extend)?;
+
+        let output = self.output.as_ref().unwrap_or(&self.file);
+        if output == "-" {
+            return Ok(val);
+        }
+        fs::write(output, &val).with_context(|| format!("Could not write {}", output))?;
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_at(pos: &str, insert: bool) -> PatchMode {
+        PatchMode::Write {
+            position: parse_value_with_prefix(pos).unwrap(),
+            insert,
+        }
+    }
+
+    fn replace(pattern: &[u8], all: bool, count: Option<usize>) -> PatchMode {
+        PatchMode::Replace {
+            pattern: pattern.to_vec(),
+            all,
+            count,
+        }
+    }
+
+    fn patched(val: &[u8], data: &[u8], mode: &PatchMode, extend: bool) -> Result<Vec<u8>> {
+        let mut val = val.to_vec();
+        patch(&mut val, data, mode, extend)?;
+        Ok(val)
+    }
+
+    #[test]
+    fn test_patch_write() {
+        let val = b"0123456789";
+        assert_eq!(
+            patched(val, b"AB", &write_at("0x2", false), false).unwrap(),
+            b"01AB456789"
+        );
+        assert_eq!(
+            patched(val, b"AB", &write_at("2", true), false).unwrap(),
+            b"01AB23456789"
+        );
+        assert_eq!(
+            patched(val, b"AB", &write_at("-2", false), false).unwrap(),
+            b"01234567AB"
+        );
+        assert_eq!(
+            patched(val, b"AB", &write_at("10", true), false).unwrap(),
+            b"0123456789AB"
+        );
+
+        // Past end of file
+        assert!(patched(val, b"AB", &write_at("-1", false), false).is_err());
+        assert!(patched(val, b"AB", &write_at("12", true), false).is_err());
+        assert!(patched(val, b"AB", &write_at("-11", false), false).is_err());
+        assert_eq!(
+            patched(val, b"AB", &write_at("-1", false), true).unwrap(),
+            b"012345678AB"
+        );
+        assert_eq!(
+            patched(val, b"AB", &write_at("12", false), true).unwrap(),
+            b"0123456789\x00\x00AB"
+        );
+    }
+
+    #[test]
+    fn test_patch_replace() {
+        let val = b"abcXXabcYYabc";
+        assert_eq!(
+            patched(val, b"D", &replace(b"abc", false, None), false).unwrap(),
+            b"DXXabcYYabc"
+        );
+        assert_eq!(
+            patched(val, b"defg", &replace(b"abc", true, None), false).unwrap(),
+            b"defgXXdefgYYdefg"
+        );
+        assert_eq!(
+            patched(val, b"", &replace(b"abc", false, Some(3)), false).unwrap(),
+            b"XXYY"
+        );
+        assert!(patched(val, b"", &replace(b"abc", false, Some(2)), false).is_err());
+        assert!(patched(val, b"", &replace(b"ZZ", true, None), false).is_err());
+        assert_eq!(
+            patched(val, b"", &replace(b"ZZ", false, Some(0)), false).unwrap(),
+            val
+        );
+        // Regex special characters
+        assert_eq!(
+            patched(b"a.b.c", b"-", &replace(b".", true, None), false).unwrap(),
+            b"a-b-c"
+        );
+    }
+}
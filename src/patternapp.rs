@@ -8,11 +8,19 @@ pub struct BofPattGenApplet {
     len: usize,
 }
 
+impl BofPattGenApplet {
+    /// Build a `BofPattGenApplet` generating a pattern of `len` bytes.
+    pub fn with_options(len: usize) -> Self {
+        Self { len }
+    }
+}
+
 const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
 const DIGITS: &str = "0123456789";
 
-fn gen_pattern(len: usize, res: &mut Vec<u8>) {
+/// Append a cyclic pattern (`Aa0Aa1...`) of at least `len` bytes to `res`.
+pub fn gen_pattern(len: usize, res: &mut Vec<u8>) {
     for u in UPPER.bytes() {
         if res.len() >= len {
             return;
@@ -57,7 +65,7 @@ impl Applet for BofPattGenApplet {
         if len > max_len {
             eprintln!("Warning: pattern length's longer than max_len {}.", max_len);
         }
-        Ok(Box::new(Self { len }))
+        Ok(Box::new(Self::with_options(len)))
     }
 
     fn process(&self, _data: Vec<u8>) -> Result<Vec<u8>> {
@@ -72,6 +80,24 @@ pub struct BofPattOffApplet {
     extract: String,
 }
 
+impl BofPattOffApplet {
+    /// Build a `BofPattOffApplet` looking for `extract` in the pattern.
+    pub fn with_options(extract: &str) -> Self {
+        Self {
+            extract: extract.to_string(),
+        }
+    }
+}
+
+/// Find the offset of `extract` in the full cyclic pattern.
+pub fn pattern_offset(extract: &str) -> Option<usize> {
+    let max_len: usize = UPPER.len() * LOWER.len() * DIGITS.len() * 3;
+    let mut full_pattern: Vec<u8> = Vec::with_capacity(max_len);
+    gen_pattern(max_len, &mut full_pattern);
+    let pattern_str = String::from_utf8(full_pattern).unwrap();
+    pattern_str.find(extract)
+}
+
 impl Applet for BofPattOffApplet {
     fn command(&self) -> &'static str {
         "bofpattoff"
@@ -116,16 +142,12 @@ impl Applet for BofPattOffApplet {
         } else {
             extract.push_str(arg_val);
         }
-        Ok(Box::new(Self { extract }))
+        Ok(Box::new(Self::with_options(&extract)))
     }
 
     fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
         let max_len: usize = UPPER.len() * LOWER.len() * DIGITS.len() * 3;
-        let mut full_pattern: Vec<u8> = Vec::with_capacity(max_len);
-        gen_pattern(max_len, &mut full_pattern);
-        let pattern_str = String::from_utf8(full_pattern).unwrap();
-        let offset = pattern_str.find(self.extract.as_str());
-        let res = match offset {
+        let res = match pattern_offset(&self.extract) {
             Some(o) => format!("Offset: {} (mod {}) / {:#x}", o, max_len, o),
             _ => String::from("Pattern not found"),
         };
@@ -0,0 +1,129 @@
+use crate::applet::Applet;
+use crate::chain::{parse_split_stages, split_args, Stage};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+
+/* Replace parameters in `arg`:
+ * - `$KEY` and `${KEY}` are replaced by the value of `KEY`
+ * - `$$` is replaced by `$`
+ * Parameter names are made of ASCII alphanumeric chars and '_'.
+ */
+fn substitute(arg: &str, params: &HashMap<String, String>) -> Result<String> {
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    let mut res = String::with_capacity(arg.len());
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            res.push(c);
+            continue;
+        }
+        let name: String = match chars.peek() {
+            Some('$') => {
+                chars.next();
+                res.push('$');
+                continue;
+            }
+            Some('{') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => bail!("unterminated parameter \"${{{}\"", name),
+                    }
+                }
+                if name.is_empty() || !name.chars().all(|c| is_name_char(&c)) {
+                    bail!("invalid parameter name \"{}\"", name);
+                }
+                name
+            }
+            _ => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(is_name_char) {
+                    name.push(c);
+                }
+                if name.is_empty() {
+                    bail!("'$' must be followed by a parameter name, use '$$' for a literal '$'");
+                }
+                name
+            }
+        };
+        res.push_str(
+            params
+                .get(&name)
+                .ok_or_else(|| anyhow!("parameter \"{}\" is not defined", name))?,
+        );
+    }
+    Ok(res)
+}
+
+/// Parse parameters given as `KEY=VALUE` strings.
+pub fn parse_params<S: AsRef<str>>(params: &[S]) -> Result<HashMap<String, String>> {
+    let mut res = HashMap::new();
+    for p in params {
+        let (key, value) = p
+            .as_ref()
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid parameter \"{}\", expected KEY=VALUE", p.as_ref()))?;
+        res.insert(key.to_string(), value.to_string());
+    }
+    Ok(res)
+}
+
+/// Parse a recipe: each non-empty line is a stage, written like an `rsbkb chain`
+/// argument. Lines starting with '#' are comments.
+pub fn parse_recipe(
+    apps: &[Box<dyn Applet>],
+    recipe: &str,
+    params: &HashMap<String, String>,
+) -> Result<Vec<Stage>> {
+    let mut stages = vec![];
+    for (lineno, line) in recipe.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args = split_args(line)
+            .and_then(|args| args.iter().map(|a| substitute(a, params)).collect())
+            .with_context(|| format!("invalid recipe line {}", lineno + 1))?;
+        stages.push(args);
+    }
+    parse_split_stages(apps, &stages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::run_stages;
+    use crate::hexapp::{HexApplet, UnHexApplet};
+    use crate::xorapp::XorApplet;
+
+    fn apps() -> Vec<Box<dyn Applet>> {
+        vec![HexApplet::new(), UnHexApplet::new(), XorApplet::new()]
+    }
+
+    #[test]
+    fn test_substitute() {
+        let params = parse_params(&["KEY=41", "other=a b"]).unwrap();
+        assert_eq!(substitute("$KEY", &params).unwrap(), "41");
+        assert_eq!(substitute("x${KEY}x", &params).unwrap(), "x41x");
+        assert_eq!(substitute("$other-$$", &params).unwrap(), "a b-$");
+        assert!(substitute("$NOPE", &params).is_err());
+        assert!(substitute("$", &params).is_err());
+        assert!(substitute("${KEY", &params).is_err());
+    }
+
+    #[test]
+    fn test_recipe() {
+        let recipe = "# comment\n\nunhex\n  xor -x $KEY\n# hex at the end\nhex\n";
+        let params = parse_params(&["KEY=0102"]).unwrap();
+        let stages = parse_recipe(&apps(), recipe, &params).unwrap();
+        assert_eq!(
+            run_stages(&stages, b"41424344".to_vec()).unwrap(),
+            b"40404246"
+        );
+        assert!(parse_recipe(&apps(), recipe, &HashMap::new()).is_err());
+        assert!(parse_recipe(&apps(), "# empty", &params).is_err());
+    }
+}
@@ -1,35 +1,60 @@
 use crate::applet::{Applet, FromStrWithRadix};
 use anyhow::{bail, Context, Result};
-use clap::{arg, Command};
+use clap::{arg, value_parser, Command};
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 
+/// A position in a file, as given to `slice`.
 #[derive(Debug)]
-struct Position {
-    offset: u64,
-    relative: bool,
-    from_end: bool,
+pub struct Position {
+    pub offset: u64,
+    /// Relative to the start of the slice ("+" prefix)
+    pub relative: bool,
+    /// Relative to the end of the file ("-" prefix)
+    pub from_end: bool,
+}
+
+/// A range to extract, from `start` to `end` (or end of file if not set).
+#[derive(Debug)]
+pub struct SliceRange {
+    pub start: Position,
+    pub end: Option<Position>,
+}
+
+/// Strided extraction: keep `take` bytes, then skip `skip` bytes, and so on.
+#[derive(Debug, Clone, Copy)]
+pub struct Stride {
+    pub take: usize,
+    pub skip: usize,
+}
+
+impl Stride {
+    fn apply(&self, data: Vec<u8>) -> Vec<u8> {
+        // A group larger than the data only keeps its first bytes
+        data.chunks(self.take.saturating_add(self.skip))
+            .flat_map(|chunk| &chunk[..self.take.min(chunk.len())])
+            .copied()
+            .collect()
+    }
 }
 
 pub struct SliceApplet {
     file: Option<String>,
-    start: Position,
-    end: Option<Position>,
+    ranges: Vec<SliceRange>,
+    stride: Option<Stride>,
 }
 
-/* Helper to parse "start" and "end".
- * return value: value, plus_prefix, minus_prefix
- */
-fn parse_value_with_prefix(s: &String) -> Result<Position> {
+/// Parse a position such as `0x10`, `+10` (relative) or `-0x10` (from end).
+pub fn parse_value_with_prefix(s: &str) -> Result<Position> {
     if s.is_empty() {
         bail!("Invalid length for value");
     }
 
     let first = s.chars().next().unwrap();
 
-    let str_stripped = &s[1..].to_string();
+    let str_stripped = &s[1..];
 
-    let (from_end, relative, str_strip): (bool, bool, &String) = if first == '-' {
+    let (from_end, relative, str_strip): (bool, bool, &str) = if first == '-' {
         (true, false, str_stripped)
     } else if first == '+' {
         (false, true, str_stripped)
@@ -44,6 +69,26 @@ fn parse_value_with_prefix(s: &String) -> Result<Position> {
     })
 }
 
+/// Parse a list of ranges such as `0x100:+0x20,0x400:-0x10,-4`, each range
+/// being `start[:end]`, with positions parsed by [`parse_value_with_prefix`].
+pub fn parse_ranges(s: &str) -> Result<Vec<SliceRange>> {
+    s.split(',')
+        .map(|range| {
+            let mut parts = range.splitn(2, ':');
+            let start = parse_value_with_prefix(parts.next().unwrap())
+                .with_context(|| format!("Invalid range \"{}\"", range))?;
+            let end = match parts.next() {
+                Some(end) => Some(
+                    parse_value_with_prefix(end)
+                        .with_context(|| format!("Invalid range \"{}\"", range))?,
+                ),
+                None => None,
+            };
+            Ok(SliceRange { start, end })
+        })
+        .collect()
+}
+
 impl Applet for SliceApplet {
     fn command(&self) -> &'static str {
         "slice"
@@ -55,9 +100,16 @@ impl Applet for SliceApplet {
     fn clap_command(&self) -> Command {
         Command::new(self.command())
             .about(self.description())
+            .arg(arg!(-k --skip <n> "strided extraction: skip <n> bytes after each group of <take> bytes")
+                .value_parser(value_parser!(usize)))
+            .arg(arg!(-t --take <n> "number of bytes to take before skipping (default 1)")
+                .value_parser(value_parser!(usize))
+                .requires("skip"))
             .arg(arg!(<file>    "file to slice, - for stdin"))
-            .arg(arg!(<start>   "start of slice, relative to end of file if negative"))
+            .arg(arg!(<start>   "start of slice, relative to end of file if negative, or list of start:end ranges separated by commas"))
             .arg(arg!([end]     "end of slice: absolute, relative to <start> if prefixed with +, relative to end of file if negative"))
+            .after_help("Ranges are extracted in order and concatenated, for example \"0x100:+0x20,0x400:-0x10\".\n\
+                         With --skip, each range is split in groups of <take> bytes followed by <skip> bytes: \"-k 3\" keeps every 4th byte.")
     }
 
     fn arg_or_stdin(&self) -> Option<&'static str> {
@@ -67,12 +119,8 @@ impl Applet for SliceApplet {
     fn new() -> Box<dyn Applet> {
         Box::new(Self {
             file: None,
-            start: Position {
-                offset: 0,
-                relative: false,
-                from_end: false,
-            },
-            end: None,
+            ranges: vec![],
+            stride: None,
         })
     }
 
@@ -81,18 +129,27 @@ impl Applet for SliceApplet {
         let start_val = args.get_one::<String>("start").unwrap();
         let end_opt = args.get_one::<String>("end");
 
-        let start = parse_value_with_prefix(start_val)?;
+        let ranges = if start_val.contains([':', ',']) {
+            if end_opt.is_some() {
+                bail!("<end> cannot be used with a list of ranges");
+            }
+            parse_ranges(start_val)?
+        } else {
+            let start = parse_value_with_prefix(start_val)?;
 
-        let end = match end_opt {
-            None => None,
-            Some(end_val) => Some(parse_value_with_prefix(end_val)?),
+            let end = match end_opt {
+                None => None,
+                Some(end_val) => Some(parse_value_with_prefix(end_val)?),
+            };
+            vec![SliceRange { start, end }]
         };
 
-        Ok(Box::new(Self {
-            file: Some(filename.to_string()),
-            start,
-            end,
-        }))
+        let stride = args.get_one::<usize>("skip").map(|skip| Stride {
+            take: *args.get_one::<usize>("take").unwrap_or(&1),
+            skip: *skip,
+        });
+
+        Ok(Box::new(Self::with_ranges(filename, ranges, stride)?))
     }
 
     fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
@@ -104,7 +161,11 @@ impl Applet for SliceApplet {
                 .rewind()
                 .is_err()
         {
-            if self.start.from_end || self.end.as_ref().is_some_and(|e| e.from_end) {
+            if self
+                .ranges
+                .iter()
+                .any(|r| r.start.from_end || r.end.as_ref().is_some_and(|e| e.from_end))
+            {
                 bail!("Cannot seek from end in an unseekable file");
             }
             self.process_unseekable(filename)
@@ -115,6 +176,40 @@ impl Applet for SliceApplet {
 }
 
 impl SliceApplet {
+    /// Build a `SliceApplet` reading `file` ("-" for stdin) from `start` to `end`.
+    pub fn with_options(file: &str, start: Position, end: Option<Position>) -> Self {
+        Self {
+            file: Some(file.to_string()),
+            ranges: vec![SliceRange { start, end }],
+            stride: None,
+        }
+    }
+
+    /// Build a `SliceApplet` reading `ranges` from `file` ("-" for stdin) and
+    /// concatenating them, optionally applying `stride` to each of them.
+    /// Ranges must be in increasing order if `file` is not seekable.
+    pub fn with_ranges(
+        file: &str,
+        ranges: Vec<SliceRange>,
+        stride: Option<Stride>,
+    ) -> Result<Self> {
+        if stride.is_some_and(|s| s.take == 0) {
+            bail!("Stride must take at least 1 byte");
+        }
+        Ok(Self {
+            file: Some(file.to_string()),
+            ranges,
+            stride,
+        })
+    }
+
+    fn output(&self, res: &mut Vec<u8>, data: Vec<u8>) {
+        match self.stride {
+            Some(stride) => res.extend(stride.apply(data)),
+            None => res.extend(data),
+        }
+    }
+
     fn process_unseekable(&self, filename: &str) -> Result<Vec<u8>> {
         let mut f: Box<dyn BufRead> = if filename == "-" {
             Box::new(BufReader::new(std::io::stdin()))
@@ -128,35 +223,41 @@ impl SliceApplet {
             ))
         };
 
-        // Read initial data
         let mut res = Vec::new();
+        // Current position in the input
+        let mut pos = 0;
 
-        // Skip initial data efficiently without allocating
-        std::io::copy(
-            &mut f.by_ref().take(self.start.offset),
-            &mut std::io::sink(),
-        )
-        .with_context(|| "Could not skip until start")?;
-
-        let start = self.start.offset;
+        for range in &self.ranges {
+            let start = range.start.offset;
+            if start < pos {
+                bail!("Ranges must be in increasing order in an unseekable file");
+            }
 
-        if let Some(end_pos) = &self.end {
-            let end = if end_pos.relative {
-                start + end_pos.offset
+            // Skip initial data efficiently without allocating
+            std::io::copy(&mut f.by_ref().take(start - pos), &mut std::io::sink())
+                .with_context(|| "Could not skip until start")?;
+
+            let mut data = Vec::new();
+            if let Some(end_pos) = &range.end {
+                let end = if end_pos.relative {
+                    start + end_pos.offset
+                } else {
+                    end_pos.offset
+                };
+
+                if end < start {
+                    bail!("specified end < start");
+                }
+                let len: usize = (end - start) as usize;
+                data.resize(len, 0);
+                f.read_exact(&mut data).with_context(|| "Read failed")?;
             } else {
-                end_pos.offset
-            };
-
-            if end < start {
-                bail!("specified end < start");
+                f.read_to_end(&mut data).with_context(|| "Read failed")?;
             }
-            let len: usize = (end - start) as usize;
-            res.resize(len, 0);
-            f.read_exact(&mut res).with_context(|| "Read failed")?;
-        } else {
-            f.read_to_end(&mut res).with_context(|| "Read failed")?;
+            pos = start + data.len() as u64;
+            self.output(&mut res, data);
         }
-        Ok(res.to_vec())
+        Ok(res)
     }
 
     fn process_seekable(&self, filename: &str) -> Result<Vec<u8>> {
@@ -171,42 +272,49 @@ impl SliceApplet {
             .len();
         let mut fbuf = BufReader::new(&f);
 
-        let start = if self.start.from_end {
-            if self.start.offset > flen {
-                bail!("start is before beginning of file");
-            }
-            flen - self.start.offset
-        } else {
-            self.start.offset
-        };
-        if start > flen {
-            bail!("start (0x{:X}) is after end of file (0x{:X})", start, flen);
-        }
-        fbuf.seek(SeekFrom::Start(start))
-            .with_context(|| "seek failed")?;
-
         let mut res = vec![];
-        if let Some(end_pos) = &self.end {
-            let end = if end_pos.from_end {
-                flen - end_pos.offset
-            } else if end_pos.relative {
-                start + end_pos.offset
+        for range in &self.ranges {
+            let start = if range.start.from_end {
+                if range.start.offset > flen {
+                    bail!("start is before beginning of file");
+                }
+                flen - range.start.offset
             } else {
-                end_pos.offset
+                range.start.offset
             };
-
-            if end < start {
-                bail!("specified end < start");
-            } else if end > flen {
-                bail!("end (0x{:X}) is after end of file (0x{:X})", end, flen);
+            if start > flen {
+                bail!("start (0x{:X}) is after end of file (0x{:X})", start, flen);
             }
-            let len: usize = (end - start) as usize;
-            res.resize(len, 0);
-            fbuf.read_exact(&mut res).with_context(|| "Read failed")?;
-        } else {
-            fbuf.read_to_end(&mut res).with_context(|| "Read failed")?;
+            fbuf.seek(SeekFrom::Start(start))
+                .with_context(|| "seek failed")?;
+
+            let mut data = vec![];
+            if let Some(end_pos) = &range.end {
+                let end = if end_pos.from_end {
+                    if end_pos.offset > flen {
+                        bail!("end is before beginning of file");
+                    }
+                    flen - end_pos.offset
+                } else if end_pos.relative {
+                    start + end_pos.offset
+                } else {
+                    end_pos.offset
+                };
+
+                if end < start {
+                    bail!("specified end < start");
+                } else if end > flen {
+                    bail!("end (0x{:X}) is after end of file (0x{:X})", end, flen);
+                }
+                let len: usize = (end - start) as usize;
+                data.resize(len, 0);
+                fbuf.read_exact(&mut data).with_context(|| "Read failed")?;
+            } else {
+                fbuf.read_to_end(&mut data).with_context(|| "Read failed")?;
+            }
+            self.output(&mut res, data);
         }
-        Ok(res.to_vec())
+        Ok(res)
     }
 }
 
@@ -230,19 +338,19 @@ mod tests {
     fn test_empty_slice() {
         let (tmpfile, d) = setup();
         let filepath = tmpfile.path().to_str().unwrap().to_string();
-        let pat = SliceApplet {
-            file: Some(filepath),
-            start: Position {
+        let pat = SliceApplet::with_options(
+            &filepath,
+            Position {
                 offset: 0,
                 relative: false,
                 from_end: false,
             },
-            end: Some(Position {
+            Some(Position {
                 offset: 0,
                 relative: false,
                 from_end: false,
             }),
-        };
+        );
 
         assert_eq!(d[0..0], pat.process_test(Vec::new()));
     }
@@ -251,19 +359,19 @@ mod tests {
     fn test_slice() {
         let (tmpfile, d) = setup();
         let filepath = tmpfile.path().to_str().unwrap().to_string();
-        let pat = SliceApplet {
-            file: Some(filepath),
-            start: Position {
+        let pat = SliceApplet::with_options(
+            &filepath,
+            Position {
                 offset: 0,
                 relative: false,
                 from_end: false,
             },
-            end: Some(Position {
+            Some(Position {
                 offset: 10,
                 relative: false,
                 from_end: false,
             }),
-        };
+        );
 
         assert_eq!(d[0..10], pat.process_test(Vec::new()));
     }
@@ -272,15 +380,15 @@ mod tests {
     fn test_slice_to_end() {
         let (tmpfile, d) = setup();
         let filepath = tmpfile.path().to_str().unwrap().to_string();
-        let pat = SliceApplet {
-            file: Some(filepath),
-            start: Position {
+        let pat = SliceApplet::with_options(
+            &filepath,
+            Position {
                 offset: 10,
                 relative: false,
                 from_end: false,
             },
-            end: None,
-        };
+            None,
+        );
 
         assert_eq!(d[10..], pat.process_test(Vec::new()));
     }
@@ -289,20 +397,62 @@ mod tests {
     fn test_slice_end_from_end() {
         let (tmpfile, d) = setup();
         let filepath = tmpfile.path().to_str().unwrap().to_string();
-        let pat = SliceApplet {
-            file: Some(filepath),
-            start: Position {
+        let pat = SliceApplet::with_options(
+            &filepath,
+            Position {
                 offset: 10,
                 relative: false,
                 from_end: false,
             },
-            end: Some(Position {
+            Some(Position {
                 offset: 10,
                 relative: false,
                 from_end: true,
             }),
-        };
+        );
 
         assert_eq!(d[10..(d.len() - 10)], pat.process_test(Vec::new()));
     }
+
+    #[test]
+    fn test_slice_ranges() {
+        let (tmpfile, d) = setup();
+        let filepath = tmpfile.path().to_str().unwrap().to_string();
+        let ranges = parse_ranges("0x10:+4,-0x10:-0xc,2:4,90").unwrap();
+        let pat = SliceApplet::with_ranges(&filepath, ranges, None).unwrap();
+
+        let mut expected = d[0x10..0x14].to_vec();
+        expected.extend(&d[84..88]);
+        expected.extend(&d[2..4]);
+        expected.extend(&d[90..]);
+        assert_eq!(expected, pat.process_test(Vec::new()));
+
+        assert!(parse_ranges("0x10:").is_err());
+        assert!(parse_ranges("0x10,,2").is_err());
+    }
+
+    #[test]
+    fn test_slice_stride() {
+        let (tmpfile, d) = setup();
+        let filepath = tmpfile.path().to_str().unwrap().to_string();
+        let ranges = parse_ranges("1:+10,50").unwrap();
+        let stride = Stride { take: 2, skip: 3 };
+        let pat = SliceApplet::with_ranges(&filepath, ranges, Some(stride)).unwrap();
+
+        let mut expected = vec![d[1], d[2], d[6], d[7]];
+        for i in (50..100).step_by(5) {
+            expected.extend(&d[i..i + 2]);
+        }
+        assert_eq!(expected, pat.process_test(Vec::new()));
+
+        let stride = Stride { take: 1, skip: 3 };
+        assert_eq!(stride.apply((0..10).collect()), [0, 4, 8]);
+        let stride = Stride {
+            take: 2,
+            skip: usize::MAX,
+        };
+        assert_eq!(stride.apply((0..10).collect()), [0, 1]);
+        let stride = Stride { take: 0, skip: 3 };
+        assert!(SliceApplet::with_ranges(&filepath, vec![], Some(stride)).is_err());
+    }
 }
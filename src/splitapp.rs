@@ -0,0 +1,272 @@
+use crate::applet::{Applet, FromStrWithRadix};
+use crate::bgrepapp::{build_pattern, hex_pattern, map_input};
+use anyhow::{bail, Context, Result};
+use clap::{arg, ArgGroup, Command};
+use regex::bytes::Regex;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+const DEFAULT_TEMPLATE: &str = "split_{n}_{offset}.bin";
+
+/// Where [`chunks`] cuts its input.
+pub enum SplitMode {
+    /// Chunks of a fixed size, the last one can be shorter
+    Size(usize),
+    /// At the start of each match of the pattern
+    Pattern(Regex),
+    /// At the given offsets
+    Offsets(Vec<usize>),
+}
+
+/// Parse offsets, one per line, such as the output of `bgrep`: in lines with
+/// several words, such as `file: 0x10 [label]: 6869`, the first `0x` offset is
+/// used. Capture groups (indented), hex dumps and `--` separators are ignored.
+pub fn parse_offsets(text: &str) -> Result<Vec<usize>> {
+    let mut res = vec![];
+    for line in text.lines() {
+        if line.starts_with(char::is_whitespace) || line == "--" {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => continue,
+            [word] => res.push(
+                usize::from_str_with_radix(word)
+                    .with_context(|| format!("Invalid offset \"{}\"", word))?,
+            ),
+            _ => {
+                let offset = words.iter().find_map(|word| {
+                    let word = word.strip_suffix(':').unwrap_or(word);
+                    if !word.starts_with("0x") {
+                        return None;
+                    }
+                    usize::from_str_with_radix(word).ok()
+                });
+                match offset {
+                    Some(offset) => res.push(offset),
+                    // Hex dump line, starting with the offset without "0x"
+                    None if words[0].len() >= 8
+                        && words[0].bytes().all(|c| c.is_ascii_hexdigit()) => {}
+                    None => bail!("No offset found in \"{}\"", line),
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Cut `val` according to `mode`, returning the offset and length of each chunk.
+/// Data before the first split point is returned as the first chunk.
+pub fn chunks(val: &[u8], mode: &SplitMode) -> Result<Vec<(usize, usize)>> {
+    let mut points: Vec<usize> = match mode {
+        SplitMode::Size(0) => bail!("Chunk size cannot be 0"),
+        SplitMode::Size(size) => (0..val.len()).step_by(*size).collect(),
+        SplitMode::Pattern(regex) => regex.find_iter(val).map(|m| m.start()).collect(),
+        SplitMode::Offsets(offsets) => {
+            if let Some(off) = offsets.iter().find(|&&off| off > val.len()) {
+                bail!(
+                    "Offset 0x{:x} is after end of file (0x{:x})",
+                    off,
+                    val.len()
+                );
+            }
+            offsets.clone()
+        }
+    };
+    points.push(0);
+    points.push(val.len());
+    points.sort_unstable();
+    points.dedup();
+    Ok(points.windows(2).map(|w| (w[0], w[1] - w[0])).collect())
+}
+
+pub struct SplitApplet {
+    file: String,
+    mode: SplitMode,
+    template: String,
+    manifest: Option<String>,
+}
+
+impl SplitApplet {
+    /// Build a `SplitApplet` cutting `file` ("-" for stdin) according to `mode`.
+    /// Chunks are written to files named after `template`, in which `{n}` and
+    /// `{offset}` are replaced by the chunk number and offset. If `manifest` is
+    /// set, the offset, length and name of each chunk are written to it ("-" for
+    /// stdout).
+    pub fn with_options(
+        file: &str,
+        mode: SplitMode,
+        template: &str,
+        manifest: Option<String>,
+    ) -> Result<Self> {
+        if !template.contains("{n}") && !template.contains("{offset}") {
+            bail!("Name template must contain {{n}} or {{offset}}");
+        }
+        Ok(Self {
+            file: file.to_string(),
+            mode,
+            template: template.to_string(),
+            manifest,
+        })
+    }
+
+    fn chunk_name(&self, n: usize, offset: usize) -> String {
+        self.template
+            .replace("{n}", &format!("{:04}", n))
+            .replace("{offset}", &format!("{:08x}", offset))
+    }
+}
+
+impl Applet for SplitApplet {
+    fn command(&self) -> &'static str {
+        "split"
+    }
+
+    fn description(&self) -> &'static str {
+        "split a file in chunks"
+    }
+
+    fn returns_data(&self) -> bool {
+        false
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(arg!(-s --size <size> "split in chunks of <size> bytes"))
+            .arg(arg!(-p --pattern <pattern> "split at each match of <pattern> (regex)"))
+            .arg(arg!(-x --hex "<pattern> is hex, with bgrep wildcards").requires("pattern"))
+            .arg(arg!(-O --offsets <file> "split at the offsets listed in <file>, one per line (bgrep output)"))
+            .group(
+                ArgGroup::new("mode")
+                    .args(["size", "pattern", "offsets"])
+                    .required(true),
+            )
+            .arg(
+                arg!(-o --output <template> "name of the output files, {n} and {offset} being replaced by the chunk number and offset")
+                    .default_value(DEFAULT_TEMPLATE),
+            )
+            .arg(arg!(-m --manifest <file> "write the offset, length and name of each chunk to <file>, - for stdout"))
+            .arg(arg!(<file> "file to split, - for stdin"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file: String::new(),
+            mode: SplitMode::Size(0),
+            template: DEFAULT_TEMPLATE.to_string(),
+            manifest: None,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        let mode = if let Some(size) = args.get_one::<String>("size") {
+            SplitMode::Size(usize::from_str_with_radix(size).with_context(|| "Invalid size")?)
+        } else if let Some(pattern_val) = args.get_one::<String>("pattern") {
+            let pattern = if args.get_flag("hex") {
+                build_pattern(&hex_pattern(pattern_val)?)?
+            } else {
+                build_pattern(pattern_val)?
+            };
+            SplitMode::Pattern(pattern)
+        } else {
+            let path = args.get_one::<String>("offsets").unwrap();
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Could not read offsets from {}", path))?;
+            SplitMode::Offsets(parse_offsets(&text)?)
+        };
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            mode,
+            args.get_one::<String>("output").unwrap(),
+            args.get_one::<String>("manifest").cloned(),
+        )?))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let data = map_input(&self.file)?;
+
+        let mut manifest: Option<Box<dyn Write>> = match self.manifest.as_deref() {
+            None => None,
+            Some("-") => Some(Box::new(io::stdout())),
+            Some(path) => Some(Box::new(
+                File::create(path).with_context(|| format!("Could not create {}", path))?,
+            )),
+        };
+
+        for (n, (offset, len)) in chunks(&data, &self.mode)?.into_iter().enumerate() {
+            let name = self.chunk_name(n, offset);
+            let path = Path::new(&name);
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Could not create {}", dir.to_string_lossy()))?;
+            }
+            fs::write(path, &data[offset..offset + len])
+                .with_context(|| format!("Could not write {}", name))?;
+            if let Some(manifest) = manifest.as_mut() {
+                writeln!(manifest, "0x{:x} {} {}", offset, len, name)
+                    .with_context(|| "Could not write manifest")?;
+            }
+        }
+
+        /* Return empty Vec as chunks are written to files */
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let val = b"0123hsqs4567hsqs89";
+        assert_eq!(
+            chunks(val, &SplitMode::Size(8)).unwrap(),
+            [(0, 8), (8, 8), (16, 2)]
+        );
+        assert!(chunks(val, &SplitMode::Size(0)).is_err());
+        assert_eq!(chunks(b"", &SplitMode::Size(8)).unwrap(), []);
+
+        let pattern = SplitMode::Pattern(build_pattern(&"hsqs").unwrap());
+        assert_eq!(chunks(val, &pattern).unwrap(), [(0, 4), (4, 8), (12, 6)]);
+        assert_eq!(chunks(&val[4..], &pattern).unwrap(), [(0, 8), (8, 6)]);
+
+        let offsets = SplitMode::Offsets(vec![12, 4, 4, 18]);
+        assert_eq!(chunks(val, &offsets).unwrap(), [(0, 4), (4, 8), (12, 6)]);
+        assert!(chunks(val, &SplitMode::Offsets(vec![19])).is_err());
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        assert_eq!(
+            parse_offsets("0x10\n\n32\nfile: 0x40\n").unwrap(),
+            [0x10, 32, 0x40]
+        );
+        assert!(parse_offsets("0x10\nzz\n").is_err());
+        assert!(parse_offsets("0x10\nsome text\n").is_err());
+
+        // bgrep -p hsqs
+        assert_eq!(
+            parse_offsets("0x4: 68737173\n0xd: 68737173\n").unwrap(),
+            [4, 0xd]
+        );
+        // bgrep -e utf8,utf16le hsqs
+        assert_eq!(parse_offsets("0x4 [utf8]\n0xd [utf8]\n").unwrap(), [4, 0xd]);
+        // bgrep -C 2 -g 'h(s)qs', on two files
+        let output = "f.bin: 0x4
+  1 @ 0x5: 73
+00000002  63 64 68 73 71 73 65 66                           |cdhsqsef|
+--
+0x1.bin: 0xd [h(s)qs]: 68737173
+  1 @ 0xe: 73
+0000000b  68 00 68 73 71 73                                 |h.hsqs|
+";
+        assert_eq!(parse_offsets(output).unwrap(), [4, 0xd]);
+    }
+}
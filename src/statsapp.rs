@@ -0,0 +1,428 @@
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
+use crate::entropyapp::{entropy_from_counts, is_printable};
+use anyhow::{bail, Result};
+use clap::{arg, Command};
+use std::cmp::Reverse;
+use std::f64::consts::{FRAC_2_SQRT_PI, PI};
+use std::io::{Read, Write};
+
+/// Number of most common bytes reported
+const TOP_BYTES: usize = 5;
+
+/// Statistics on some data, as computed by `ent`, useful to tell compressed
+/// data from encrypted data, text or code.
+pub struct Stats {
+    pub size: u64,
+    /// Number of occurences of each byte value
+    pub histogram: [u64; 256],
+    /// Shannon entropy, normalized between 0 and 1
+    pub entropy: f64,
+    /// Chi-square of the byte distribution, against a uniform distribution
+    pub chi_square: f64,
+    /// Probability of a random distribution exceeding `chi_square`
+    pub chi_square_p: f64,
+    /// Arithmetic mean of the bytes, 127.5 for random data
+    pub mean: f64,
+    /// Monte Carlo estimate of pi, using 24-bit coordinates from 6 bytes groups
+    pub pi: f64,
+    /// Serial correlation coefficient, close to 0 for random data. Not defined
+    /// when all bytes are identical.
+    pub serial_correlation: Option<f64>,
+    /// Ratio of printable ASCII chars, including whitespace
+    pub printable: f64,
+}
+
+impl Stats {
+    /// Return the `n` most common bytes with their number of occurences.
+    pub fn most_common(&self, n: usize) -> Vec<(u8, u64)> {
+        let mut bytes: Vec<(u8, u64)> = (0..=255u8)
+            .map(|b| (b, self.histogram[b as usize]))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        bytes.sort_by_key(|(_, count)| Reverse(*count));
+        bytes.truncate(n);
+        bytes
+    }
+
+    /// Relative error of the pi estimate
+    pub fn pi_error(&self) -> f64 {
+        (self.pi - PI).abs() / PI
+    }
+}
+
+/* Probability of the normal z value, and of the chi-square value with the
+ * given degrees of freedom, adapted from Gary Perlman's public domain code
+ * as used by `ent` */
+fn poz(z: f64) -> f64 {
+    const Z_MAX: f64 = 6.0;
+    let x = if z == 0.0 {
+        0.0
+    } else {
+        let y = 0.5 * z.abs();
+        if y >= Z_MAX * 0.5 {
+            1.0
+        } else if y < 1.0 {
+            let w = y * y;
+            ((((((((0.000124818987 * w - 0.001075204047) * w + 0.005198775019) * w
+                - 0.019198292004)
+                * w
+                + 0.059054035642)
+                * w
+                - 0.151968751364)
+                * w
+                + 0.319152932694)
+                * w
+                - 0.531923007300)
+                * w
+                + 0.797884560593)
+                * y
+                * 2.0
+        } else {
+            let y = y - 2.0;
+            (((((((((((((-0.000045255659 * y + 0.000152529290) * y - 0.000019538132) * y
+                - 0.000676904986)
+                * y
+                + 0.001390604284)
+                * y
+                - 0.000794620820)
+                * y
+                - 0.002034254874)
+                * y
+                + 0.006549791214)
+                * y
+                - 0.010557625006)
+                * y
+                + 0.011630447319)
+                * y
+                - 0.009279453341)
+                * y
+                + 0.005353579108)
+                * y
+                - 0.002141268741)
+                * y
+                + 0.000535310849)
+                * y
+                + 0.999936657524
+        }
+    };
+    if z > 0.0 {
+        (x + 1.0) * 0.5
+    } else {
+        (1.0 - x) * 0.5
+    }
+}
+
+fn pochisq(x: f64, df: u32) -> f64 {
+    const I_SQRT_PI: f64 = FRAC_2_SQRT_PI / 2.0;
+    const BIGX: f64 = 20.0;
+    let ex = |x: f64| if x < -BIGX { 0.0 } else { x.exp() };
+
+    if x <= 0.0 || df < 1 {
+        return 1.0;
+    }
+    let a = 0.5 * x;
+    let even = df % 2 == 0;
+    let y = ex(-a);
+    let mut s = if even { y } else { 2.0 * poz(-x.sqrt()) };
+    if df <= 2 {
+        return s;
+    }
+    let x = 0.5 * (df as f64 - 1.0);
+    let mut z = if even { 1.0 } else { 0.5 };
+    if a > BIGX {
+        // log(sqrt(pi))
+        let mut e = if even { 0.0 } else { PI.ln() / 2.0 };
+        let c = a.ln();
+        while z <= x {
+            e += z.ln();
+            s += ex(c * z - a - e);
+            z += 1.0;
+        }
+        s
+    } else {
+        let mut e = if even { 1.0 } else { I_SQRT_PI / a.sqrt() };
+        let mut c = 0.0;
+        while z <= x {
+            e *= a / z;
+            c += e;
+            z += 1.0;
+        }
+        c * y + s
+    }
+}
+
+/// Compute the [`Stats`] of the data read from `input`.
+pub fn compute_stats(input: &mut dyn Read) -> Result<Stats> {
+    let mut histogram = [0u64; 256];
+    let mut sum = 0u64;
+    /* Monte Carlo: current group of 6 bytes, number of points, inside the circle */
+    let mut monte = [0u8; 6];
+    let (mut monte_len, mut points, mut inside) = (0, 0u64, 0u64);
+    const RADIUS: u64 = (1 << 24) - 1;
+    /* Serial correlation: sum of products of consecutive bytes, sum of squares */
+    let (mut first, mut last) = (None, 0u64);
+    let (mut products, mut squares) = (0u64, 0u64);
+
+    let mut buf = vec![0; STREAM_BUF_SIZE];
+    loop {
+        let len = read_chunk(input, &mut buf)?;
+        for b in &buf[..len] {
+            let v = *b as u64;
+            histogram[*b as usize] += 1;
+            sum += v;
+            squares += v * v;
+            if first.is_none() {
+                first = Some(v);
+            } else {
+                products += last * v;
+            }
+            last = v;
+
+            monte[monte_len] = *b;
+            monte_len += 1;
+            if monte_len == monte.len() {
+                let coord = |b: &[u8]| b.iter().fold(0u64, |acc, v| (acc << 8) | *v as u64);
+                let (x, y) = (coord(&monte[..3]), coord(&monte[3..]));
+                points += 1;
+                if x * x + y * y <= RADIUS * RADIUS {
+                    inside += 1;
+                }
+                monte_len = 0;
+            }
+        }
+        if len < buf.len() {
+            break;
+        }
+    }
+
+    let size: u64 = histogram.iter().sum();
+    if size == 0 {
+        bail!("No data");
+    }
+    let n = size as f64;
+    let expected = n / 256.0;
+    let chi_square = histogram
+        .iter()
+        .map(|c| (*c as f64 - expected).powi(2) / expected)
+        .sum();
+    // The last byte is correlated with the first one, like in `ent`
+    products += last * first.unwrap();
+    let sum_squared = (sum as f64).powi(2);
+    let denominator = n * squares as f64 - sum_squared;
+    let printable: u64 = (0..=255u8)
+        .filter(|b| is_printable(*b))
+        .map(|b| histogram[b as usize])
+        .sum();
+
+    Ok(Stats {
+        size,
+        histogram,
+        entropy: entropy_from_counts(&histogram),
+        chi_square,
+        chi_square_p: pochisq(chi_square, 255),
+        mean: sum as f64 / n,
+        pi: if points > 0 {
+            4.0 * inside as f64 / points as f64
+        } else {
+            0.0
+        },
+        // All bytes identical: the denominator is 0
+        serial_correlation: if histogram.iter().filter(|c| **c > 0).count() == 1 {
+            None
+        } else {
+            Some((n * products as f64 - sum_squared) / denominator)
+        },
+        printable: printable as f64 / n,
+    })
+}
+
+pub struct StatsApplet {
+    json: bool,
+    histogram: bool,
+}
+
+impl StatsApplet {
+    /// Build a `StatsApplet`, outputting a single line of JSON if `json` is
+    /// set, and the full histogram if `histogram` is set (always in JSON).
+    pub fn with_options(json: bool, histogram: bool) -> Self {
+        Self { json, histogram }
+    }
+
+    fn format_json(&self, stats: &Stats) -> String {
+        let top: Vec<String> = stats
+            .most_common(TOP_BYTES)
+            .iter()
+            .map(|(b, count)| format!("[{}, {}]", b, count))
+            .collect();
+        let histogram: Vec<String> = stats.histogram.iter().map(|c| c.to_string()).collect();
+        format!(
+            "{{\"size\": {}, \"entropy\": {:.6}, \"chi_square\": {:.2}, \"chi_square_p\": {:.6}, \
+             \"mean\": {:.4}, \"pi\": {:.6}, \"serial_correlation\": {}, \"printable\": {:.6}, \
+             \"most_common\": [{}], \"histogram\": [{}]}}",
+            stats.size,
+            stats.entropy,
+            stats.chi_square,
+            stats.chi_square_p,
+            stats.mean,
+            stats.pi,
+            stats
+                .serial_correlation
+                .map_or("null".to_string(), |c| format!("{:.6}", c)),
+            stats.printable,
+            top.join(", "),
+            histogram.join(", ")
+        )
+    }
+
+    fn format_text(&self, stats: &Stats) -> String {
+        let top: Vec<String> = stats
+            .most_common(TOP_BYTES)
+            .iter()
+            .map(|(b, count)| format!("0x{:02x} ({:.2}%)", b, percent(*count, stats.size)))
+            .collect();
+        let mut lines = vec![
+            format!("size: {} bytes", stats.size),
+            format!(
+                "entropy: {:.6} ({:.6} bits per byte)",
+                stats.entropy,
+                stats.entropy * 8.0
+            ),
+            format!(
+                "chi-square: {:.2}, randomly exceeded {:.2}% of the times",
+                stats.chi_square,
+                stats.chi_square_p * 100.0
+            ),
+            format!("mean: {:.4} (127.5 = random)", stats.mean),
+            format!(
+                "monte carlo pi: {:.9} (error {:.2}%)",
+                stats.pi,
+                stats.pi_error() * 100.0
+            ),
+            match stats.serial_correlation {
+                Some(c) => format!("serial correlation: {:.6} (0.0 = random)", c),
+                None => "serial correlation: undefined (all bytes are identical)".to_string(),
+            },
+            format!("printable: {:.2}%", stats.printable * 100.0),
+            format!("most common: {}", top.join(", ")),
+        ];
+        if self.histogram {
+            lines.push("histogram:".to_string());
+            for (b, count) in stats.histogram.iter().enumerate() {
+                if *count > 0 {
+                    lines.push(format!(
+                        "  0x{:02x} {} ({:.2}%)",
+                        b,
+                        count,
+                        percent(*count, stats.size)
+                    ));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+fn percent(count: u64, size: u64) -> f64 {
+    count as f64 * 100.0 / size as f64
+}
+
+impl Applet for StatsApplet {
+    fn command(&self) -> &'static str {
+        "stats"
+    }
+
+    fn description(&self) -> &'static str {
+        "byte statistics (entropy, chi-square, serial correlation...)"
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(arg!(-j --json "output a single line of JSON, including the histogram"))
+            .arg(arg!(-H --histogram "output the histogram"))
+            .arg(arg!([value] "input value, reads from stdin if not present"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            json: false,
+            histogram: false,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_flag("json"),
+            args.get_flag("histogram"),
+        )))
+    }
+
+    fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
+        let mut res = vec![];
+        self.process_stream(&mut val.as_slice(), &mut res)?;
+        Ok(res)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let stats = compute_stats(input)?;
+        let res = if self.json {
+            self.format_json(&stats)
+        } else {
+            self.format_text(&stats)
+        };
+        output.write_all(res.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let data: Vec<u8> = (0..256 * 64).map(|i| (i % 256) as u8).collect();
+        let stats = compute_stats(&mut data.as_slice()).unwrap();
+        assert_eq!(stats.size, 256 * 64);
+        assert_eq!(stats.entropy, 1.0);
+        assert_eq!(stats.chi_square, 0.0);
+        assert_eq!(stats.chi_square_p, 1.0);
+        assert_eq!(stats.mean, 127.5);
+        assert!(stats.serial_correlation.unwrap() > 0.9);
+        assert_eq!(stats.printable, 99.0 / 256.0);
+
+        let stats = compute_stats(&mut &b"aaaa"[..]).unwrap();
+        assert_eq!(stats.serial_correlation, None);
+        assert_eq!(stats.most_common(3), [(b'a', 4)]);
+        assert!(compute_stats(&mut &b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_stats_random() {
+        // Deterministic pseudo-random data (xorshift)
+        let mut state = 0x2545f4914f6cdd1du64;
+        let data: Vec<u8> = (0..1_000_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 56) as u8
+            })
+            .collect();
+        let stats = compute_stats(&mut data.as_slice()).unwrap();
+        assert!(stats.entropy > 0.9999);
+        assert!(stats.chi_square_p > 0.01 && stats.chi_square_p < 0.99);
+        assert!((stats.mean - 127.5).abs() < 0.5);
+        assert!(stats.pi_error() < 0.01);
+        assert!(stats.serial_correlation.unwrap().abs() < 0.01);
+    }
+
+    #[test]
+    fn test_pochisq() {
+        // Reference values from the chi-square distribution
+        assert!((pochisq(255.0, 255) - 0.4876).abs() < 0.001);
+        assert!((pochisq(293.25, 255) - 0.05).abs() < 0.001);
+        assert!((pochisq(3.84, 1) - 0.05).abs() < 0.001);
+        assert!((pochisq(5.99, 2) - 0.05).abs() < 0.001);
+    }
+}
@@ -16,12 +16,12 @@ use time::{format_description, Duration, OffsetDateTime, UtcDateTime, UtcOffset}
       2025: 1735689600
       2030: 1900000000
 */
-fn decode_epoch_seconds(ts: i64) -> Result<OffsetDateTime> {
+pub fn decode_epoch_seconds(ts: i64) -> Result<OffsetDateTime> {
     OffsetDateTime::from_unix_timestamp(ts).with_context(|| "Could not decode as epoch")
 }
 
-/* Decode epoch date with more precision */
-fn decode_epoch_subseconds(ts: i64, resolution: i64) -> Result<OffsetDateTime> {
+/// Decode epoch date with more precision: `ts` is in 1/`resolution` seconds.
+pub fn decode_epoch_subseconds(ts: i64, resolution: i64) -> Result<OffsetDateTime> {
     let micros: i32 = i32::try_from((ts % resolution) * (1_000_000_000 / resolution)).unwrap();
     let unix = OffsetDateTime::from_unix_timestamp(ts / resolution);
     if let Ok(date) = unix {
@@ -43,14 +43,16 @@ fn decode_epoch_subseconds(ts: i64, resolution: i64) -> Result<OffsetDateTime> {
       2025: 133801632000000000
       2065: 146424672000000000
 */
-fn decode_windows_filetime(ts: i64) -> Result<OffsetDateTime> {
+pub fn decode_windows_filetime(ts: i64) -> Result<OffsetDateTime> {
     /* Shift to Unix Epoch */
     let shifted = ts - 116_444_736_000_000_000;
     decode_epoch_subseconds(shifted, 10_000_000)
 }
 
+/// Timestamp types supported by `tsenc`
 #[derive(clap::ValueEnum, Clone, Default, Debug)]
-enum TimeEncoding {
+#[allow(clippy::upper_case_acronyms)]
+pub enum TimeEncoding {
     #[default]
     UnixSecond = 1,
     UnixCentiSecond = 100,
@@ -61,8 +63,9 @@ enum TimeEncoding {
     Chrome,
 }
 
+/// Date formats supported by `tsenc`
 #[derive(clap::ValueEnum, Clone, Default, Debug)]
-enum TimeFormats {
+pub enum TimeFormats {
     #[default]
     Iso8601,
     Rfc2822,
@@ -74,6 +77,17 @@ pub struct TsEncApplet {
     input_format: TimeFormats,
 }
 
+impl TsEncApplet {
+    /// Build a `TsEncApplet` parsing dates in `input_format` and encoding them as
+    /// `encoding_type` timestamps.
+    pub fn with_options(encoding_type: TimeEncoding, input_format: TimeFormats) -> Self {
+        Self {
+            encoding_type,
+            input_format,
+        }
+    }
+}
+
 impl Applet for TsEncApplet {
     fn command(&self) -> &'static str {
         "tsenc"
@@ -106,10 +120,10 @@ impl Applet for TsEncApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            encoding_type: args.get_one::<TimeEncoding>("type").unwrap().clone(),
-            input_format: args.get_one::<TimeFormats>("input-format").unwrap().clone(),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_one::<TimeEncoding>("type").unwrap().clone(),
+            args.get_one::<TimeFormats>("input-format").unwrap().clone(),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
@@ -152,6 +166,14 @@ pub struct TsDecApplet {
     verbose: bool,
 }
 
+impl TsDecApplet {
+    /// Build a `TsDecApplet`, showing dates in the local time zone if `local` is set.
+    /// If `verbose` is set, the detected timestamp type is printed on stderr.
+    pub fn with_options(local: bool, verbose: bool) -> Self {
+        Self { local, verbose }
+    }
+}
+
 impl Applet for TsDecApplet {
     fn command(&self) -> &'static str {
         "tsdec"
@@ -176,10 +198,10 @@ impl Applet for TsDecApplet {
     }
 
     fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
-        Ok(Box::new(Self {
-            local: args.get_flag("local"),
-            verbose: args.get_flag("verbose"),
-        }))
+        Ok(Box::new(Self::with_options(
+            args.get_flag("local"),
+            args.get_flag("verbose"),
+        )))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
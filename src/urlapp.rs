@@ -1,13 +1,24 @@
-use crate::applet::Applet;
 use crate::applet::SliceExt;
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
 use anyhow::Result;
 use clap::{arg, Command};
+use std::io::{Read, Write};
 
 pub struct UrlEncApplet {
     // true: should be encoded
     table: [bool; 256],
 }
 
+/// Set of chars to encode with `UrlEncApplet`.
+pub enum UrlEncMode {
+    /// All non alphanumeric (ASCII) chars
+    Default,
+    /// Non graphic chars and reserved chars from RFC 3986
+    Rfc3986,
+    /// Only the chars of the given string
+    Custom(String),
+}
+
 // Encoding table according to RFC 3986
 fn build_url_table(excluded: &str, table: &mut [bool; 256]) {
     for i in 0..256 {
@@ -100,20 +111,51 @@ impl Applet for UrlEncApplet {
         } else {
             &empty_exclude
         };
-        let mut table = [false; 256];
-        if args.get_flag("rfc3986") {
-            build_url_table(excluded, &mut table);
-        } else if args.contains_id("custom") {
-            let custom = args.get_one::<String>("custom").unwrap();
-            build_custom_table(excluded, custom, &mut table);
+        let mode = if args.get_flag("rfc3986") {
+            UrlEncMode::Rfc3986
+        } else if let Some(custom) = args.get_one::<String>("custom") {
+            UrlEncMode::Custom(custom.to_string())
         } else {
-            build_default_table(excluded, &mut table);
+            UrlEncMode::Default
         };
-        Ok(Box::new(Self { table }))
+        Ok(Box::new(Self::with_options(&mode, excluded)))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
         let mut encoded = Vec::with_capacity(val.len());
+        self.encode_into(&val, &mut encoded);
+        Ok(encoded)
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let mut buf = vec![0; STREAM_BUF_SIZE];
+        let mut encoded = Vec::with_capacity(STREAM_BUF_SIZE);
+        loop {
+            let len = read_chunk(input, &mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+            encoded.clear();
+            self.encode_into(&buf[..len], &mut encoded);
+            output.write_all(&encoded)?;
+        }
+    }
+}
+
+impl UrlEncApplet {
+    /// Build an `UrlEncApplet` encoding chars according to `mode`, except the ones
+    /// in `excluded`.
+    pub fn with_options(mode: &UrlEncMode, excluded: &str) -> Self {
+        let mut table = [false; 256];
+        match mode {
+            UrlEncMode::Default => build_default_table(excluded, &mut table),
+            UrlEncMode::Rfc3986 => build_url_table(excluded, &mut table),
+            UrlEncMode::Custom(custom) => build_custom_table(excluded, custom, &mut table),
+        };
+        Self { table }
+    }
+
+    fn encode_into(&self, val: &[u8], encoded: &mut Vec<u8>) {
         for b in val.iter() {
             if self.table[*b as usize] {
                 // format! is not the fastest, but we are encoding URLs, not gigabytes of data
@@ -122,7 +164,6 @@ impl Applet for UrlEncApplet {
                 encoded.push(*b);
             };
         }
-        Ok(encoded)
     }
 }
 
@@ -145,12 +186,15 @@ impl Applet for UrlDecApplet {
     }
 
     fn process(&self, urlval: Vec<u8>) -> Result<Vec<u8>> {
-        let trimmed: Vec<u8> = urlval.trim().into();
-        let decoded: Vec<u8> = percent_encoding::percent_decode(&trimmed).collect();
-        Ok(decoded)
+        Ok(url_decode(&urlval))
     }
 }
 
+/// Decode percent-encoded data, ignoring surrounding whitespace.
+pub fn url_decode(urlval: &[u8]) -> Vec<u8> {
+    percent_encoding::percent_decode(urlval.trim()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +219,17 @@ mod tests {
         assert_eq!(String::from_utf8(encoded).unwrap(), "%00%ff");
     }
 
+    #[test]
+    fn test_urlenc_stream() {
+        let mut table = [false; 256];
+        build_default_table("", &mut table);
+        let urlenc = UrlEncApplet { table };
+        assert_eq!(
+            urlenc.process_stream_test("aA!,é".as_bytes().to_vec()),
+            b"aA%21%2c%c3%a9"
+        );
+    }
+
     #[test]
     fn test_urlencdec() {
         let mut table = [false; 256];
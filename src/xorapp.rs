@@ -1,12 +1,26 @@
-use crate::applet::Applet;
+use crate::applet::{read_chunk, Applet, STREAM_BUF_SIZE};
 use anyhow::{Context, Result};
 use clap::{arg, Command};
 use std::fs;
+use std::io::{Read, Write};
 
 pub struct XorApplet {
     key_bytes: Vec<u8>,
 }
 
+impl XorApplet {
+    /// Build a `XorApplet` using `key_bytes` as the key.
+    pub fn with_options(key_bytes: Vec<u8>) -> Self {
+        Self { key_bytes }
+    }
+}
+
+/// Xor `val` with `key`, repeated as needed.
+pub fn xor(val: &[u8], key: &[u8]) -> Vec<u8> {
+    let inf_key = key.iter().cycle(); // Iterate endlessly over key bytes
+    val.iter().zip(inf_key).map(|(x, k)| x ^ k).collect()
+}
+
 impl Applet for XorApplet {
     fn command(&self) -> &'static str {
         "xor"
@@ -39,12 +53,32 @@ impl Applet for XorApplet {
             fs::read(args.get_one::<String>("keyfile").unwrap())
                 .with_context(|| "Could not read keyfile")?
         };
-        Ok(Box::new(Self { key_bytes }))
+        Ok(Box::new(Self::with_options(key_bytes)))
     }
 
     fn process(&self, val: Vec<u8>) -> Result<Vec<u8>> {
-        let inf_key = self.key_bytes.iter().cycle(); // Iterate endlessly over key bytes
-        Ok(val.iter().zip(inf_key).map(|(x, k)| x ^ k).collect())
+        Ok(xor(&val, &self.key_bytes))
+    }
+
+    fn process_stream(&self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        // Mimic `process`: an empty key produces an empty output
+        if self.key_bytes.is_empty() {
+            return Ok(());
+        }
+        let mut buf = vec![0; STREAM_BUF_SIZE];
+        // Keep track of the key position between chunks
+        let mut key_pos = 0;
+        loop {
+            let len = read_chunk(input, &mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+            for b in buf[..len].iter_mut() {
+                *b ^= self.key_bytes[key_pos];
+                key_pos = (key_pos + 1) % self.key_bytes.len();
+            }
+            output.write_all(&buf[..len])?;
+        }
     }
 }
 
@@ -69,4 +103,14 @@ mod tests {
             vec![1, 0x55, 0xAA, 0xFF, 0, 1]
         );
     }
+
+    #[test]
+    fn test_stream() {
+        let x = XorApplet {
+            key_bytes: vec![1, 2, 3],
+        };
+        // Make sure the key position is kept between chunks
+        let data = vec![0x42; STREAM_BUF_SIZE + 2];
+        assert_eq!(x.process_stream_test(data.clone()), x.process_test(data));
+    }
 }
@@ -0,0 +1,286 @@
+use crate::applet::Applet;
+use crate::bgrepapp::map_input;
+use crate::entropyapp::entropy;
+use crate::flateapp::{detect_format, inflate_prefix, FlateFormat};
+use anyhow::{Context, Result};
+use clap::{arg, value_parser, Command};
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+use std::fs;
+use std::path::PathBuf;
+
+/// Compressed stream found by [`scan`]
+pub struct FoundStream {
+    pub offset: usize,
+    pub format: FlateFormat,
+    /// Length of the stream, including headers and trailers
+    pub compressed_len: usize,
+    pub data: Vec<u8>,
+}
+
+fn format_name(format: FlateFormat) -> &'static str {
+    match format {
+        FlateFormat::Raw => "raw",
+        FlateFormat::Zlib => "zlib",
+        FlateFormat::Gzip => "gzip",
+    }
+}
+
+/* Check quickly if a raw deflate stream starts at the beginning of some data,
+ * without keeping the decompressed data. As it is tried at every offset, the
+ * decompressor and its (wrapping) output buffer are reused. */
+struct RawProbe {
+    decompressor: Box<DecompressorOxide>,
+    window: Vec<u8>,
+}
+
+impl RawProbe {
+    fn new() -> Self {
+        Self {
+            decompressor: Box::default(),
+            // Must be a power of 2 and at least the size of the deflate window
+            window: vec![0; 32768],
+        }
+    }
+
+    /// Return the decompressed size of the stream at the start of `val`, if valid.
+    fn probe(&mut self, val: &[u8]) -> Option<usize> {
+        self.decompressor.init();
+        let (mut in_pos, mut out_pos, mut total) = (0, 0, 0);
+        loop {
+            let (status, consumed, written) = decompress(
+                &mut self.decompressor,
+                &val[in_pos..],
+                &mut self.window,
+                out_pos,
+                0,
+            );
+            in_pos += consumed;
+            total += written;
+            out_pos = (out_pos + written) & (self.window.len() - 1);
+            match status {
+                TINFLStatus::Done => return Some(total),
+                TINFLStatus::HasMoreOutput => continue,
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Find the gzip and Zlib streams in `val`, and the raw deflate streams if `brute`
+/// is set, calling `found` for each of them. Streams which decompress to less than
+/// `min_size` bytes are ignored, and scanning resumes after the end of each stream.
+pub fn scan(
+    val: &[u8],
+    brute: bool,
+    min_size: usize,
+    found: &mut dyn FnMut(FoundStream) -> Result<()>,
+) -> Result<()> {
+    let mut probe = RawProbe::new();
+    let mut offset = 0;
+    while offset < val.len() {
+        // When brute forcing, data which looks like a header can also be raw deflate
+        let formats = Some(detect_format(&val[offset..]))
+            .filter(|format| *format != FlateFormat::Raw)
+            .into_iter()
+            .chain(brute.then_some(FlateFormat::Raw));
+        let mut next = offset + 1;
+        for format in formats {
+            // Avoid the cost of a full decompression for each offset
+            if format == FlateFormat::Raw
+                && probe.probe(&val[offset..]).is_none_or(|len| len < min_size)
+            {
+                continue;
+            }
+            match inflate_prefix(&val[offset..], format) {
+                Ok((data, compressed_len)) if data.len() >= min_size => {
+                    found(FoundStream {
+                        offset,
+                        format,
+                        compressed_len,
+                        data,
+                    })?;
+                    next = offset + compressed_len;
+                    break;
+                }
+                _ => (),
+            }
+        }
+        offset = next;
+    }
+    Ok(())
+}
+
+pub struct ZScanApplet {
+    file: String,
+    brute: bool,
+    min_size: usize,
+    output_dir: Option<PathBuf>,
+}
+
+impl ZScanApplet {
+    /// Build a `ZScanApplet` scanning `file` ("-" for stdin), see [`scan`] for `brute`
+    /// and `min_size`. If `output_dir` is set, decompressed streams are written to it.
+    pub fn with_options(
+        file: &str,
+        brute: bool,
+        min_size: usize,
+        output_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            file: file.to_string(),
+            brute,
+            min_size,
+            output_dir,
+        }
+    }
+}
+
+impl Applet for ZScanApplet {
+    fn command(&self) -> &'static str {
+        "zscan"
+    }
+
+    fn description(&self) -> &'static str {
+        "find deflate, Zlib and gzip streams"
+    }
+
+    fn returns_data(&self) -> bool {
+        false
+    }
+
+    fn arg_or_stdin(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn clap_command(&self) -> Command {
+        Command::new(self.command())
+            .about(self.description())
+            .arg(arg!(-b --brute "also look for raw deflate streams at every offset (slow)"))
+            .arg(
+                arg!(-m --"min-size" <size> "minimum decompressed size")
+                    .value_parser(value_parser!(usize))
+                    .default_value("16"),
+            )
+            .arg(arg!(-o --output <dir> "write decompressed streams to <dir>, as OFFSET.FORMAT"))
+            .arg(arg!(<file> "file to scan, - for stdin"))
+    }
+
+    fn new() -> Box<dyn Applet> {
+        Box::new(Self {
+            file: String::new(),
+            brute: false,
+            min_size: 16,
+            output_dir: None,
+        })
+    }
+
+    fn parse_args(&self, args: &clap::ArgMatches) -> Result<Box<dyn Applet>> {
+        Ok(Box::new(Self::with_options(
+            args.get_one::<String>("file").unwrap(),
+            args.get_flag("brute"),
+            *args.get_one::<usize>("min-size").unwrap(),
+            args.get_one::<String>("output").map(PathBuf::from),
+        )))
+    }
+
+    fn process(&self, _val: Vec<u8>) -> Result<Vec<u8>> {
+        let data = map_input(&self.file)?;
+
+        if let Some(dir) = &self.output_dir {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Could not create {}", dir.to_string_lossy()))?;
+        }
+
+        scan(&data, self.brute, self.min_size, &mut |stream| {
+            /* Print streams on stdout directly, to avoid buffering */
+            println!(
+                "0x{:x}: {}, {} -> {} bytes, entropy {:.3}",
+                stream.offset,
+                format_name(stream.format),
+                stream.compressed_len,
+                stream.data.len(),
+                entropy(&stream.data)
+            );
+            if let Some(dir) = &self.output_dir {
+                let path = dir.join(format!(
+                    "{:08x}.{}",
+                    stream.offset,
+                    format_name(stream.format)
+                ));
+                fs::write(&path, &stream.data)
+                    .with_context(|| format!("Could not write {}", path.to_string_lossy()))?;
+            }
+            Ok(())
+        })?;
+
+        /* Return empty Vec as we output directly on stdout */
+        Ok(Vec::<u8>::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flateapp::DeflateApplet;
+
+    fn scan_all(val: &[u8], brute: bool) -> Vec<(usize, FlateFormat, usize, Vec<u8>)> {
+        let mut res = vec![];
+        scan(val, brute, 16, &mut |s| {
+            res.push((s.offset, s.format, s.compressed_len, s.data));
+            Ok(())
+        })
+        .unwrap();
+        res
+    }
+
+    #[test]
+    fn test_scan() {
+        let text = b"some text which is long enough to be found".to_vec();
+        let mut blob = b"\x78\x9cgarbage".to_vec();
+        let mut streams = vec![];
+        for format in [FlateFormat::Zlib, FlateFormat::Raw, FlateFormat::Gzip] {
+            let def = DeflateApplet::with_options(format, 6, None, 0).unwrap();
+            let compressed = def.process(text.clone()).unwrap();
+            streams.push((blob.len(), format, compressed.len(), text.clone()));
+            blob.extend(compressed);
+            blob.extend(b"\x00\x01garbage");
+        }
+
+        // Raw deflate is only found when brute forcing
+        let found = scan_all(&blob, false);
+        assert_eq!(found, [streams[0].clone(), streams[2].clone()]);
+        assert_eq!(scan_all(&blob, true), streams);
+    }
+
+    #[test]
+    fn test_scan_raw_like_header() {
+        // Raw deflate starting with a stored block whose first bytes, 78 01,
+        // are also a valid Zlib header
+        let text = b"some text which is long enough to be found";
+        let mut stream = b"\x78\x01\x00\xfe\xff>".to_vec();
+        stream.extend([0x01, text.len() as u8, 0, !text.len() as u8, 0xff]);
+        stream.extend(text);
+        let mut blob = b"garbage".to_vec();
+        blob.extend(&stream);
+
+        let mut expected = b">".to_vec();
+        expected.extend(text);
+        assert_eq!(
+            scan_all(&blob, true),
+            [(7, FlateFormat::Raw, stream.len(), expected)]
+        );
+        assert!(scan_all(&blob, false).is_empty());
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut probe = RawProbe::new();
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        let def = DeflateApplet::with_options(FlateFormat::Raw, 6, None, 0).unwrap();
+        let compressed = def.process(data).unwrap();
+        assert_eq!(probe.probe(&compressed), Some(100_000));
+        assert_eq!(probe.probe(&compressed[..compressed.len() - 1]), None);
+        assert_eq!(probe.probe(b"\xff\xff"), None);
+    }
+}
@@ -132,6 +132,57 @@ fn test_unhex_cli_stdin_strict() {
         .failure();
 }
 
+#[test]
+fn test_hexdump_unhex_reverse_cli() {
+    let data: Vec<u8> = (0..=255u8).collect();
+    for format in ["canonical", "xxd", "c", "python", "rust"] {
+        let dump = Command::cargo_bin("rsbkb")
+            .expect("Could not run binary")
+            .args(["hexdump", "-f", format])
+            .write_stdin(data.clone())
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+
+        Command::cargo_bin("rsbkb")
+            .expect("Could not run binary")
+            .args(["unhex", "-r"])
+            .write_stdin(dump)
+            .assert()
+            .stdout(data.clone())
+            .success();
+    }
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hexdump", "-s", "0x41", "-n", "3"])
+        .write_stdin(data)
+        .assert()
+        .stdout("00000041  41 42 43                                          |ABC|\n00000044")
+        .success();
+}
+
+#[test]
+fn test_unhex_reverse_plain_cli() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["unhex", "-r"])
+        .write_stdin("41424344\n45464748\n")
+        .assert()
+        .stdout(&b"ABCDEFGH"[..])
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["unhex", "-r"])
+        .write_stdin("4142 4344 4546")
+        .assert()
+        .stdout(&b"ABCDEF"[..])
+        .success();
+}
+
 // UrlEnc/UrlDec CLI tests
 
 #[test]
@@ -175,13 +226,240 @@ fn test_urlenc_stdin() {
         .success();
 }
 
+// B64Enc/B64Dec CLI tests
+
+#[test]
+fn test_d64_cli_whitespace() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .arg("d64")
+        .write_stdin("  Zm9v\n")
+        .assert()
+        .stdout("foo")
+        .success();
+}
+
+#[test]
+fn test_d64_cli_strict() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["d64", "--strict"])
+        .write_stdin("Zm9v\n")
+        .assert()
+        .stdout("foo")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["d64", "--strict", "Zm9v!!!!"])
+        .assert()
+        .stderr(contains("Decoding base64 failed"))
+        .failure();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["d64", "Zm9v!!!!"])
+        .assert()
+        .stdout("foo!!!!")
+        .success();
+}
+
+// CRCApplet CLI tests
+
+#[test]
+fn test_crc_cli_custom() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "crc",
+            "--width",
+            "16",
+            "--poly",
+            "0x8005",
+            "--init",
+            "0xffff",
+            "--refin",
+            "--refout",
+            "--xorout",
+            "0",
+            "123456789",
+        ])
+        .assert()
+        .stdout("4b37")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["crc", "-w", "5", "-p", "0x15", "--refin", "--refout"])
+        .write_stdin("123456789")
+        .assert()
+        .stdout("07")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["crc", "-w", "8", "-p", "0x107", "123456789"])
+        .assert()
+        .stderr(contains("does not fit in 8 bits"))
+        .failure();
+}
+
+// CrcRevApplet CLI tests
+
+#[test]
+fn test_crcrev_cli() {
+    let mut samples = NamedTempFile::new().unwrap();
+    samples
+        .write_all(b"# Modbus requests\n0103000a0002 09e4\n01030064000a 1284\n0206000100ff 7998\n")
+        .unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["crcrev", samples.path().to_str().unwrap()])
+        .assert()
+        .stdout("CRC_16_MODBUS\n")
+        .success();
+
+    // Custom CRC, stored in little endian at the end of messages
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["crcrev", "-t", "-"])
+        .write_stdin(
+            "303130333030306130303032e4af\n\
+             30313033303036343030306186a9\n\
+             3032303630303031303066666030\n\
+             303131303030303130303032303430303061303130322e1b\n\
+             6161626263638781\n",
+        )
+        .assert()
+        .stdout(
+            "--width 16 --poly 0xc867 --init 0x1234 --refin --refout --xorout 0xffff  # little endian\n",
+        )
+        .success();
+}
+
+// CrcForgeApplet CLI tests
+
+#[test]
+fn test_crcforge_cli() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(b"firmware v2 ....").unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    // Overwrite the last 4 bytes to get the CRC of "firmware v1 ...."
+    let forged = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "crcforge",
+            "-a",
+            "CRC_32_ISO_HDLC",
+            "-t",
+            "08ff4bf0",
+            path,
+            "--",
+            "-4",
+        ])
+        .assert()
+        .stdout(&b"firmware v2 \xed\x7d\x03\x05"[..])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .arg("crc32")
+        .write_stdin(forged)
+        .assert()
+        .stdout("08ff4bf0")
+        .success();
+
+    // Append bytes keeping the CRC unchanged
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["crcforge", "-w", "16", "-p", "0x1021", "-b", path])
+        .assert()
+        .stdout(&b"\x2a\xd9"[..])
+        .success();
+}
+
+// HashApplet CLI tests
+
+#[test]
+fn test_hash_cli() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hash", "abc"])
+        .assert()
+        .stdout("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hash", "-a", "sha1", "-r"])
+        .write_stdin("abc")
+        .assert()
+        .stdout(&b"\xa9\x99\x3e\x36\x47\x06\x81\x6a\xba\x3e\x25\x71\x78\x50\xc2\x6c\x9c\xd0\xd8\x9d"[..])
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hash", "-a", "all"])
+        .write_stdin("abc")
+        .assert()
+        .stdout(contains(
+            "sha3-256: 3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532\n",
+        ))
+        .success();
+}
+
+// HmacApplet CLI tests
+
+#[test]
+fn test_hmac_cli() {
+    let mut keyfile = NamedTempFile::new().unwrap();
+    keyfile.write_all(b"key").unwrap();
+    let msg = "The quick brown fox jumps over the lazy dog";
+    let mac = "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hmac", "-k", "6b6579", msg])
+        .assert()
+        .stdout(mac)
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hmac", "-a", "sha1", "-f", keyfile.path().to_str().unwrap()])
+        .write_stdin(msg)
+        .assert()
+        .stdout("de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hmac", "-k", "6b6579", "--verify", mac])
+        .write_stdin(msg)
+        .assert()
+        .stdout("")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["hmac", "-k", "6b6579", "--verify", mac, "the lazy dog"])
+        .assert()
+        .stderr(contains("HMAC mismatch"))
+        .failure();
+}
+
 // SliceApplet CLI tests
 
 #[test]
 fn test_slice_cli_file() {
     let mut data: [u8; 10] = [0; 10];
-    for i in 0..10 {
-        data[i] = i as u8;
+    for (i, d) in data.iter_mut().enumerate() {
+        *d = i as u8;
     }
 
     let mut tmpfile = NamedTempFile::new().unwrap();
@@ -239,8 +517,8 @@ fn test_slice_cli_file() {
 #[test]
 fn test_slice_cli_stdin() {
     let mut data: [u8; 10] = [0; 10];
-    for i in 0..10 {
-        data[i] = i as u8;
+    for (i, d) in data.iter_mut().enumerate() {
+        *d = i as u8;
     }
 
     Command::cargo_bin("rsbkb")
@@ -286,6 +564,154 @@ fn test_slice_cli_stdin() {
         .success();
 }
 
+#[test]
+fn test_slice_cli_ranges() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(b"0123456789abcdef").unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["slice", tmpfile.path().to_str().unwrap(), "0xa:+2,2:4,-2"])
+        .assert()
+        .stdout("ab23ef")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["slice", "-k", "3", "-", "1"])
+        .write_stdin("0123456789abcdef")
+        .assert()
+        .stdout("159d")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["slice", "-t", "2", "-k", "6", "-", "0:+0xc,0xc"])
+        .write_stdin("0123456789abcdef")
+        .assert()
+        .stdout("0189cd")
+        .success();
+
+    // Ranges must be ordered on stdin
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["slice", "-", "4:6,2:4"])
+        .write_stdin("0123456789abcdef")
+        .assert()
+        .stderr(contains("increasing order"))
+        .failure();
+}
+
+// PatchApplet CLI tests
+
+#[test]
+fn test_patch_cli_file() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(b"0123456789").unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-x", "4142", path, "0x2"])
+        .assert()
+        .stdout("")
+        .success();
+    assert_eq!(std::fs::read(path).unwrap(), b"01AB456789");
+
+    // Data from stdin, inserted
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-i", "--", path, "-1"])
+        .write_stdin("XY")
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(path).unwrap(), b"01AB45678XY9");
+
+    // Past end of file
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-x", "4142", "--", path, "-1"])
+        .assert()
+        .stderr(contains("--extend"))
+        .failure();
+
+    let out_file = NamedTempFile::new().unwrap();
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-r", "4142", "-x", "00", "-c", "1", "-o"])
+        .args([out_file.path().to_str().unwrap(), path])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read(out_file.path()).unwrap(), b"01\x0045678XY9");
+    assert_eq!(std::fs::read(path).unwrap(), b"01AB45678XY9");
+}
+
+#[test]
+fn test_patch_cli_stdin() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-r", "61", "-x", "4141", "-a", "-"])
+        .write_stdin("abcabc")
+        .assert()
+        .stdout("AAbcAAbc")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["patch", "-r", "61", "-x", "41", "-c", "3", "-"])
+        .write_stdin("abcabc")
+        .assert()
+        .stderr(contains("Pattern found 2 times instead of 3"))
+        .failure();
+}
+
+// SplitApplet CLI tests
+
+#[test]
+fn test_split_cli() {
+    let out_dir = TempDir::new().unwrap();
+    let template = out_dir.path().join("{n}_{offset}.bin");
+    let template = template.to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "split", "-x", "-p", "68737173", "-o", template, "-m", "-", "-",
+        ])
+        .write_stdin("headerhsqs1234hsqs56")
+        .assert()
+        .stdout(format!(
+            "0x0 6 {}\n0x6 8 {}\n0xe 6 {}\n",
+            template.replace("{n}_{offset}", "0000_00000000"),
+            template.replace("{n}_{offset}", "0001_00000006"),
+            template.replace("{n}_{offset}", "0002_0000000e")
+        ))
+        .success();
+    assert_eq!(
+        std::fs::read(out_dir.path().join("0000_00000000.bin")).unwrap(),
+        b"header"
+    );
+    assert_eq!(
+        std::fs::read(out_dir.path().join("0002_0000000e.bin")).unwrap(),
+        b"hsqs56"
+    );
+
+    let mut offsets = NamedTempFile::new().unwrap();
+    offsets.write_all(b"0x4\n").unwrap();
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["split", "-O", offsets.path().to_str().unwrap()])
+        .args(["-o", template, "-"])
+        .write_stdin("abcdefgh")
+        .assert()
+        .stdout("")
+        .success();
+    assert_eq!(
+        std::fs::read(out_dir.path().join("0001_00000004.bin")).unwrap(),
+        b"efgh"
+    );
+}
+
 // Escape/UnEscape CLI tests
 
 #[test]
@@ -490,13 +916,59 @@ fn test_key_file_cli_stdin() {
         .success();
 }
 
+// EntropyApplet CLI tests
+
+#[test]
+fn test_entropy_cli_windows() {
+    let mut data = vec![0u8; 1024];
+    data.extend((0..4096u32).map(|i| (i % 256) as u8));
+    data.extend([0u8; 1024]);
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["entropy", "-b", "1024", "-a", "0.9"])
+        .write_stdin(data.clone())
+        .assert()
+        .stdout("0x400-0x1400 1.000")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["entropy", "-b", "2048", "-g"])
+        .write_stdin(data)
+        .assert()
+        .stdout(contains("0x00000800 1.000 |####"))
+        .success();
+}
+
+#[test]
+fn test_stats_cli() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["stats", "-j"])
+        .write_stdin("aaaa")
+        .assert()
+        .stdout(contains("\"size\": 4, \"entropy\": 0.000000,"))
+        .stdout(contains("\"serial_correlation\": null"))
+        .stdout(contains("\"most_common\": [[97, 4]]"))
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["stats"])
+        .write_stdin("")
+        .assert()
+        .stderr(contains("No data"))
+        .failure();
+}
+
 // BgrepApplet CLI tests
 
 #[test]
 fn test_bgrep_cli() {
     let mut data: [u8; 10] = [0; 10];
-    for i in 0..10 {
-        data[i] = i as u8;
+    for (i, d) in data.iter_mut().enumerate() {
+        *d = i as u8;
     }
 
     let mut tmpfile = NamedTempFile::new().unwrap();
@@ -556,3 +1028,529 @@ fn test_bgrep_recursive() {
         .stdout(contains(": 0x1\n"))
         .success();
 }
+
+#[test]
+fn test_bgrep_cli_wildcards() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile
+        .write_all(b"\x7fELF\x02\x01\x01\x00\x7fELF\x01\x02")
+        .unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-x", "7f 4? ?? [1] (01|02) 01", path])
+        .assert()
+        .stdout("0x0\n")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-x", "7f 45 4", path])
+        .assert()
+        .stderr(contains(
+            "Incomplete byte in hex pattern:\n  7f 45 4\n        ^",
+        ))
+        .failure();
+}
+
+#[test]
+fn test_bgrep_cli_output() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(b"xxxxELF\x01header ELF\x02").unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-B", "2", "-A", "1", "-m", "1", "ELF", path])
+        .assert()
+        .stdout("0x4\n00000002  78 78 45 4c 46 01                                 |xxELF.|\n")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-p", "-g", "E(?<l>L)F(.)", path])
+        .assert()
+        .stdout(
+            "0x4: 454c4601\n  l @ 0x5: 4c\n  2 @ 0x7: 01\n\
+             0xf: 454c4602\n  l @ 0x10: 4c\n  2 @ 0x12: 02\n",
+        )
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "--raw", "ELF.", path])
+        .assert()
+        .stdout(&b"ELF\x01ELF\x02"[..])
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-c", "ELF", path])
+        .assert()
+        .stdout("2\n")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-l", "ELF", path, "Cargo.toml"])
+        .assert()
+        .stdout(format!("{}\n", path))
+        .success();
+}
+
+#[test]
+fn test_bgrep_cli_patterns_file() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(b"MZ\x90\x00 PK\x03\x04 \x7fELF").unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    let mut patterns = NamedTempFile::new().unwrap();
+    patterns
+        .write_all(b"# signatures\n4d5a\nregex:PK\\x03\\x04\n7f 45 4c 46\n")
+        .unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "bgrep",
+            "-x",
+            "-p",
+            "-f",
+            patterns.path().to_str().unwrap(),
+            path,
+        ])
+        .assert()
+        .stdout(
+            "0x0 [4d5a]: 4d5a\n0x5 [regex:PK\\x03\\x04]: 504b0304\n0xa [7f 45 4c 46]: 7f454c46\n",
+        )
+        .success();
+
+    // Word boundaries see the data before the match, and all the patterns
+    // matching at an offset are reported
+    let mut patterns = NamedTempFile::new().unwrap();
+    patterns.write_all(b"\\bbar\nba\n").unwrap();
+    for (data, expected) in [
+        (&b"foobar"[..], "0x3 [ba]\n"),
+        (b"foo bar", "0x4 [\\bbar]\n0x4 [ba]\n"),
+    ] {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.write_all(data).unwrap();
+        Command::cargo_bin("rsbkb")
+            .expect("Could not run binary")
+            .args([
+                "bgrep",
+                "-f",
+                patterns.path().to_str().unwrap(),
+                tmpfile.path().to_str().unwrap(),
+            ])
+            .assert()
+            .stdout(expected)
+            .success();
+    }
+}
+
+#[test]
+fn test_bgrep_cli_encodings() {
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile
+        .write_all(b"password=\x00P\x00a\x00s\x00s\x00")
+        .unwrap();
+    let path = tmpfile.path().to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-e", "utf16le", "-i", "PASS", path])
+        .assert()
+        .stdout("0xa [utf16le]\n")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bgrep", "-e", "utf8,utf16le", "-i", "-p", "pass", path])
+        .assert()
+        .stdout("0x0 [utf8]: 70617373\n0xa [utf16le]: 5000610073007300\n")
+        .success();
+}
+
+// ZScanApplet CLI tests
+
+#[test]
+fn test_zscan_cli() {
+    let compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["deflate", "-g"])
+        .write_stdin("some text which is long enough to be found")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let mut data = b"garbage".to_vec();
+    data.extend(&compressed);
+    data.extend(b"garbage");
+
+    let mut tmpfile = NamedTempFile::new().unwrap();
+    tmpfile.write_all(&data).unwrap();
+    let out_dir = TempDir::new().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "zscan",
+            "-o",
+            out_dir.path().to_str().unwrap(),
+            tmpfile.path().to_str().unwrap(),
+        ])
+        .assert()
+        .stdout(contains(format!(
+            "0x7: gzip, {} -> 42 bytes",
+            compressed.len()
+        )))
+        .success();
+    assert_eq!(
+        std::fs::read(out_dir.path().join("00000007.gzip")).unwrap(),
+        b"some text which is long enough to be found"
+    );
+}
+
+// CarveApplet CLI tests
+
+#[test]
+fn test_carve_cli() {
+    let compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["deflate", "-g"])
+        .write_stdin("carved data")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let mut data = b"garbage".to_vec();
+    data.extend(&compressed);
+    data.extend(b"garbage\x1f\x8b\x08\x00truncated");
+    let out_dir = TempDir::new().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["carve", "-o", out_dir.path().to_str().unwrap(), "-"])
+        .write_stdin(data.clone())
+        .assert()
+        .stdout(format!(
+            "0x7: gzip, {} bytes\n0x{:x}: gzip\n",
+            compressed.len(),
+            data.len() - 13
+        ))
+        .success();
+    assert_eq!(
+        std::fs::read(out_dir.path().join("00000007.gz")).unwrap(),
+        compressed
+    );
+}
+
+// BdiffApplet CLI tests
+
+#[test]
+fn test_bdiff_cli() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    file1.write_all(b"0123456789abcdefghijklmnop").unwrap();
+    let path1 = file1.path().to_str().unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bdiff", path1, "-"])
+        .write_stdin("0123XX6789abcdefghijkl\x00\x01mnop")
+        .assert()
+        .stdout(format!(
+            "0x4-0x6 -> 0x4-0x6: 2 bytes changed\n  \
+             00000004  34 35                    |45      |  \
+             00000004  58 58                    |XX      |\n\
+             0x16 -> 0x16-0x18: 2 bytes inserted\n\
+             {}00000016  00 01                    |..      |\n",
+            " ".repeat(49)
+        ))
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["bdiff", "-s", path1, "-"])
+        .write_stdin("0123XX6789abcdefghijklmnop")
+        .assert()
+        .stdout(contains("1 differing ranges, 2 bytes changed (7.69%)"))
+        .success();
+}
+
+// Inflate/Deflate CLI tests
+
+#[test]
+fn test_deflate_inflate_cli_stream() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+
+    let compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["deflate", "-z"])
+        .write_stdin(data.clone())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["inflate", "-z"])
+        .write_stdin(compressed)
+        .assert()
+        .stdout(data)
+        .success();
+}
+
+#[test]
+fn test_inflate_cli_trunc() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["inflate"])
+        .write_stdin(&b"\x2b\x49\x2d\x2e\x29"[..])
+        .assert()
+        .stdout("test")
+        .stderr(contains("Decompression error"))
+        .success();
+}
+
+#[test]
+fn test_deflate_inflate_cli_gzip() {
+    let compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["deflate", "-g", "-n", "test.txt", "-m", "1234"])
+        .write_stdin("testtest\n")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(compressed.starts_with(b"\x1f\x8b\x08\x08\xd2\x04\x00\x00"));
+
+    // Two members, detected as gzip
+    let mut members = compressed.clone();
+    members.extend(compressed);
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["inflate"])
+        .write_stdin(members)
+        .assert()
+        .stdout("testtest\ntesttest\n")
+        .stderr("")
+        .success();
+}
+
+#[test]
+fn test_inflate_cli_bad_checksum() {
+    let mut compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["deflate", "-z"])
+        .write_stdin("testtest\n")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    *compressed.last_mut().unwrap() ^= 1;
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["inflate"])
+        .write_stdin(compressed)
+        .assert()
+        .stdout("testtest\n")
+        .stderr(contains("Adler-32 mismatch"))
+        .success();
+}
+
+// Compress/Decompress CLI tests
+
+#[test]
+fn test_compress_decompress_cli() {
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    for codec in ["xz", "zstd", "brotli", "lzma-raw"] {
+        let compressed = Command::cargo_bin("rsbkb")
+            .expect("Could not run binary")
+            .args(["compress", "-c", codec])
+            .write_stdin(data.clone())
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+
+        let mut args = vec!["decompress"];
+        if codec == "brotli" || codec == "lzma-raw" {
+            args.extend(["-c", codec]);
+        }
+        Command::cargo_bin("rsbkb")
+            .expect("Could not run binary")
+            .args(args)
+            .write_stdin(compressed)
+            .assert()
+            .stdout(data.clone())
+            .success();
+    }
+}
+
+#[test]
+fn test_decompress_cli_trunc() {
+    let data: Vec<u8> = (0..200_000u64).map(|i| (i * i % 251) as u8).collect();
+    let compressed = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["compress", "-c", "lzma-raw"])
+        .write_stdin(data.clone())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["decompress", "-c", "lzma-raw"])
+        .write_stdin(&compressed[..compressed.len() / 2])
+        .assert()
+        .stderr(contains("Decompression error"))
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(!output.is_empty());
+    assert!(data.starts_with(&output));
+}
+
+#[test]
+fn test_decompress_cli_unknown() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["decompress", "test"])
+        .assert()
+        .stderr(contains("unknown compression format"))
+        .failure();
+}
+
+// Chain CLI tests
+
+#[test]
+fn test_chain_cli() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["chain", "unhex", "inflate", "b64 -u"])
+        .write_stdin("2b492d2e2901622e00")
+        .assert()
+        .stdout("dGVzdHRlc3QK")
+        .success();
+}
+
+#[test]
+fn test_chain_cli_error() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["chain", "unhex", "unhex -s"])
+        .write_stdin("6c")
+        .assert()
+        .stdout("")
+        .stderr(contains("stage 2 (unhex) failed"))
+        .failure();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["chain", "hex", "xor -x 41 ABC"])
+        .write_stdin("x")
+        .assert()
+        .stdout("")
+        .stderr(contains(
+            "stage 2 (xor) takes its data from the previous stage",
+        ))
+        .failure();
+}
+
+// MagicApplet CLI tests
+
+#[test]
+fn test_magic_cli() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["magic", "eJwrSS0uKUHCXABCfwcL"])
+        .assert()
+        .stdout(predicates::str::starts_with(
+            "d64 -> inflate -z -> text (entropy 0.22, 17 bytes)\n",
+        ))
+        .success();
+}
+
+#[test]
+fn test_magic_cli_best() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["magic", "-b"])
+        .write_stdin("eJwrSS0uKUHCXABCfwcL\n")
+        .assert()
+        .stdout("testtesttesttest\n")
+        .success();
+}
+
+#[test]
+fn test_magic_cli_depth() {
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["magic", "-d", "9", "eJwrSS0uKUHCXABCfwcL"])
+        .assert()
+        .stderr(contains("9 is not in 1..=8"))
+        .failure();
+}
+
+// Recipe CLI tests
+
+#[test]
+fn test_recipe_cli() {
+    let mut recipe = NamedTempFile::new().unwrap();
+    recipe
+        .write_all(b"# decode, xor then encode\nunhex\nxor -x $KEY\n\nb64\n")
+        .unwrap();
+    let mut input = NamedTempFile::new().unwrap();
+    input.write_all(b"414243").unwrap();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "recipe",
+            "run",
+            "-p",
+            "KEY=01",
+            recipe.path().to_str().unwrap(),
+        ])
+        .write_stdin("414243")
+        .assert()
+        .stdout("QENC")
+        .success();
+
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args([
+            "recipe",
+            "run",
+            "-p",
+            "KEY=01",
+            "-i",
+            input.path().to_str().unwrap(),
+            recipe.path().to_str().unwrap(),
+        ])
+        .assert()
+        .stdout("QENC")
+        .success();
+
+    // Missing parameter
+    Command::cargo_bin("rsbkb")
+        .expect("Could not run binary")
+        .args(["recipe", "run", recipe.path().to_str().unwrap()])
+        .write_stdin("414243")
+        .assert()
+        .stderr(contains("parameter \"KEY\" is not defined"))
+        .failure();
+}